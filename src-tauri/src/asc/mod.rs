@@ -0,0 +1,660 @@
+mod parser;
+
+use serde::Serialize;
+use std::fmt;
+
+pub use parser::ParseError;
+pub(crate) use parser::{parse_shape, parse_symattr, parse_window};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default, Serialize)]
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Point {
+    pub fn new(x: i32, y: i32) -> Self {
+        Self { x, y }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize)]
+pub enum Rotation {
+    #[default]
+    R0,
+    R90,
+    R180,
+    R270,
+    M0,
+    M90,
+    M180,
+    M270,
+}
+
+impl Rotation {
+    pub fn parse(s: &str) -> Option<Self> {
+        Some(match s {
+            "R0" => Self::R0,
+            "R90" => Self::R90,
+            "R180" => Self::R180,
+            "R270" => Self::R270,
+            "M0" => Self::M0,
+            "M90" => Self::M90,
+            "M180" => Self::M180,
+            "M270" => Self::M270,
+            _ => return None,
+        })
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::R0 => "R0",
+            Self::R90 => "R90",
+            Self::R180 => "R180",
+            Self::R270 => "R270",
+            Self::M0 => "M0",
+            Self::M90 => "M90",
+            Self::M180 => "M180",
+            Self::M270 => "M270",
+        }
+    }
+
+    pub fn is_mirrored(self) -> bool {
+        matches!(self, Self::M0 | Self::M90 | Self::M180 | Self::M270)
+    }
+
+    /// Maps a pin offset given in the symbol's R0 frame into the placed
+    /// orientation. Mirroring flips x before rotating.
+    pub fn apply(self, offset: Point) -> Point {
+        let Point { x, y } = offset;
+        let (x, y) = if self.is_mirrored() { (-x, y) } else { (x, y) };
+        match self {
+            Self::R0 | Self::M0 => Point::new(x, y),
+            Self::R90 | Self::M90 => Point::new(-y, x),
+            Self::R180 | Self::M180 => Point::new(-x, -y),
+            Self::R270 | Self::M270 => Point::new(y, -x),
+        }
+    }
+}
+
+impl fmt::Display for Rotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Original spelling of a line whose whitespace or number formatting differs
+/// from the canonical form. It is written back only while the parsed fields
+/// still produce the same canonical line, and never takes part in equality.
+#[derive(Debug, Clone, Default)]
+pub struct Source(Option<Box<(String, String)>>);
+
+impl Source {
+    fn capture(raw: &str, canonical: String) -> Self {
+        if raw == canonical {
+            Self(None)
+        } else {
+            Self(Some(Box::new((raw.to_string(), canonical))))
+        }
+    }
+
+    fn write(&self, f: &mut fmt::Formatter<'_>, canonical: String) -> fmt::Result {
+        match self.0.as_deref() {
+            Some((raw, original)) if *original == canonical => f.write_str(raw),
+            _ => f.write_str(&canonical),
+        }
+    }
+}
+
+impl PartialEq for Source {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Sheet {
+    pub number: i32,
+    pub width: i32,
+    pub height: i32,
+    #[serde(skip)]
+    source: Source,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Wire {
+    pub start: Point,
+    pub end: Point,
+    #[serde(skip)]
+    source: Source,
+}
+
+impl Wire {
+    pub fn new(start: Point, end: Point) -> Self {
+        Self {
+            start,
+            end,
+            source: Source::default(),
+        }
+    }
+
+    pub fn is_axis_aligned(&self) -> bool {
+        self.start.x == self.end.x || self.start.y == self.end.y
+    }
+
+    /// True if `p` lies on the segment, endpoints included.
+    pub fn contains(&self, p: Point) -> bool {
+        let (a, b) = (self.start, self.end);
+        let cross =
+            (b.x - a.x) as i64 * (p.y - a.y) as i64 - (b.y - a.y) as i64 * (p.x - a.x) as i64;
+        cross == 0
+            && p.x >= a.x.min(b.x)
+            && p.x <= a.x.max(b.x)
+            && p.y >= a.y.min(b.y)
+            && p.y <= a.y.max(b.y)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Flag {
+    pub at: Point,
+    pub label: String,
+    #[serde(skip)]
+    source: Source,
+}
+
+impl Flag {
+    pub fn new(at: Point, label: impl Into<String>) -> Self {
+        Self {
+            at,
+            label: label.into(),
+            source: Source::default(),
+        }
+    }
+
+    pub fn is_ground(&self) -> bool {
+        self.label == "0"
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IoPin {
+    pub at: Point,
+    pub direction: String,
+    #[serde(skip)]
+    source: Source,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Window {
+    pub id: i32,
+    pub offset: Point,
+    pub align: String,
+    pub size: i32,
+    #[serde(skip)]
+    source: Source,
+}
+
+impl Window {
+    pub fn new(id: i32, offset: Point, align: impl Into<String>, size: i32) -> Self {
+        Self {
+            id,
+            offset,
+            align: align.into(),
+            size,
+            source: Source::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Attr {
+    pub key: String,
+    pub value: String,
+    #[serde(skip)]
+    source: Source,
+}
+
+impl Attr {
+    pub fn new(key: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            value: value.into(),
+            source: Source::default(),
+        }
+    }
+}
+
+/// A `WINDOW` or `SYMATTR` line under a `SYMBOL`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SymbolLine {
+    Window(Window),
+    Attr(Attr),
+}
+
+/// A `SYMBOL` line together with the `WINDOW` and `SYMATTR` lines that follow
+/// it, in their original order.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Symbol {
+    pub name: String,
+    pub at: Point,
+    pub rotation: Rotation,
+    pub lines: Vec<SymbolLine>,
+    #[serde(skip)]
+    source: Source,
+}
+
+impl Symbol {
+    pub fn new(name: impl Into<String>, at: Point, rotation: Rotation) -> Self {
+        Self {
+            name: name.into(),
+            at,
+            rotation,
+            lines: Vec::new(),
+            source: Source::default(),
+        }
+    }
+
+    pub fn windows(&self) -> impl Iterator<Item = &Window> {
+        self.lines.iter().filter_map(|line| match line {
+            SymbolLine::Window(w) => Some(w),
+            _ => None,
+        })
+    }
+
+    pub fn attrs(&self) -> impl Iterator<Item = &Attr> {
+        self.lines.iter().filter_map(|line| match line {
+            SymbolLine::Attr(a) => Some(a),
+            _ => None,
+        })
+    }
+
+    pub fn attr(&self, key: &str) -> Option<&str> {
+        self.attrs()
+            .find(|a| a.key == key)
+            .map(|a| a.value.as_str())
+    }
+
+    /// Changes an attribute in place, or appends it after the existing lines.
+    pub fn set_attr(&mut self, key: &str, value: impl Into<String>) {
+        let existing = self.lines.iter_mut().find_map(|line| match line {
+            SymbolLine::Attr(a) if a.key == key => Some(a),
+            _ => None,
+        });
+        match existing {
+            Some(attr) => attr.value = value.into(),
+            None => self.lines.push(SymbolLine::Attr(Attr::new(key, value))),
+        }
+    }
+
+    pub fn inst_name(&self) -> Option<&str> {
+        self.attr("InstName")
+    }
+
+    /// Last path component of the symbol name, e.g. `opamp2` for `Opamps\opamp2`.
+    pub fn base_name(&self) -> &str {
+        self.name.rsplit(['\\', '/']).next().unwrap_or(&self.name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Text {
+    pub at: Point,
+    pub align: String,
+    pub size: i32,
+    /// Text including its leading `!` (directive) or `;` (comment) marker.
+    pub content: String,
+    #[serde(skip)]
+    source: Source,
+}
+
+impl Text {
+    pub fn new(at: Point, align: impl Into<String>, size: i32, content: impl Into<String>) -> Self {
+        Self {
+            at,
+            align: align.into(),
+            size,
+            content: content.into(),
+            source: Source::default(),
+        }
+    }
+
+    pub fn is_directive(&self) -> bool {
+        self.content.starts_with('!')
+    }
+
+    /// Directive or comment text without its marker. LTspice stores line
+    /// breaks inside a TEXT block as a literal `\n`.
+    pub fn body(&self) -> String {
+        self.content
+            .strip_prefix(['!', ';'])
+            .unwrap_or(&self.content)
+            .replace("\\n", "\n")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ShapeKind {
+    Line,
+    Rectangle,
+    Circle,
+    Arc,
+}
+
+impl ShapeKind {
+    pub fn keyword(self) -> &'static str {
+        match self {
+            Self::Line => "LINE",
+            Self::Rectangle => "RECTANGLE",
+            Self::Circle => "CIRCLE",
+            Self::Arc => "ARC",
+        }
+    }
+
+    fn point_count(self) -> usize {
+        match self {
+            Self::Arc => 4,
+            _ => 2,
+        }
+    }
+}
+
+/// Free-standing graphics: `LINE`, `RECTANGLE`, `CIRCLE` and `ARC`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Shape {
+    pub kind: ShapeKind,
+    pub pen: String,
+    pub points: Vec<Point>,
+    pub style: Option<i32>,
+    #[serde(skip)]
+    source: Source,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BusTap {
+    pub start: Point,
+    pub end: Point,
+    #[serde(skip)]
+    source: Source,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Item {
+    Version {
+        version: String,
+        #[serde(skip)]
+        source: Source,
+    },
+    Sheet(Sheet),
+    Wire(Wire),
+    Flag(Flag),
+    IoPin(IoPin),
+    Symbol(Symbol),
+    Text(Text),
+    Shape(Shape),
+    BusTap(BusTap),
+    /// Any line this parser does not model (e.g. `DATAFLAG`), kept verbatim.
+    Other {
+        line: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::CrLf => "\r\n",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+pub struct Schematic {
+    pub items: Vec<Item>,
+    pub line_ending: LineEnding,
+    pub trailing_newline: bool,
+}
+
+impl Schematic {
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        parser::parse(text)
+    }
+
+    pub fn version(&self) -> Option<&str> {
+        self.items.iter().find_map(|item| match item {
+            Item::Version { version, .. } => Some(version.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn sheet(&self) -> Option<&Sheet> {
+        self.items.iter().find_map(|item| match item {
+            Item::Sheet(s) => Some(s),
+            _ => None,
+        })
+    }
+
+    pub fn wires(&self) -> impl Iterator<Item = &Wire> {
+        self.items.iter().filter_map(|item| match item {
+            Item::Wire(w) => Some(w),
+            _ => None,
+        })
+    }
+
+    pub fn flags(&self) -> impl Iterator<Item = &Flag> {
+        self.items.iter().filter_map(|item| match item {
+            Item::Flag(f) => Some(f),
+            _ => None,
+        })
+    }
+
+    pub fn symbols(&self) -> impl Iterator<Item = &Symbol> {
+        self.items.iter().filter_map(|item| match item {
+            Item::Symbol(s) => Some(s),
+            _ => None,
+        })
+    }
+
+    pub fn symbols_mut(&mut self) -> impl Iterator<Item = &mut Symbol> {
+        self.items.iter_mut().filter_map(|item| match item {
+            Item::Symbol(s) => Some(s),
+            _ => None,
+        })
+    }
+
+    pub fn texts(&self) -> impl Iterator<Item = &Text> {
+        self.items.iter().filter_map(|item| match item {
+            Item::Text(t) => Some(t),
+            _ => None,
+        })
+    }
+
+    pub fn directives(&self) -> impl Iterator<Item = &Text> {
+        self.texts().filter(|t| t.is_directive())
+    }
+
+    pub fn symbol(&self, inst_name: &str) -> Option<&Symbol> {
+        self.symbols().find(|s| s.inst_name() == Some(inst_name))
+    }
+
+    pub fn symbol_mut(&mut self, inst_name: &str) -> Option<&mut Symbol> {
+        self.symbols_mut()
+            .find(|s| s.inst_name() == Some(inst_name))
+    }
+}
+
+fn join_points(points: &[Point]) -> String {
+    points
+        .iter()
+        .map(|p| format!("{} {}", p.x, p.y))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+impl Item {
+    fn write_lines(&self, f: &mut fmt::Formatter<'_>, eol: &str) -> fmt::Result {
+        match self {
+            Item::Version { version, source } => source.write(f, format!("Version {}", version)),
+            Item::Sheet(s) => s
+                .source
+                .write(f, format!("SHEET {} {} {}", s.number, s.width, s.height)),
+            Item::Wire(w) => w
+                .source
+                .write(f, format!("WIRE {}", join_points(&[w.start, w.end]))),
+            Item::Flag(fl) => fl
+                .source
+                .write(f, format!("FLAG {} {} {}", fl.at.x, fl.at.y, fl.label)),
+            Item::IoPin(p) => p
+                .source
+                .write(f, format!("IOPIN {} {} {}", p.at.x, p.at.y, p.direction)),
+            Item::Symbol(s) => {
+                s.source.write(
+                    f,
+                    format!("SYMBOL {} {} {} {}", s.name, s.at.x, s.at.y, s.rotation),
+                )?;
+                for line in &s.lines {
+                    f.write_str(eol)?;
+                    match line {
+                        SymbolLine::Window(w) => w.source.write(
+                            f,
+                            format!(
+                                "WINDOW {} {} {} {} {}",
+                                w.id, w.offset.x, w.offset.y, w.align, w.size
+                            ),
+                        )?,
+                        SymbolLine::Attr(a) => {
+                            let canonical = if a.value.is_empty() {
+                                format!("SYMATTR {}", a.key)
+                            } else {
+                                format!("SYMATTR {} {}", a.key, a.value)
+                            };
+                            a.source.write(f, canonical)?
+                        }
+                    }
+                }
+                Ok(())
+            }
+            Item::Text(t) => t.source.write(
+                f,
+                format!(
+                    "TEXT {} {} {} {} {}",
+                    t.at.x, t.at.y, t.align, t.size, t.content
+                ),
+            ),
+            Item::Shape(s) => {
+                let mut canonical =
+                    format!("{} {} {}", s.kind.keyword(), s.pen, join_points(&s.points));
+                if let Some(style) = s.style {
+                    canonical.push_str(&format!(" {}", style));
+                }
+                s.source.write(f, canonical)
+            }
+            Item::BusTap(b) => b
+                .source
+                .write(f, format!("BUSTAP {}", join_points(&[b.start, b.end]))),
+            Item::Other { line } => f.write_str(line),
+        }
+    }
+}
+
+/// Serializes back to .asc text. An unmodified parse result reproduces its
+/// input byte for byte.
+impl fmt::Display for Schematic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let eol = self.line_ending.as_str();
+        for (i, item) in self.items.iter().enumerate() {
+            if i > 0 {
+                f.write_str(eol)?;
+            }
+            item.write_lines(f, eol)?;
+        }
+        if self.trailing_newline && !self.items.is_empty() {
+            f.write_str(eol)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::textfile;
+
+    const RC_FILTER: &str = include_str!("../../tests/fixtures/asc/rc_filter.asc");
+    const INVERTING_AMP: &str = include_str!("../../tests/fixtures/asc/inverting_amp.asc");
+    const LC_UTF16: &[u8] = include_bytes!("../../tests/fixtures/asc/lc_utf16.asc");
+
+    fn round_trip(text: &str) -> String {
+        Schematic::parse(text).unwrap().to_string()
+    }
+
+    #[test]
+    fn round_trips_lf_file() {
+        assert_eq!(round_trip(RC_FILTER), RC_FILTER);
+    }
+
+    #[test]
+    fn round_trips_crlf_file() {
+        let schematic = Schematic::parse(INVERTING_AMP).unwrap();
+        assert_eq!(schematic.line_ending, LineEnding::CrLf);
+        assert_eq!(schematic.to_string(), INVERTING_AMP);
+    }
+
+    #[test]
+    fn round_trips_utf16_file() {
+        let file = textfile::decode(LC_UTF16, None);
+        assert_eq!(file.format.encoding, textfile::Encoding::Utf16Le);
+        let written = round_trip(&file.content);
+        assert_eq!(textfile::encode(&written, file.format), LC_UTF16);
+    }
+
+    #[test]
+    fn keeps_window_and_symattr_lines_in_order() {
+        let schematic = Schematic::parse(INVERTING_AMP).unwrap();
+        let u1 = schematic.symbol("U1").unwrap();
+        let order: Vec<&str> = u1
+            .lines
+            .iter()
+            .map(|line| match line {
+                SymbolLine::Window(_) => "WINDOW",
+                SymbolLine::Attr(a) => a.key.as_str(),
+            })
+            .collect();
+        assert_eq!(
+            order,
+            [
+                "InstName",
+                "WINDOW",
+                "Value",
+                "Prefix",
+                "SpiceModel",
+                "WINDOW"
+            ]
+        );
+        assert_eq!(u1.windows().count(), 2);
+    }
+
+    #[test]
+    fn edits_only_change_their_own_line() {
+        let mut schematic = Schematic::parse(RC_FILTER).unwrap();
+        schematic.symbol_mut("R1").unwrap().set_attr("Value", "2k2");
+        let expected = RC_FILTER.replace("SYMATTR Value 1k\n", "SYMATTR Value 2k2\n");
+        assert_eq!(schematic.to_string(), expected);
+    }
+
+    #[test]
+    fn keeps_irregular_spacing_and_unknown_lines() {
+        let text = round_trip(INVERTING_AMP);
+        assert!(text.contains("WIRE  -32 64 -96 64\r\n"));
+        assert!(text.contains("DATAFLAG 192 80 \"\"\r\n"));
+    }
+}
@@ -0,0 +1,260 @@
+use super::{
+    Attr, BusTap, Flag, IoPin, Item, LineEnding, Point, Rotation, Schematic, Shape, ShapeKind,
+    Sheet, Source, Symbol, SymbolLine, Text, Window, Wire,
+};
+
+#[derive(Debug, thiserror::Error)]
+#[error("line {line}: {message}")]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+/// Splits off the first `n` whitespace-separated fields and returns them with
+/// the remainder of the line (leading whitespace removed).
+fn split_fields(line: &str, n: usize) -> Option<(Vec<&str>, &str)> {
+    let mut fields = Vec::with_capacity(n);
+    let mut rest = line;
+    for _ in 0..n {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            return None;
+        }
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        fields.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+    // Only the single separator after the last field belongs to the syntax;
+    // anything beyond it is part of the free-text value.
+    let rest = rest.strip_prefix([' ', '\t']).unwrap_or(rest);
+    Some((fields, rest))
+}
+
+struct LineParser<'a> {
+    number: usize,
+    raw: &'a str,
+}
+
+impl<'a> LineParser<'a> {
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        ParseError {
+            line: self.number,
+            message: message.into(),
+        }
+    }
+
+    fn fields(&self, n: usize) -> Result<(Vec<&'a str>, &'a str), ParseError> {
+        split_fields(self.raw, n)
+            .ok_or_else(|| self.error(format!("expected {} fields after keyword", n - 1)))
+    }
+
+    fn int(&self, s: &str) -> Result<i32, ParseError> {
+        s.parse()
+            .map_err(|_| self.error(format!("invalid integer '{}'", s)))
+    }
+
+    fn point(&self, x: &str, y: &str) -> Result<Point, ParseError> {
+        Ok(Point::new(self.int(x)?, self.int(y)?))
+    }
+
+    fn source(&self, canonical: String) -> Source {
+        Source::capture(self.raw, canonical)
+    }
+}
+
+pub(crate) fn parse_shape(kind: ShapeKind, raw: &str, number: usize) -> Result<Shape, ParseError> {
+    let p = LineParser { number, raw };
+    let coords = kind.point_count() * 2;
+    let (f, rest) = p.fields(2 + coords)?;
+    let points = f[2..]
+        .chunks(2)
+        .map(|c| p.point(c[0], c[1]))
+        .collect::<Result<Vec<_>, _>>()?;
+    let style = match rest.trim() {
+        "" => None,
+        s => Some(p.int(s)?),
+    };
+    let mut shape = Shape {
+        kind,
+        pen: f[1].to_string(),
+        points,
+        style,
+        source: Source::default(),
+    };
+    shape.source = p.source(canonical_line(&Item::Shape(shape.clone())));
+    Ok(shape)
+}
+
+pub(crate) fn parse_window(raw: &str, number: usize) -> Result<Window, ParseError> {
+    let p = LineParser { number, raw };
+    let (f, rest) = p.fields(5)?;
+    let size = p.int(rest.trim())?;
+    let mut window = Window::new(p.int(f[1])?, p.point(f[2], f[3])?, f[4], size);
+    window.source = p.source(format!(
+        "WINDOW {} {} {} {} {}",
+        window.id, window.offset.x, window.offset.y, window.align, window.size
+    ));
+    Ok(window)
+}
+
+pub(crate) fn parse_symattr(raw: &str, number: usize) -> Result<Attr, ParseError> {
+    let p = LineParser { number, raw };
+    let (f, rest) = p.fields(2)?;
+    let mut attr = Attr::new(f[1], rest);
+    attr.source = p.source(if rest.is_empty() {
+        format!("SYMATTR {}", attr.key)
+    } else {
+        format!("SYMATTR {} {}", attr.key, attr.value)
+    });
+    Ok(attr)
+}
+
+fn canonical_line(item: &Item) -> String {
+    let schematic = Schematic {
+        items: vec![item.clone()],
+        line_ending: LineEnding::Lf,
+        trailing_newline: false,
+    };
+    schematic.to_string()
+}
+
+fn parse_line(raw: &str, number: usize) -> Result<Item, ParseError> {
+    let p = LineParser { number, raw };
+    let keyword = raw.split_whitespace().next().unwrap_or("");
+
+    let item = match keyword {
+        "Version" => {
+            let (_, rest) = p.fields(1)?;
+            Item::Version {
+                version: rest.trim().to_string(),
+                source: Source::default(),
+            }
+        }
+        "SHEET" => {
+            let (f, _) = p.fields(4)?;
+            Item::Sheet(Sheet {
+                number: p.int(f[1])?,
+                width: p.int(f[2])?,
+                height: p.int(f[3])?,
+                source: Source::default(),
+            })
+        }
+        "WIRE" => {
+            let (f, _) = p.fields(5)?;
+            Item::Wire(Wire::new(p.point(f[1], f[2])?, p.point(f[3], f[4])?))
+        }
+        "BUSTAP" => {
+            let (f, _) = p.fields(5)?;
+            Item::BusTap(BusTap {
+                start: p.point(f[1], f[2])?,
+                end: p.point(f[3], f[4])?,
+                source: Source::default(),
+            })
+        }
+        "FLAG" => {
+            let (f, rest) = p.fields(3)?;
+            Item::Flag(Flag::new(p.point(f[1], f[2])?, rest.trim_end()))
+        }
+        "IOPIN" => {
+            let (f, rest) = p.fields(3)?;
+            Item::IoPin(IoPin {
+                at: p.point(f[1], f[2])?,
+                direction: rest.trim().to_string(),
+                source: Source::default(),
+            })
+        }
+        "SYMBOL" => {
+            let (f, rest) = p.fields(4)?;
+            let rotation = Rotation::parse(rest.trim())
+                .ok_or_else(|| p.error(format!("invalid rotation '{}'", rest.trim())))?;
+            Item::Symbol(Symbol::new(f[1], p.point(f[2], f[3])?, rotation))
+        }
+        "TEXT" => {
+            let (f, rest) = p.fields(5)?;
+            Item::Text(Text::new(p.point(f[1], f[2])?, f[3], p.int(f[4])?, rest))
+        }
+        "LINE" => Item::Shape(parse_shape(ShapeKind::Line, raw, number)?),
+        "RECTANGLE" => Item::Shape(parse_shape(ShapeKind::Rectangle, raw, number)?),
+        "CIRCLE" => Item::Shape(parse_shape(ShapeKind::Circle, raw, number)?),
+        "ARC" => Item::Shape(parse_shape(ShapeKind::Arc, raw, number)?),
+        _ => Item::Other {
+            line: raw.to_string(),
+        },
+    };
+
+    // Record the original spelling if it differs from what we would write.
+    let canonical = canonical_line(&item);
+    Ok(match item {
+        Item::Version { version, .. } => Item::Version {
+            version,
+            source: p.source(canonical),
+        },
+        Item::Sheet(mut s) => {
+            s.source = p.source(canonical);
+            Item::Sheet(s)
+        }
+        Item::Wire(mut w) => {
+            w.source = p.source(canonical);
+            Item::Wire(w)
+        }
+        Item::BusTap(mut b) => {
+            b.source = p.source(canonical);
+            Item::BusTap(b)
+        }
+        Item::Flag(mut fl) => {
+            fl.source = p.source(canonical);
+            Item::Flag(fl)
+        }
+        Item::IoPin(mut io) => {
+            io.source = p.source(canonical);
+            Item::IoPin(io)
+        }
+        Item::Symbol(mut s) => {
+            s.source = p.source(canonical);
+            Item::Symbol(s)
+        }
+        Item::Text(mut t) => {
+            t.source = p.source(canonical);
+            Item::Text(t)
+        }
+        other => other,
+    })
+}
+
+pub(super) fn parse(text: &str) -> Result<Schematic, ParseError> {
+    let line_ending = match text.find('\n') {
+        Some(i) if i > 0 && text.as_bytes()[i - 1] == b'\r' => LineEnding::CrLf,
+        _ => LineEnding::Lf,
+    };
+
+    let mut lines: Vec<&str> = text.split('\n').collect();
+    let trailing_newline = lines.len() > 1 && lines.last() == Some(&"");
+    if trailing_newline || text.is_empty() {
+        lines.pop();
+    }
+
+    let mut items: Vec<Item> = Vec::new();
+    for (i, line) in lines.into_iter().enumerate() {
+        let number = i + 1;
+        let raw = line.strip_suffix('\r').unwrap_or(line);
+        let keyword = raw.split_whitespace().next().unwrap_or("");
+
+        // WINDOW and SYMATTR lines belong to the SYMBOL above them.
+        if let (Some(Item::Symbol(symbol)), "WINDOW" | "SYMATTR") = (items.last_mut(), keyword) {
+            symbol.lines.push(if keyword == "WINDOW" {
+                SymbolLine::Window(parse_window(raw, number)?)
+            } else {
+                SymbolLine::Attr(parse_symattr(raw, number)?)
+            });
+            continue;
+        }
+
+        items.push(parse_line(raw, number)?);
+    }
+
+    Ok(Schematic {
+        items,
+        line_ending,
+        trailing_newline,
+    })
+}
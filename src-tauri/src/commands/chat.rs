@@ -1,6 +1,26 @@
+use crate::asc::Schematic;
+use crate::commands::edit_history::write_recorded;
+use crate::commands::ltspice::reload_ltspice;
+use crate::commands::proposals::Proposal;
+use crate::commands::schematic::symbol_library;
+use crate::commands::simulation::{run_simulation, select_simulator, simulation_summary};
+use crate::commands::tools;
+use crate::connectivity;
+use crate::goals::{self, GoalResult};
+use crate::llm::{CompletionRequest, Delta, Message, Provider, Reply, ToolCall, Usage};
+use crate::ltspice::ReloadStatus;
+use crate::operations;
+use crate::settings::EditPolicy;
 use crate::state::AppState;
+use crate::symbols::SymbolLibrary;
+use crate::textfile::FormatCache;
+use crate::validation::{self, LineEdit, Rule, Violation};
+use crate::workspace::{self, SCHEMATIC};
 use futures::StreamExt;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use similar::{capture_diff_slices, Algorithm, DiffOp};
+use std::sync::Mutex;
 use tauri::ipc::Channel;
 use tauri::State;
 
@@ -25,31 +45,141 @@ pub enum StreamEvent {
     },
     #[serde(rename = "error")]
     Error { message: String },
+    /// A validated edit waiting for `accept_proposal` or `reject_proposal`.
+    #[serde(rename = "proposal")]
+    Proposal(Proposal),
+    /// The file changed on disk after the model last saw it, and the edit
+    /// could not be merged with those changes; nothing was written.
+    #[serde(rename = "conflict")]
+    Conflict { file: String, message: String },
+    /// The edit was refused before anything was written.
+    #[serde(rename = "edit_rejected")]
+    EditRejected { violations: Vec<Violation> },
+    /// The edit was refused and the model is asked for a corrected one.
+    #[serde(rename = "repairing")]
+    Repairing {
+        attempt: u32,
+        max_attempts: u32,
+        violations: Vec<Violation>,
+    },
+    #[serde(rename = "iteration")]
+    Iteration { iteration: u32, max_iterations: u32 },
+    #[serde(rename = "edits_applied")]
+    EditsApplied {
+        iteration: u32,
+        changes: Vec<FileChange>,
+        explanation: String,
+    },
+    #[serde(rename = "simulated")]
+    Simulated {
+        iteration: u32,
+        success: bool,
+        errors: Vec<String>,
+    },
+    /// The model called a tool; `arguments` is its raw JSON text.
+    #[serde(rename = "tool_call")]
+    ToolCall { name: String, arguments: String },
+    #[serde(rename = "tool_result")]
+    ToolResult { name: String, success: bool },
+    /// Whether LTspice picked up a file the edit wrote.
+    #[serde(rename = "ltspice_reload")]
+    LtspiceReload { file: String, status: ReloadStatus },
+    /// Tokens used by one completion.
+    #[serde(rename = "usage")]
+    Usage(Usage),
+    #[serde(rename = "goals")]
+    Goals {
+        iteration: u32,
+        results: Vec<GoalResult>,
+        all_met: bool,
+    },
 }
 
-#[derive(Serialize, Deserialize)]
-struct ChatMsg {
-    role: String,
-    content: String,
+/// Asks for the active file's latest simulation results to be attached.
+#[derive(Deserialize)]
+pub struct SimulationContext {
+    /// Traces to summarize; the first few node voltages when empty.
+    #[serde(default)]
+    pub traces: Vec<String>,
+}
+
+/// Edit, simulate and verify in a loop until `goals` are met.
+#[derive(Deserialize)]
+pub struct AgentOptions {
+    /// `.meas` comparisons such as `Vout_pp < 50m` are checked by the
+    /// backend; anything else is judged by the model from the results.
+    pub goals: Vec<String>,
+    pub max_iterations: Option<u32>,
+    /// Analysis to run instead of the schematic's own directives.
+    pub analysis: Option<String>,
 }
 
-#[derive(Serialize)]
-struct OpenRouterRequest {
-    model: String,
-    max_tokens: u32,
-    messages: Vec<ChatMsg>,
-    stream: bool,
+#[derive(Deserialize, Default)]
+pub struct ChatOptions {
+    pub simulation: Option<SimulationContext>,
+    pub agent: Option<AgentOptions>,
+    /// Hold edits as a `Proposal` for review instead of writing them. The
+    /// `propose` edit policy does this for every message outside agent mode.
+    #[serde(default)]
+    pub propose: bool,
+    /// Have the model write edits as JSON text instead of calling tools, for
+    /// models without tool support. Agent mode always works this way.
+    #[serde(default)]
+    pub json_edits: bool,
 }
 
+/// System prompt for chats where the model writes edits as JSON text.
 const SYSTEM_PROMPT: &str = r#"You are Spicy, an AI assistant for LTspice circuit schematics (.asc files).
 
 The user's currently active .asc file content will be provided at the start of their message with line numbers (e.g. "1| Version 4"). Always use this content as context — never ask the user to paste it.
 
+When the file can be parsed, it is followed by a "Pin positions" section listing every component's absolute pin coordinates and the net each pin is on. These are computed from the SYMBOL origin and rotation — use them instead of doing the rotation math yourself.
+
+When the user attaches simulation results, a "Simulation results" section follows with simulator errors, .meas values, operating-point voltages and waveform statistics from the latest run. Base your answers on these numbers rather than guessing from the topology, and say so when the data does not cover the question.
+
+When the message lists "Goals", you are in a closed loop: respond with edit JSON that moves the circuit toward them. The circuit is simulated after every edit and you receive the updated file, the results and the status of each goal. If a goal names a .meas result the schematic does not define yet, add that .meas directive in your edit. Once the goals are met, or cannot be reached, reply in plain text instead of JSON.
+
 ## MODES
 
 1. **Analysis mode** — When the user asks to explain, analyze, or understand a circuit, respond in plain text. Do NOT output JSON.
 
-2. **Edit mode** — When the user asks to modify, change, add, or remove something, respond with ONLY this JSON (no markdown, no code blocks):
+2. **Edit mode** — When the user asks to modify, change, add, or remove something, respond with ONLY the edit JSON described under EDIT FORMAT (no markdown, no code blocks)."#;
+
+/// System prompt for chats where the model reads and edits through tools.
+const TOOLS_SYSTEM_PROMPT: &str = r#"You are Spicy, an AI assistant for LTspice circuit schematics (.asc files).
+
+The user's message names the active .asc file, and may attach simulation results. The file is not pasted into messages: call read_file to see it with line numbers (e.g. "1| Version 4"), followed by a "Pin positions" section listing every component's absolute pin coordinates and the net each pin is on. These are computed from the SYMBOL origin and rotation — use them instead of doing the rotation math yourself. list_components and get_net answer narrower questions without reading the whole file.
+
+When the user attaches simulation results, a "Simulation results" section follows with simulator errors, .meas values, operating-point voltages and waveform statistics from the latest run. Base your answers on these numbers rather than guessing from the topology, and say so when the data does not cover the question.
+
+## TOOLS
+
+Answer questions about the circuit in plain text. To change it, call apply_edits with an edit object described under EDIT FORMAT as its arguments; never write edit JSON in your reply. When apply_edits refuses an edit, correct it and call it again. A successful apply_edits returns the updated file; line numbers always refer to the file as you last received it. run_simulation simulates the saved file. Finish with a short plain-text answer once you are done."#;
+
+/// The edit object, whether written as the reply or passed to apply_edits.
+const EDIT_FORMAT_PROMPT: &str = r#"## EDIT FORMAT
+
+An edit is a JSON object. Prefer operations, which the app turns into correctly placed lines itself:
+{
+  "operations": [
+    { "op": "set_value", "component": "R1", "value": "24k" }
+  ],
+  "explanation": "Changed R1 from 10kΩ to 24kΩ",
+  "changes": [
+    { "component": "R1", "filename": "<filename>", "description": "Value 10kΩ → 24kΩ" }
+  ]
+}
+
+Operations, applied in order:
+- { "op": "set_value", "component": "R1", "value": "24k" }
+- { "op": "insert_series", "symbol": "res", "value": "1k", "net": "N002" } — cuts a wire of that net and places the part in the gap; add "wire": [x1, y1, x2, y2] to pick the wire and "name": "R5" to choose the InstName
+- { "op": "add_parallel", "component": "R1", "symbol": "cap", "value": "100n" } — places the part beside R1 and wires it across R1's pins; "name" is optional
+- { "op": "remove_component", "component": "R3" } — add "bridge": true to replace it with a wire
+- { "op": "add_directive", "text": ".tran 10m" }
+- { "op": "rename", "from": "R1", "to": "R10" }
+Net names are the ones in the "Pin positions" section. New parts only work for two-pin symbols.
+
+For anything operations cannot express, use line edits instead (never both in one response):
 {
   "edits": [
     { "start": 15, "end": 15, "replacement": "SYMATTR Value 24k" }
@@ -67,9 +197,10 @@ Edit rules:
 - Delete: { "start": 15, "end": 17, "replacement": "" }
 - Insert after line 15: { "start": 15, "end": 15, "replacement": "<original line 15>\n<new lines>" }
 - Multiple edits applied bottom-up so line numbers stay correct
-- No overlapping ranges
+- No overlapping ranges"#;
 
-## .ASC FILE FORMAT
+/// .asc syntax, pin tables and placement recipes.
+const REFERENCE_PROMPT: &str = r#"## .ASC FILE FORMAT
 
 ```
 Version 4
@@ -117,12 +248,12 @@ R0 offsets: Pin1(+16, 0), Pin2(+16, +64)
 ### Inductor (ind) — 2 pins, 80 units apart
 Same offsets as resistor: Pin1(+16, +16), Pin2(+16, +96)
 
-### Voltage source (voltage) — 2 pins, 96 units apart
-R0 offsets: Plus(0, 0), Minus(0, +96)
-- R0:   plus=(x, y), minus=(x, y+96)                — vertical, + on top
+### Voltage source (voltage) — 2 pins, 80 units apart
+R0 offsets: Plus(0, +16), Minus(0, +96)
+- R0:   plus=(x, y+16), minus=(x, y+96)             — vertical, + on top
 
 ### Diode (diode) — 2 pins, 64 units apart
-R0 offsets: Cathode(+16, 0), Anode(+16, +64)
+R0 offsets: Anode(+16, 0), Cathode(+16, +64)
 
 ### NPN transistor (npn) — 3 pins
 R0 offsets: Base(0, +48), Collector(+64, 0), Emitter(+64, +96)
@@ -130,7 +261,7 @@ R0 offsets: Base(0, +48), Collector(+64, 0), Emitter(+64, +96)
 ### PNP transistor (pnp) — 3 pins
 R0 offsets: Base(0, +48), Collector(+64, +96), Emitter(+64, 0)
 
-### Op-amps — pins vary by model. Read existing WIRE endpoints in the file to find positions.
+### Op-amps and other library symbols — pins come from their .asy files and are listed in the "Pin positions" section. If a symbol is reported as unknown, read existing WIRE endpoints in the file to find positions.
 
 ## STEP-BY-STEP RECIPES
 
@@ -237,11 +368,14 @@ T=1e12, G=1e9, Meg=1e6, k=1e3, m=1e-3, u=1e-6, n=1e-9, p=1e-12, f=1e-15
    WINDOW 3 32 56 VTop 2
 6. When inserting in series: break the wire at the pin positions, place the component in the gap
 7. When space is tight, shift downstream components/wires/flags by a uniform offset
-8. To find pin positions of unfamiliar components, trace the existing WIREs in the file
+8. Take pin positions from the "Pin positions" section; for components listed there as unknown, trace the existing WIREs in the file
 9. Double-check your coordinate math before outputting — wrong coordinates break the circuit
 10. Keep edits minimal: only change what's necessary for the requested modification
 
-## EDIT STRATEGY
+Edits are checked before they are written. An edit that overlaps ranges, leaves the file unparseable, or introduces off-grid coordinates, diagonal wires, duplicate InstNames or floating pins is refused as a whole."#;
+
+/// Tells the model to reply with nothing but the edit JSON.
+const JSON_OUTPUT_PROMPT: &str = r#"## EDIT STRATEGY
 
 When the user requests a circuit modification, do ALL reasoning in your thinking block:
 1. PARSE — State what the user wants
@@ -253,87 +387,247 @@ Then OUTPUT only the JSON object. Your entire visible response must be the raw J
 
 RULES: Commit to your first reasonable answer. Do not narrate your thought process in the response. Do not calculate component values (use sensible defaults). The response must start with { and end with }."#;
 
-fn apply_edits(file_path: &std::path::Path, edits: &[serde_json::Value]) -> Result<String, String> {
-    let content = std::fs::read_to_string(file_path)
-        .map_err(|e| format!("Failed to read file: {}", e))?;
-    let mut lines: Vec<String> = content.lines().map(|l| l.to_string()).collect();
+const TOOLS_OUTPUT_PROMPT: &str = r#"## EDIT STRATEGY
 
-    // Collect edits as (start, end, replacement) and sort descending by start line
-    let mut edit_ops: Vec<(usize, usize, String)> = edits
-        .iter()
-        .filter_map(|e| {
-            let start = e["start"].as_u64()? as usize;
-            let end = e["end"].as_u64()? as usize;
-            let replacement = e["replacement"].as_str()?.to_string();
-            Some((start, end, replacement))
-        })
-        .collect();
-    edit_ops.sort_by(|a, b| b.0.cmp(&a.0));
-
-    for (start, end, replacement) in edit_ops {
-        if start == 0 || end == 0 || start > lines.len() || end > lines.len() || start > end {
-            continue;
-        }
-        let start_idx = start - 1;
-        let end_idx = end; // exclusive for drain/splice
-        let new_lines: Vec<String> = if replacement.is_empty() {
-            vec![]
-        } else {
-            replacement.lines().map(|l| l.to_string()).collect()
-        };
-        lines.splice(start_idx..end_idx, new_lines);
+Before calling apply_edits, do the reasoning in your thinking block: state what the user wants, find every component, wire and flag involved with its line numbers and pin positions, list the edits needed, and compute coordinates for new components (all multiples of 16). Commit to your first reasonable answer and do not calculate component values (use sensible defaults)."#;
+
+/// The full system prompt for JSON edits or tool calls.
+fn system_prompt(use_tools: bool) -> String {
+    let (context, output) = if use_tools {
+        (TOOLS_SYSTEM_PROMPT, TOOLS_OUTPUT_PROMPT)
+    } else {
+        (SYSTEM_PROMPT, JSON_OUTPUT_PROMPT)
+    };
+    [context, EDIT_FORMAT_PROMPT, REFERENCE_PROMPT, output].join("\n\n")
+}
+
+/// Why an edit response was not written to disk.
+enum EditError {
+    /// The edit breaks the schematic rules.
+    Rejected(Vec<Violation>),
+    /// The file changed since the model saw it, in the lines the edit touches.
+    Conflict(String),
+    Failed(String),
+}
+
+impl From<String> for EditError {
+    fn from(e: String) -> Self {
+        EditError::Failed(e)
+    }
+}
+
+/// Splices line edits into `content` after checking that the ranges are sound.
+fn apply_line_edits(content: &str, edits: &[serde_json::Value]) -> Result<String, EditError> {
+    let lines: Vec<&str> = content.lines().collect();
+    let edit_ops = checked_line_edits(edits, lines.len())?;
+    Ok(splice_lines(content, &lines, edit_ops))
+}
+
+fn checked_line_edits(
+    edits: &[serde_json::Value],
+    line_count: usize,
+) -> Result<Vec<LineEdit>, EditError> {
+    let edit_ops = validation::parse_edits(edits).map_err(EditError::Rejected)?;
+    let violations = validation::check_ranges(&edit_ops, line_count);
+    if !violations.is_empty() {
+        return Err(EditError::Rejected(violations));
+    }
+    Ok(edit_ops)
+}
+
+fn splice_lines(content: &str, lines: &[&str], mut edit_ops: Vec<LineEdit>) -> String {
+    let mut lines: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+
+    // Apply bottom-up so line numbers of earlier edits stay correct
+    edit_ops.sort_by_key(|e| std::cmp::Reverse(e.start));
+    for edit in edit_ops {
+        let new_lines = edit.replacement.lines().map(|l| l.to_string());
+        lines.splice(edit.start - 1..edit.end, new_lines);
     }
 
     let mut result = lines.join("\n");
     if content.ends_with('\n') && !result.ends_with('\n') {
         result.push('\n');
     }
-    std::fs::write(file_path, &result)
-        .map_err(|e| format!("Failed to write file: {}", e))?;
-    Ok(result)
-}
-
-/// Shared helper: parse JSON edit response, apply edits, send Done event.
-/// Returns true if the response was handled as a JSON edit.
-fn reload_ltspice() {
-    std::thread::spawn(|| {
-        let _ = std::process::Command::new("osascript")
-            .arg("-e")
-            .arg(
-                r#"tell application "System Events"
-    if exists process "LTspice" then
-        tell application "LTspice" to activate
-        delay 0.3
-        tell process "LTspice"
-            click menu item "Revert to Saved" of menu "File" of menu bar 1
-        end tell
-    end if
-end tell"#,
-            )
-            .output();
-    });
+    result
 }
 
-fn handle_edit_response(
-    json_val: &serde_json::Value,
-    active_file: &Option<String>,
+/// Carries out semantic operations on the parsed schematic.
+fn apply_operations(
+    content: &str,
+    operations: &[serde_json::Value],
+    library: &SymbolLibrary,
+) -> Result<String, EditError> {
+    let operations = operations::parse(operations).map_err(EditError::Rejected)?;
+    let mut schematic = Schematic::parse(content).map_err(|e| {
+        EditError::Rejected(vec![Violation::new(
+            Rule::ParseError,
+            format!("The file does not parse ({}); use line edits instead", e),
+        )])
+    })?;
+    operations::apply(&mut schematic, &operations, library)
+        .map_err(|v| EditError::Rejected(vec![v]))?;
+    Ok(schematic.to_string())
+}
+
+/// The active file as the model last saw it. Line edits refer to this
+/// content, which may no longer be what is on disk.
+struct SentContent {
+    hash: String,
+    content: String,
+}
+
+fn content_hash(content: &str) -> String {
+    hex::encode(Sha256::digest(content.as_bytes()))
+}
+
+impl SentContent {
+    fn new(content: String) -> Self {
+        Self {
+            hash: content_hash(&content),
+            content,
+        }
+    }
+}
+
+/// Applies line edits made against `sent` to `current`, the file as it is
+/// now. Each range moves with the lines around it; it conflicts when any of
+/// its lines were changed on disk since.
+fn rebase_line_edits(
+    filename: &str,
+    sent: &str,
+    current: &str,
+    edits: &[serde_json::Value],
+) -> Result<String, EditError> {
+    let sent_lines: Vec<&str> = sent.lines().collect();
+    let current_lines: Vec<&str> = current.lines().collect();
+    let mut edit_ops = checked_line_edits(edits, sent_lines.len())?;
+
+    // Where each unchanged line of `sent` is now, 0-based.
+    let mut moved: Vec<Option<usize>> = vec![None; sent_lines.len()];
+    for op in capture_diff_slices(Algorithm::Myers, &sent_lines, &current_lines) {
+        if let DiffOp::Equal {
+            old_index,
+            new_index,
+            len,
+        } = op
+        {
+            for i in 0..len {
+                moved[old_index + i] = Some(new_index + i);
+            }
+        }
+    }
+
+    for edit in &mut edit_ops {
+        let start = moved[edit.start - 1];
+        let untouched = (edit.start..=edit.end)
+            .all(|line| moved[line - 1] == start.map(|s| s + line - edit.start));
+        match start {
+            Some(start) if untouched => {
+                edit.end = start + 1 + edit.end - edit.start;
+                edit.start = start + 1;
+            }
+            _ => {
+                return Err(EditError::Conflict(format!(
+                    "{} was changed outside the chat in the lines this edit touches",
+                    filename
+                )))
+            }
+        }
+    }
+    Ok(splice_lines(current, &current_lines, edit_ops))
+}
+
+/// Computes the result of an edit response's `operations` or line `edits`
+/// on `filename`, checking that the result still follows the schematic
+/// rules. Line edits are rebased when the file changed after `sent`;
+/// operations name components, so they apply to the file as it is. Returns
+/// the current content and the edited content; nothing is written.
+fn plan_edits(
+    state: &AppState,
     dir: &str,
-    on_event: &Channel<StreamEvent>,
-) -> bool {
-    let edits = match json_val["edits"].as_array() {
-        Some(e) => e,
-        None => return false,
+    filename: &str,
+    response: &serde_json::Value,
+    sent: Option<&SentContent>,
+) -> Result<(String, String), EditError> {
+    let file_path = workspace::resolve(dir, filename, &SCHEMATIC).map_err(String::from)?;
+    let content = state.file_formats.read(&file_path)?;
+    let library = symbol_library(state, dir, filename)?;
+    let changed_since_sent = sent.filter(|s| s.hash != content_hash(&content));
+
+    let result = match (
+        response["operations"].as_array(),
+        response["edits"].as_array(),
+    ) {
+        (Some(ops), Some(edits)) if !ops.is_empty() && !edits.is_empty() => {
+            return Err(EditError::Rejected(vec![Violation::new(
+                Rule::MalformedEdit,
+                "Use either operations or line edits in one response, not both",
+            )]))
+        }
+        (Some(ops), _) if !ops.is_empty() => apply_operations(&content, ops, &library)?,
+        (_, Some(edits)) => match changed_since_sent {
+            Some(sent) => rebase_line_edits(filename, &sent.content, &content, edits)?,
+            None => apply_line_edits(&content, edits)?,
+        },
+        _ => return Err(EditError::Failed("Response contains no edits".to_string())),
     };
 
-    if let Some(ref filename) = active_file {
-        let file_path = std::path::Path::new(dir).join(filename);
-        if let Err(e) = apply_edits(&file_path, edits) {
-            let _ = on_event.send(StreamEvent::Error { message: e });
-            return true;
+    let violations = validation::check_result(&content, &result, &library);
+    if !violations.is_empty() {
+        return Err(EditError::Rejected(violations));
+    }
+
+    Ok((content, result))
+}
+
+/// Where an edit response goes: the active file in `dir`, if any, either
+/// written directly or held back as a proposal for review.
+struct EditTarget<'a> {
+    dir: &'a str,
+    file: Option<&'a str>,
+    propose: bool,
+    /// Set whenever the active file's content is shown to the model.
+    sent: Mutex<Option<SentContent>>,
+}
+
+impl<'a> EditTarget<'a> {
+    fn new(dir: &'a str, file: Option<&'a str>, propose: bool) -> Self {
+        Self {
+            dir,
+            file,
+            propose,
+            sent: Mutex::new(None),
         }
-        reload_ltspice();
     }
 
+    /// The active file's context for the model, remembering the content
+    /// it was given.
+    fn context(&self, state: &AppState) -> Result<String, String> {
+        let file = self.file.ok_or("No file is open")?;
+        let content = read_asc_file_content(&state.file_formats, self.dir, file)?;
+        let context = format_file_context(state, self.dir, file, &content)?;
+        *self.sent.lock().map_err(|e| e.to_string())? = Some(SentContent::new(content));
+        Ok(context)
+    }
+}
+
+struct AppliedEdit {
+    changes: Vec<FileChange>,
+    explanation: String,
+    /// Set in propose mode, where nothing was written.
+    proposal: Option<Proposal>,
+    /// Whether LTspice shows the written file; unset when nothing was written.
+    reload: Option<ReloadStatus>,
+}
+
+/// Applies an edit response to the target file and reloads it in LTspice,
+/// or stores it as a pending proposal in propose mode.
+async fn apply_edit_response(
+    state: &AppState,
+    json_val: &serde_json::Value,
+    target: &EditTarget<'_>,
+) -> Result<AppliedEdit, EditError> {
     let explanation = json_val["explanation"]
         .as_str()
         .unwrap_or("Changes applied.")
@@ -354,41 +648,713 @@ fn handle_edit_response(
         vec![]
     };
 
-    let _ = on_event.send(StreamEvent::Done {
+    let mut proposal = None;
+    let mut reload = None;
+    if let Some(filename) = target.file {
+        let (base, result) = {
+            let sent = target.sent.lock().map_err(|e| e.to_string())?;
+            plan_edits(state, target.dir, filename, json_val, sent.as_ref())?
+        };
+        if target.propose {
+            let pending = Proposal::new(filename, base, result, &explanation, changes.clone());
+            state
+                .proposals
+                .lock()
+                .map_err(|e| e.to_string())?
+                .insert(pending.id.clone(), pending.clone());
+            proposal = Some(pending);
+        } else {
+            write_recorded(state, target.dir, filename, &result, &explanation)?;
+            let path =
+                workspace::resolve(target.dir, filename, &SCHEMATIC).map_err(String::from)?;
+            reload = Some(reload_ltspice(state, &path).await);
+        }
+    }
+
+    Ok(AppliedEdit {
         changes,
-        explanation: Some(explanation),
+        explanation,
+        proposal,
+        reload,
+    })
+}
+
+fn report_conflict(target: &EditTarget<'_>, message: &str, on_event: &Channel<StreamEvent>) {
+    let _ = on_event.send(StreamEvent::Conflict {
+        file: target.file.unwrap_or_default().to_string(),
+        message: message.to_string(),
     });
-    true
-}
-
-fn read_asc_file_content(dir: &str, filename: &str) -> Result<String, String> {
-    let file_path = std::path::Path::new(dir).join(filename);
-    match std::fs::read_to_string(&file_path) {
-        Ok(content) => Ok(content),
-        Err(_utf8_err) => {
-            match std::fs::read(&file_path) {
-                Ok(bytes) => {
-                    let content = if bytes.len() >= 2 && bytes[0] == 0xFF && bytes[1] == 0xFE {
-                        let u16s: Vec<u16> = bytes[2..]
-                            .chunks(2)
-                            .filter_map(|c| {
-                                if c.len() == 2 {
-                                    Some(u16::from_le_bytes([c[0], c[1]]))
-                                } else {
-                                    None
-                                }
-                            })
-                            .collect();
-                        String::from_utf16_lossy(&u16s)
-                    } else {
-                        String::from_utf8_lossy(&bytes).to_string()
-                    };
-                    Ok(content)
+}
+
+/// Tells the UI whether LTspice picked up a file the edit wrote.
+fn report_reload(edit: &AppliedEdit, target: &EditTarget<'_>, on_event: &Channel<StreamEvent>) {
+    if let (Some(status), Some(file)) = (&edit.reload, target.file) {
+        let _ = on_event.send(StreamEvent::LtspiceReload {
+            file: file.to_string(),
+            status: status.clone(),
+        });
+    }
+}
+
+fn is_edit_response(json_val: &serde_json::Value) -> bool {
+    json_val["operations"].is_array() || json_val["edits"].is_array()
+}
+
+/// Finds an edit response in the model's reply: either the whole reply, or
+/// JSON starting at `{"operations"` or `{"edits"` inside mixed text.
+fn parse_edit_response(text: &str) -> Option<serde_json::Value> {
+    if let Ok(json_val) = serde_json::from_str::<serde_json::Value>(text) {
+        if is_edit_response(&json_val) {
+            return Some(json_val);
+        }
+    }
+    let json_start = ["{\"operations\"", "{\"edits\""]
+        .iter()
+        .filter_map(|key| text.find(key))
+        .min()?;
+    serde_json::from_str::<serde_json::Value>(&text[json_start..])
+        .ok()
+        .filter(is_edit_response)
+}
+
+/// Pin coordinates and nets for the prompt, so the model does not have to
+/// derive them from SYMBOL rotations itself.
+pub(crate) fn pin_positions_context(content: &str, library: &SymbolLibrary) -> Option<String> {
+    let schematic = Schematic::parse(content).ok()?;
+    let graph = connectivity::extract(&schematic, library);
+    if graph.components.is_empty() && graph.unresolved.is_empty() {
+        return None;
+    }
+
+    let mut out = String::from("Pin positions:\n");
+    for component in &graph.components {
+        let pins = component
+            .pins
+            .iter()
+            .map(|p| format!("{}=({}, {}) net {}", p.name, p.at.x, p.at.y, p.net))
+            .collect::<Vec<_>>()
+            .join(", ");
+        out.push_str(&format!(
+            "{} ({} {}): {}\n",
+            component.inst_name, component.symbol, component.rotation, pins
+        ));
+    }
+    if !graph.unresolved.is_empty() {
+        out.push_str(&format!(
+            "Unknown pin layout (trace WIREs instead): {}\n",
+            graph.unresolved.join(", ")
+        ));
+    }
+    for error in &graph.symbol_errors {
+        out.push_str(&format!("Symbol error: {}\n", error));
+    }
+    Some(out)
+}
+
+fn read_asc_file_content(
+    formats: &FormatCache,
+    dir: &str,
+    filename: &str,
+) -> Result<String, String> {
+    let file_path = workspace::resolve(dir, filename, &SCHEMATIC)?;
+    formats.read(&file_path)
+}
+
+/// The numbered file plus its pin positions, as placed before user messages.
+pub(crate) fn file_context(state: &AppState, dir: &str, filename: &str) -> Result<String, String> {
+    let content = read_asc_file_content(&state.file_formats, dir, filename)?;
+    format_file_context(state, dir, filename, &content)
+}
+
+fn format_file_context(
+    state: &AppState,
+    dir: &str,
+    filename: &str,
+    content: &str,
+) -> Result<String, String> {
+    let numbered: String = content
+        .lines()
+        .enumerate()
+        .map(|(i, line)| format!("{}| {}", i + 1, line))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut out = format!("Current file: {}\n\n{}\n\n", filename, numbered);
+    let library = symbol_library(state, dir, filename)?;
+    if let Some(pins) = pin_positions_context(content, &library) {
+        out.push_str(&pins);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+enum SseLine {
+    Continue,
+    Done,
+    /// An error was reported to the frontend.
+    Failed,
+}
+
+/// Progress through one streamed completion.
+struct StreamState {
+    reply: Reply,
+    /// Without tools, edit JSON arrives as text and is kept off the screen.
+    detect_json: bool,
+    suppress_text: bool,
+}
+
+/// Passes one line of the provider's stream through it, forwarding thinking
+/// and text to the frontend.
+fn process_sse_line(
+    provider: &dyn Provider,
+    line: &str,
+    stream: &mut StreamState,
+    on_event: &Channel<StreamEvent>,
+) -> SseLine {
+    for delta in provider.parse_line(line, &mut stream.reply) {
+        match delta {
+            Delta::Thinking(content) => {
+                let _ = on_event.send(StreamEvent::Thinking { content });
+            }
+            Delta::Text(content) => {
+                // Detect JSON edit on first text chunk
+                if stream.detect_json
+                    && stream.reply.text.is_empty()
+                    && content.trim_start().starts_with('{')
+                {
+                    stream.suppress_text = true;
                 }
-                Err(io_err) => Err(format!("Failed to read file {}: {}", filename, io_err)),
+                stream.reply.text.push_str(&content);
+                if !stream.suppress_text {
+                    let _ = on_event.send(StreamEvent::Text { content });
+                }
+            }
+            Delta::Error(message) => {
+                let _ = on_event.send(StreamEvent::Error { message });
+                return SseLine::Failed;
             }
+            Delta::Done => return SseLine::Done,
         }
     }
+    SseLine::Continue
+}
+
+/// The HTTP client and provider shared by every completion of one chat turn.
+struct ModelClient {
+    client: reqwest::Client,
+    provider: Box<dyn Provider>,
+}
+
+impl ModelClient {
+    fn new(client: reqwest::Client, provider: Box<dyn Provider>) -> Self {
+        Self { client, provider }
+    }
+
+    /// Streams one completion and reports its token usage. Returns the full
+    /// reply, or None when an error has already been reported through
+    /// `on_event`.
+    async fn stream(
+        &self,
+        request: &CompletionRequest,
+        on_event: &Channel<StreamEvent>,
+    ) -> Result<Option<Reply>, String> {
+        let reply =
+            stream_completion(&self.client, self.provider.as_ref(), request, on_event).await?;
+        if let Some(usage) = reply.as_ref().and_then(|r| r.usage) {
+            let _ = on_event.send(StreamEvent::Usage(usage));
+        }
+        Ok(reply)
+    }
+}
+
+async fn stream_completion(
+    client: &reqwest::Client,
+    provider: &dyn Provider,
+    request: &CompletionRequest,
+    on_event: &Channel<StreamEvent>,
+) -> Result<Option<Reply>, String> {
+    let response = provider
+        .request(client, request)
+        .send()
+        .await
+        .map_err(|e| format!("API request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        let _ = on_event.send(StreamEvent::Error {
+            message: format!("API error ({}): {}", status, body),
+        });
+        return Ok(None);
+    }
+
+    // Read the SSE stream
+    let mut stream = response.bytes_stream();
+    let mut buffer = String::new();
+    let mut state = StreamState {
+        reply: Reply::default(),
+        detect_json: request.tools.is_empty(),
+        suppress_text: false,
+    };
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = match chunk {
+            Ok(c) => c,
+            Err(e) => {
+                let _ = on_event.send(StreamEvent::Error {
+                    message: format!("Stream error: {}", e),
+                });
+                return Ok(None);
+            }
+        };
+
+        buffer.push_str(&String::from_utf8_lossy(&chunk));
+
+        while let Some(newline_pos) = buffer.find('\n') {
+            let line = buffer[..newline_pos].trim_end().to_string();
+            buffer = buffer[newline_pos + 1..].to_string();
+
+            match process_sse_line(provider, &line, &mut state, on_event) {
+                SseLine::Continue => {}
+                SseLine::Done => return Ok(Some(state.reply)),
+                SseLine::Failed => return Ok(None),
+            }
+        }
+    }
+
+    // Stream ended without a final event — flush any remaining data in the buffer
+    for line in buffer.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        match process_sse_line(provider, line, &mut state, on_event) {
+            SseLine::Continue => {}
+            SseLine::Done => break,
+            SseLine::Failed => return Ok(None),
+        }
+    }
+
+    Ok(Some(state.reply))
+}
+
+/// Corrected edits requested from the model before an edit is given up on.
+const MAX_REPAIR_ATTEMPTS: u32 = 2;
+
+enum EditOutcome {
+    /// The reply is not an edit response.
+    NoEdits,
+    /// `reply` is the model response that was finally applied.
+    Applied {
+        edit: Box<AppliedEdit>,
+        reply: String,
+    },
+    /// Still refused after the repair attempts ran out, or the model
+    /// stopped proposing edits.
+    Rejected(Vec<Violation>),
+    /// Refused because of outside changes; the conflict has been reported.
+    Conflict(String),
+    /// An error has already been reported through the channel.
+    Failed,
+}
+
+/// Applies the edit in `reply`. When validation refuses it, the violations
+/// and the numbered file go back to the model for a corrected edit, up to
+/// `MAX_REPAIR_ATTEMPTS` times. The exchange is appended to `request`.
+async fn apply_with_repair(
+    state: &AppState,
+    model: &ModelClient,
+    request: &mut CompletionRequest,
+    mut reply: String,
+    target: &EditTarget<'_>,
+    on_event: &Channel<StreamEvent>,
+) -> Result<EditOutcome, String> {
+    let Some(mut json_val) = parse_edit_response(&reply) else {
+        return Ok(EditOutcome::NoEdits);
+    };
+
+    let mut attempt = 0;
+    loop {
+        let violations = match apply_edit_response(state, &json_val, target).await {
+            Ok(edit) => {
+                report_reload(&edit, target, on_event);
+                return Ok(EditOutcome::Applied {
+                    edit: Box::new(edit),
+                    reply,
+                });
+            }
+            Err(EditError::Failed(e)) => {
+                let _ = on_event.send(StreamEvent::Error { message: e });
+                return Ok(EditOutcome::Failed);
+            }
+            Err(EditError::Conflict(message)) => {
+                report_conflict(target, &message, on_event);
+                return Ok(EditOutcome::Conflict(message));
+            }
+            Err(EditError::Rejected(violations)) => violations,
+        };
+        if target.file.is_none() || attempt >= MAX_REPAIR_ATTEMPTS {
+            return Ok(EditOutcome::Rejected(violations));
+        }
+        attempt += 1;
+        let _ = on_event.send(StreamEvent::Repairing {
+            attempt,
+            max_attempts: MAX_REPAIR_ATTEMPTS,
+            violations: violations.clone(),
+        });
+
+        let mut feedback = String::from("Your edit was refused and nothing was written:\n");
+        for v in &violations {
+            feedback.push_str(&format!("- {}\n", v.message));
+        }
+        feedback.push('\n');
+        feedback.push_str(&target.context(state)?);
+        feedback.push_str(
+            "Respond with corrected edit JSON for the same request, using the line \
+             numbers above.",
+        );
+        request.messages.push(Message::new("assistant", reply));
+        request.messages.push(Message::new("user", feedback));
+
+        let Some(Reply { text: next, .. }) = model.stream(request, on_event).await? else {
+            return Ok(EditOutcome::Failed);
+        };
+        match parse_edit_response(&next) {
+            Some(next_json) => {
+                json_val = next_json;
+                reply = next;
+            }
+            None => return Ok(EditOutcome::Rejected(violations)),
+        }
+    }
+}
+
+fn rejected_explanation(violations: &[Violation]) -> String {
+    format!(
+        "The edit was not applied: {} problem{} found.",
+        violations.len(),
+        if violations.len() == 1 { "" } else { "s" }
+    )
+}
+
+/// Model turns allowed in one tool-calling exchange.
+const MAX_TOOL_ROUNDS: u32 = 12;
+
+/// Everything the model changed through tools in one exchange.
+#[derive(Default)]
+struct ToolSession {
+    changes: Vec<FileChange>,
+    explanation: Option<String>,
+}
+
+/// Runs one tool call and returns the text sent back to the model.
+async fn execute_tool(
+    state: &AppState,
+    target: &EditTarget<'_>,
+    call: &ToolCall,
+    session: &mut ToolSession,
+    on_event: &Channel<StreamEvent>,
+) -> Result<String, String> {
+    let args = call.arguments()?;
+    let name = call.function.name.as_str();
+    let reads_active_file = name == "read_file"
+        && args["file"]
+            .as_str()
+            .is_none_or(|file| Some(file) == target.file);
+    if reads_active_file {
+        return target.context(state);
+    }
+    if let Some(result) = tools::query(state, target.dir, target.file, name, &args) {
+        return result;
+    }
+    let file = target.file.ok_or("No file is open")?;
+
+    match name {
+        "apply_edits" => match apply_edit_response(state, &args, target).await {
+            Ok(edit) => {
+                report_reload(&edit, target, on_event);
+                session.explanation = Some(edit.explanation);
+                match edit.proposal {
+                    Some(proposal) => {
+                        let id = proposal.id.clone();
+                        let _ = on_event.send(StreamEvent::Proposal(proposal));
+                        Ok(format!(
+                            "Proposed as {} for the user to review; the file is unchanged.",
+                            id
+                        ))
+                    }
+                    None => {
+                        session.changes.extend(edit.changes);
+                        Ok(format!("Applied.\n\n{}", target.context(state)?))
+                    }
+                }
+            }
+            Err(EditError::Rejected(violations)) => {
+                let mut out = String::from("Refused; nothing was written:\n");
+                for v in &violations {
+                    out.push_str(&format!("- {}\n", v.message));
+                }
+                let _ = on_event.send(StreamEvent::EditRejected { violations });
+                Err(out)
+            }
+            Err(EditError::Conflict(message)) => {
+                report_conflict(target, &message, on_event);
+                Err(format!(
+                    "{}; nothing was written. Call read_file for the current content and \
+                     make the edit again.",
+                    message
+                ))
+            }
+            Err(EditError::Failed(e)) => Err(e),
+        },
+        "run_simulation" => {
+            let analysis = args["analysis"].as_str();
+            let simulator = select_simulator(state)?;
+            let run = run_simulation(state, simulator, target.dir, file, analysis, |_| {}).await?;
+            let mut out = format!(
+                "Simulation {}.\n",
+                if run.outcome.success {
+                    "finished"
+                } else {
+                    "failed"
+                }
+            );
+            if let Some(summary) = simulation_summary(state, target.dir, file, &[])? {
+                out.push_str(&summary);
+            }
+            Ok(out)
+        }
+        _ => Err(format!("Unknown tool {}", name)),
+    }
+}
+
+/// Lets the model call tools until it answers in plain text or runs out of
+/// rounds. Every call and its result are appended to `request`.
+async fn run_tools(
+    state: &AppState,
+    model: &ModelClient,
+    mut request: CompletionRequest,
+    target: &EditTarget<'_>,
+    on_event: &Channel<StreamEvent>,
+) -> Result<(), String> {
+    let mut session = ToolSession::default();
+    for _ in 0..MAX_TOOL_ROUNDS {
+        let Some(reply) = model.stream(&request, on_event).await? else {
+            return Ok(());
+        };
+        if reply.tool_calls.is_empty() {
+            let _ = on_event.send(StreamEvent::Done {
+                changes: session.changes,
+                explanation: session.explanation,
+            });
+            return Ok(());
+        }
+
+        let calls = reply.tool_calls.clone();
+        request.messages.push(Message {
+            tool_calls: reply.tool_calls,
+            thinking: reply.thinking,
+            ..Message::new("assistant", reply.text)
+        });
+        for call in &calls {
+            let _ = on_event.send(StreamEvent::ToolCall {
+                name: call.function.name.clone(),
+                arguments: call.function.arguments.clone(),
+            });
+            let result = execute_tool(state, target, call, &mut session, on_event).await;
+            let _ = on_event.send(StreamEvent::ToolResult {
+                name: call.function.name.clone(),
+                success: result.is_ok(),
+            });
+            request.messages.push(Message {
+                tool_call_id: Some(call.id.clone()),
+                ..Message::new("tool", result.unwrap_or_else(|e| format!("Error: {}", e)))
+            });
+        }
+    }
+
+    let _ = on_event.send(StreamEvent::Done {
+        changes: session.changes,
+        explanation: Some(format!(
+            "Stopped after {} rounds of tool calls.",
+            MAX_TOOL_ROUNDS
+        )),
+    });
+    Ok(())
+}
+
+/// Default and upper bound for `AgentOptions::max_iterations`.
+const DEFAULT_AGENT_ITERATIONS: u32 = 3;
+const MAX_AGENT_ITERATIONS: u32 = 10;
+
+fn goal_status(results: &[GoalResult]) -> String {
+    let mut out = String::from("Goal status:\n");
+    for r in results {
+        let status = match (r.met, r.actual) {
+            (Some(true), Some(actual)) => format!("met (actual {:.6e})", actual),
+            (Some(true), None) => "met".to_string(),
+            (Some(false), Some(actual)) => format!("NOT met (actual {:.6e})", actual),
+            (Some(false), None) => "NOT met (no such .meas result)".to_string(),
+            (None, _) => "check against the results above".to_string(),
+        };
+        out.push_str(&format!("- {}: {}\n", r.goal, status));
+    }
+    out
+}
+
+/// Edit, simulate and verify until the goals are met, the model stops
+/// proposing edits, or the iteration budget runs out.
+async fn run_agent(
+    state: &AppState,
+    model: &ModelClient,
+    mut request: CompletionRequest,
+    target: &EditTarget<'_>,
+    agent: AgentOptions,
+    on_event: &Channel<StreamEvent>,
+) -> Result<(), String> {
+    let budget = agent
+        .max_iterations
+        .unwrap_or(DEFAULT_AGENT_ITERATIONS)
+        .clamp(1, MAX_AGENT_ITERATIONS);
+    let dir = target.dir;
+    let filename = target.file.ok_or("Agent mode needs an active file")?;
+    let mut changes: Vec<FileChange> = Vec::new();
+
+    for iteration in 1..=budget {
+        let _ = on_event.send(StreamEvent::Iteration {
+            iteration,
+            max_iterations: budget,
+        });
+
+        let Some(Reply { text: reply, .. }) = model.stream(&request, on_event).await? else {
+            return Ok(());
+        };
+        let outcome =
+            apply_with_repair(state, model, &mut request, reply, target, on_event).await?;
+        let (applied, explanation, reply) = match outcome {
+            EditOutcome::Applied { edit, reply } => (edit.changes, edit.explanation, reply),
+            // A reply without edits means the model considers the goals met
+            // or out of reach; its text has already been streamed.
+            EditOutcome::NoEdits => {
+                let _ = on_event.send(StreamEvent::Done {
+                    changes,
+                    explanation: None,
+                });
+                return Ok(());
+            }
+            EditOutcome::Rejected(violations) => {
+                let _ = on_event.send(StreamEvent::EditRejected { violations });
+                let _ = on_event.send(StreamEvent::Done {
+                    changes,
+                    explanation: Some(format!(
+                        "Stopped at iteration {}: the edit was not applied.",
+                        iteration
+                    )),
+                });
+                return Ok(());
+            }
+            EditOutcome::Conflict(message) => {
+                let _ = on_event.send(StreamEvent::Done {
+                    changes,
+                    explanation: Some(format!("Stopped at iteration {}: {}.", iteration, message)),
+                });
+                return Ok(());
+            }
+            EditOutcome::Failed => return Ok(()),
+        };
+        let _ = on_event.send(StreamEvent::EditsApplied {
+            iteration,
+            changes: applied.clone(),
+            explanation: explanation.clone(),
+        });
+        changes.extend(applied);
+
+        let simulation = match select_simulator(state) {
+            Ok(simulator) => {
+                let analysis = agent.analysis.as_deref();
+                run_simulation(state, simulator, dir, filename, analysis, |_| {}).await
+            }
+            Err(e) => Err(e),
+        };
+        // Goals are only checked against a run that succeeded, never against
+        // the log an earlier run left behind.
+        let succeeded = simulation.as_ref().is_ok_and(|run| run.outcome.success);
+        let log = simulation.as_ref().ok().and_then(|run| run.outcome.log());
+        let report = log.as_ref().filter(|_| succeeded);
+        let mut errors: Vec<String> = log
+            .iter()
+            .flat_map(|r| r.errors.iter().map(|d| d.message.clone()))
+            .collect();
+        if let Err(ref e) = simulation {
+            errors.insert(0, e.clone());
+        }
+        let _ = on_event.send(StreamEvent::Simulated {
+            iteration,
+            success: succeeded,
+            errors,
+        });
+
+        let results = goals::evaluate(&agent.goals, report);
+        // Described goals cannot be checked here, so they are left to the
+        // model once every checked goal holds.
+        let checked_met = results.iter().all(|r| r.met != Some(false));
+        let described = results.iter().any(|r| r.met.is_none());
+        let all_met = checked_met && !described;
+        let _ = on_event.send(StreamEvent::Goals {
+            iteration,
+            results: results.clone(),
+            all_met,
+        });
+        if all_met {
+            let _ = on_event.send(StreamEvent::Done {
+                changes,
+                explanation: Some(format!(
+                    "All goals met after {} iteration{}. {}",
+                    iteration,
+                    if iteration == 1 { "" } else { "s" },
+                    explanation
+                )),
+            });
+            return Ok(());
+        }
+
+        let mut feedback = format!(
+            "Iteration {} applied: {}\n\n{}",
+            iteration,
+            explanation,
+            target.context(state)?
+        );
+        match &simulation {
+            Ok(_) => {
+                if let Some(summary) = simulation_summary(state, dir, filename, &[])? {
+                    feedback.push_str(&summary);
+                }
+            }
+            Err(e) => feedback.push_str(&format!("Simulation failed: {}\n", e)),
+        }
+        feedback.push('\n');
+        feedback.push_str(&goal_status(&results));
+        feedback.push_str(if checked_met {
+            "\nEvery checked goal is met. Judge the remaining goals against the results \
+             above: respond in plain text if they are met or cannot be reached, or with \
+             edit JSON for the next change against the updated line numbers above."
+        } else {
+            "\nThe goals are not all met. Respond with edit JSON for the next change \
+             against the updated line numbers above, or in plain text if the goals \
+             are met or cannot be reached."
+        });
+        request.messages.push(Message::new("assistant", reply));
+        request.messages.push(Message::new("user", feedback));
+    }
+
+    let _ = on_event.send(StreamEvent::Done {
+        changes,
+        explanation: Some(format!(
+            "Stopped after {} iteration{} without meeting every goal.",
+            budget,
+            if budget == 1 { "" } else { "s" }
+        )),
+    });
+    Ok(())
 }
 
 #[tauri::command]
@@ -398,25 +1364,37 @@ pub async fn send_chat_message_stream(
     active_file: Option<String>,
     history: Vec<serde_json::Value>,
     model: Option<String>,
+    options: Option<ChatOptions>,
     on_event: Channel<StreamEvent>,
 ) -> Result<(), String> {
+    let options = options.unwrap_or_default();
+    let settings = state.settings.lock().map_err(|e| e.to_string())?.clone();
+    let provider_config = settings.provider.clone();
     let api_key = {
-        let mut key = state.api_key.lock().map_err(|e| e.to_string())?;
+        let key = state.api_key.lock().map_err(|e| e.to_string())?;
         if key.is_empty() {
-            if let Ok(env_key) = std::env::var("OPENROUTER_API_KEY") {
-                *key = env_key;
-            }
+            std::env::var(provider_config.key_variable()).unwrap_or_default()
+        } else {
+            key.clone()
         }
-        key.clone()
     };
 
-    if api_key.is_empty() {
+    if api_key.is_empty() && provider_config.needs_key() {
         let _ = on_event.send(StreamEvent::Error {
-            message: "OPENROUTER_API_KEY not set. Please set it as an environment variable."
-                .to_string(),
+            message: format!(
+                "{} not set. Please set it as an environment variable.",
+                provider_config.key_variable()
+            ),
         });
         return Ok(());
     }
+    let provider = match provider_config.build(api_key) {
+        Ok(provider) => provider,
+        Err(message) => {
+            let _ = on_event.send(StreamEvent::Error { message });
+            return Ok(());
+        }
+    };
 
     let dir = state
         .working_directory
@@ -433,266 +1411,255 @@ pub async fn send_chat_message_stream(
         }
     };
 
+    // Every edit goes to the active file, so a path outside the working
+    // directory is refused before anything is sent.
+    if let Some(ref filename) = active_file {
+        if let Err(e) = workspace::resolve(&dir, filename, &SCHEMATIC) {
+            let _ = on_event.send(StreamEvent::Error {
+                message: e.to_string(),
+            });
+            return Ok(());
+        }
+    }
+
+    let use_tools = !options.json_edits && options.agent.is_none();
+    if options.agent.is_some() {
+        let problem = if options.propose {
+            Some("Agent mode writes every edit and cannot be combined with propose mode")
+        } else if active_file.is_none() {
+            Some("Agent mode needs an active file")
+        } else {
+            None
+        };
+        if let Some(message) = problem {
+            let _ = on_event.send(StreamEvent::Error {
+                message: message.to_string(),
+            });
+            return Ok(());
+        }
+    }
+    let target = EditTarget::new(
+        &dir,
+        active_file.as_deref(),
+        options.agent.is_none() && (options.propose || settings.edit_policy == EditPolicy::Propose),
+    );
+
     // Build user message with file context
     let mut user_content = String::new();
 
     if let Some(ref filename) = active_file {
-        match read_asc_file_content(&dir, filename) {
-            Ok(content) => {
-                let numbered: String = content
-                    .lines()
-                    .enumerate()
-                    .map(|(i, line)| format!("{}| {}", i + 1, line))
-                    .collect::<Vec<_>>()
-                    .join("\n");
-                user_content.push_str(&format!(
-                    "Current file: {}\n\n{}\n\n",
-                    filename, numbered
-                ));
+        if use_tools {
+            user_content.push_str(&format!("Active file: {}\n\n", filename));
+        } else {
+            match target.context(&state) {
+                Ok(context) => user_content.push_str(&context),
+                Err(e) => {
+                    let _ = on_event.send(StreamEvent::Error { message: e });
+                    return Ok(());
+                }
             }
-            Err(e) => {
-                let _ = on_event.send(StreamEvent::Error { message: e });
-                return Ok(());
+        }
+        if let Some(ref simulation) = options.simulation {
+            match simulation_summary(&state, &dir, filename, &simulation.traces)? {
+                Some(summary) => user_content.push_str(&summary),
+                None => user_content.push_str("Simulation results: not simulated yet\n"),
             }
+            user_content.push('\n');
         }
     }
+    if let Some(ref agent) = options.agent {
+        user_content.push_str(
+            "Goals (the circuit is simulated after each edit and checked against these):\n",
+        );
+        for goal in &agent.goals {
+            user_content.push_str(&format!("- {}\n", goal));
+        }
+        user_content.push('\n');
+    }
     user_content.push_str(&message);
 
     // Build message history with system prompt first
-    let mut messages: Vec<ChatMsg> = vec![ChatMsg {
-        role: "system".to_string(),
-        content: SYSTEM_PROMPT.to_string(),
-    }];
+    let mut messages: Vec<Message> = vec![Message::new("system", system_prompt(use_tools))];
 
     for msg in &history {
         if let (Some(role), Some(content)) = (msg["role"].as_str(), msg["content"].as_str()) {
-            messages.push(ChatMsg {
-                role: role.to_string(),
-                content: content.to_string(),
-            });
+            messages.push(Message::new(role, content));
         }
     }
 
-    messages.push(ChatMsg {
-        role: "user".to_string(),
-        content: user_content,
-    });
+    messages.push(Message::new("user", user_content));
 
-    let selected_model = model.unwrap_or_else(|| "google/gemini-3.1-pro-preview".to_string());
+    let selected_model = model
+        .or(provider_config.model)
+        .or_else(|| provider.default_model().map(str::to_string));
+    let Some(selected_model) = selected_model else {
+        let _ = on_event.send(StreamEvent::Error {
+            message: format!("No model chosen for the {} provider", provider.name()),
+        });
+        return Ok(());
+    };
 
-    let request = OpenRouterRequest {
+    let mut request = CompletionRequest {
         model: selected_model,
-        max_tokens: 16000,
+        max_tokens: settings.max_tokens,
         messages,
-        stream: true,
+        tools: if use_tools {
+            tools::definitions()
+        } else {
+            vec![]
+        },
     };
 
-    let client = reqwest::Client::new();
-    let response = client
-        .post("https://openrouter.ai/api/v1/chat/completions")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("content-type", "application/json")
-        .json(&request)
-        .send()
-        .await
-        .map_err(|e| format!("API request failed: {}", e))?;
-
-    if !response.status().is_success() {
-        let status = response.status();
-        let body = response.text().await.unwrap_or_default();
-        let _ = on_event.send(StreamEvent::Error {
-            message: format!("API error ({}): {}", status, body),
-        });
-        return Ok(());
+    let client = state.http_client.lock().map_err(|e| e.to_string())?.clone();
+    let model = ModelClient::new(client, provider);
+    if let Some(agent) = options.agent {
+        return run_agent(&state, &model, request, &target, agent, &on_event).await;
+    }
+    if use_tools {
+        return run_tools(&state, &model, request, &target, &on_event).await;
     }
 
-    // Read SSE stream (OpenAI-compatible format from OpenRouter)
-    let mut stream = response.bytes_stream();
-    let mut buffer = String::new();
-    let mut accumulated_text = String::new();
-    let mut done_sent = false;
-    let mut suppress_text = false; // true when response looks like JSON edit
-
-    // Process a single SSE data line; returns true if we should stop
-    let process_line = |line: &str,
-                        accumulated_text: &mut String,
-                        on_event: &Channel<StreamEvent>,
-                        active_file: &Option<String>,
-                        dir: &str,
-                        done_sent: &mut bool,
-                        suppress_text: &mut bool|
-     -> bool {
-        let data = match line.strip_prefix("data: ") {
-            Some(d) => d,
-            None => return false,
-        };
-
-        if data == "[DONE]" {
-            // Stream finished — process accumulated text
-            if !*done_sent {
-                // Check if accumulated text is JSON edit response
-                if let Ok(json_val) =
-                    serde_json::from_str::<serde_json::Value>(accumulated_text)
-                {
-                    if handle_edit_response(&json_val, active_file, dir, on_event) {
-                        *done_sent = true;
-                        return true;
-                    }
-                }
-
-                // Fallback: extract JSON from mixed text
-                if let Some(json_start) = accumulated_text.find("{\"edits\"") {
-                    let candidate = &accumulated_text[json_start..];
-                    if let Ok(json_val) =
-                        serde_json::from_str::<serde_json::Value>(candidate)
-                    {
-                        if handle_edit_response(&json_val, active_file, dir, on_event) {
-                            *done_sent = true;
-                            return true;
-                        }
-                    }
+    let Some(Reply { text: reply, .. }) = model.stream(&request, &on_event).await? else {
+        return Ok(());
+    };
+    let outcome =
+        apply_with_repair(&state, &model, &mut request, reply, &target, &on_event).await?;
+    match outcome {
+        // Analysis mode: plain text
+        EditOutcome::NoEdits => {
+            let _ = on_event.send(StreamEvent::Done {
+                changes: vec![],
+                explanation: None,
+            });
+        }
+        EditOutcome::Applied { edit, .. } => {
+            let changes = match edit.proposal {
+                // Nothing is applied until the proposal is accepted.
+                Some(proposal) => {
+                    let _ = on_event.send(StreamEvent::Proposal(proposal));
+                    vec![]
                 }
-
-                // Analysis mode: plain text
-                let _ = on_event.send(StreamEvent::Done {
-                    changes: vec![],
-                    explanation: None,
-                });
-                *done_sent = true;
-            }
-            return true;
+                None => edit.changes,
+            };
+            let _ = on_event.send(StreamEvent::Done {
+                changes,
+                explanation: Some(edit.explanation),
+            });
         }
-
-        let parsed = match serde_json::from_str::<serde_json::Value>(data) {
-            Ok(p) => p,
-            Err(_) => return false,
-        };
-
-        // Handle OpenRouter/OpenAI error objects
-        if let Some(err) = parsed.get("error") {
-            let error_msg = err["message"]
-                .as_str()
-                .unwrap_or("Unknown API error");
-            let _ = on_event.send(StreamEvent::Error {
-                message: error_msg.to_string(),
+        EditOutcome::Rejected(violations) => {
+            let explanation = rejected_explanation(&violations);
+            let _ = on_event.send(StreamEvent::EditRejected { violations });
+            let _ = on_event.send(StreamEvent::Done {
+                changes: vec![],
+                explanation: Some(explanation),
             });
-            *done_sent = true;
-            return true;
         }
+        EditOutcome::Conflict(message) => {
+            let _ = on_event.send(StreamEvent::Done {
+                changes: vec![],
+                explanation: Some(format!("{}; the edit was not applied.", message)),
+            });
+        }
+        EditOutcome::Failed => {}
+    }
 
-        // OpenAI-compatible streaming: choices[0].delta.content
-        if let Some(choices) = parsed["choices"].as_array() {
-            if let Some(choice) = choices.first() {
-                // Check finish_reason
-                if let Some(reason) = choice["finish_reason"].as_str() {
-                    if reason == "stop" || reason == "end_turn" {
-                        // Will be finalized on [DONE], nothing to do here
-                    }
-                }
-
-                // Extract reasoning/thinking from delta
-                if let Some(reasoning) = choice["delta"]["reasoning"].as_str() {
-                    if !reasoning.is_empty() {
-                        let _ = on_event.send(StreamEvent::Thinking {
-                            content: reasoning.to_string(),
-                        });
-                    }
-                }
+    Ok(())
+}
 
-                // Extract text content from delta
-                if let Some(content) = choice["delta"]["content"].as_str() {
-                    if !content.is_empty() {
-                        // Detect JSON edit on first text chunk
-                        if accumulated_text.is_empty()
-                            && content.trim_start().starts_with('{')
-                        {
-                            *suppress_text = true;
-                        }
-                        accumulated_text.push_str(content);
-                        if !*suppress_text {
-                            let _ = on_event.send(StreamEvent::Text {
-                                content: content.to_string(),
-                            });
-                        }
-                    }
-                }
-            }
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::path::Path;
 
-        false
-    };
+    const RC_FILTER: &str = include_str!("../../tests/fixtures/asc/rc_filter.asc");
 
-    'outer: while let Some(chunk) = stream.next().await {
-        let chunk = match chunk {
-            Ok(c) => c,
-            Err(e) => {
-                let _ = on_event.send(StreamEvent::Error {
-                    message: format!("Stream error: {}", e),
-                });
-                return Ok(());
-            }
-        };
+    fn replace_line(content: &str, line: usize, with: &str) -> String {
+        let mut lines: Vec<&str> = content.lines().collect();
+        lines[line - 1] = with;
+        lines.join("\n") + "\n"
+    }
 
-        buffer.push_str(&String::from_utf8_lossy(&chunk));
+    fn set_r1(value: &str) -> Vec<serde_json::Value> {
+        vec![json!({ "start": 21, "end": 21, "replacement": format!("SYMATTR Value {}", value) })]
+    }
 
-        while let Some(newline_pos) = buffer.find('\n') {
-            let line = buffer[..newline_pos].trim_end().to_string();
-            buffer = buffer[newline_pos + 1..].to_string();
+    #[test]
+    fn rebase_keeps_changes_made_elsewhere() {
+        let current = replace_line(RC_FILTER, 24, "SYMATTR Value 220n");
+        let Ok(result) = rebase_line_edits("rc.asc", RC_FILTER, &current, &set_r1("2.2k")) else {
+            panic!("rebase failed");
+        };
+        assert_eq!(result, replace_line(&current, 21, "SYMATTR Value 2.2k"));
+    }
 
-            if process_line(
-                &line,
-                &mut accumulated_text,
-                &on_event,
-                &active_file,
-                &dir,
-                &mut done_sent,
-                &mut suppress_text,
-            ) {
-                break 'outer;
-            }
-        }
+    #[test]
+    fn rebase_follows_lines_added_above() {
+        let current = RC_FILTER.replacen(
+            "WIRE 144 96 48 96\n",
+            "WIRE 144 96 48 96\nWIRE 400 96 400 160\nWIRE 400 160 464 160\n",
+            1,
+        );
+        let Ok(result) = rebase_line_edits("rc.asc", RC_FILTER, &current, &set_r1("2.2k")) else {
+            panic!("rebase failed");
+        };
+        assert_eq!(result, replace_line(&current, 23, "SYMATTR Value 2.2k"));
     }
 
-    // Flush any remaining data in the buffer
-    if !done_sent && !buffer.trim().is_empty() {
-        for line in buffer.lines() {
-            let line = line.trim();
-            if !line.is_empty() {
-                process_line(
-                    line,
-                    &mut accumulated_text,
-                    &on_event,
-                    &active_file,
-                    &dir,
-                    &mut done_sent,
-                    &mut suppress_text,
-                );
-            }
-        }
+    #[test]
+    fn rebase_refuses_lines_changed_on_disk() {
+        let current = replace_line(RC_FILTER, 21, "SYMATTR Value 4.7k");
+        let edits = [
+            json!({ "start": 19, "end": 21, "replacement": "SYMATTR InstName R2\nSYMATTR Value 1k" }),
+        ];
+        let result = rebase_line_edits("rc.asc", RC_FILTER, &current, &edits);
+        assert!(
+            matches!(result, Err(EditError::Conflict(m)) if m.starts_with("rc.asc was changed"))
+        );
+
+        // A line inserted inside the range conflicts as well.
+        let current = RC_FILTER.replacen(
+            "WINDOW 3 32 56",
+            "SYMATTR SpiceLine tol=1\nWINDOW 3 32 56",
+            1,
+        );
+        let result = rebase_line_edits("rc.asc", RC_FILTER, &current, &edits);
+        assert!(matches!(result, Err(EditError::Conflict(_))));
     }
 
-    if !done_sent {
-        // Stream ended without [DONE] — do final edit check
-        if let Ok(json_val) = serde_json::from_str::<serde_json::Value>(&accumulated_text) {
-            if handle_edit_response(&json_val, &active_file, &dir, &on_event) {
-                return Ok(());
-            }
-        }
+    #[test]
+    fn plan_edits_rebases_onto_the_file_on_disk() {
+        let dir = std::env::temp_dir().join(format!("spicy-plan-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let current = replace_line(RC_FILTER, 24, "SYMATTR Value 220n");
+        std::fs::write(dir.join("rc.asc"), &current).unwrap();
+        let state = AppState::new();
+        state
+            .bundled_symbols
+            .set(Path::new(env!("CARGO_MANIFEST_DIR")).join("resources/sym"))
+            .unwrap();
+        let dir_str = dir.to_string_lossy();
+        let sent = SentContent::new(RC_FILTER.to_string());
+
+        let response = json!({ "edits": set_r1("2.2k") });
+        let Ok((content, result)) = plan_edits(&state, &dir_str, "rc.asc", &response, Some(&sent))
+        else {
+            panic!("plan failed");
+        };
+        assert_eq!(content, current);
+        assert_eq!(result, replace_line(&current, 21, "SYMATTR Value 2.2k"));
 
-        if let Some(json_start) = accumulated_text.find("{\"edits\"") {
-            let candidate = &accumulated_text[json_start..];
-            if let Ok(json_val) = serde_json::from_str::<serde_json::Value>(candidate) {
-                if handle_edit_response(&json_val, &active_file, &dir, &on_event) {
-                    return Ok(());
-                }
-            }
-        }
+        let response =
+            json!({ "edits": [{ "start": 24, "end": 24, "replacement": "SYMATTR Value 1u" }] });
+        let planned = plan_edits(&state, &dir_str, "rc.asc", &response, Some(&sent));
+        assert!(matches!(planned, Err(EditError::Conflict(_))));
 
-        let _ = on_event.send(StreamEvent::Done {
-            changes: vec![],
-            explanation: None,
-        });
-    }
+        // An edit whose result breaks the drawing rules is refused.
+        let response =
+            json!({ "edits": [{ "start": 3, "end": 3, "replacement": "WIRE 144 96 40 90" }] });
+        let planned = plan_edits(&state, &dir_str, "rc.asc", &response, Some(&sent));
+        assert!(matches!(planned, Err(EditError::Rejected(v)) if v[0].rule == Rule::OffGrid));
 
-    Ok(())
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,383 @@
+use crate::commands::history::{sanitize_filename, timestamp_now};
+use crate::commands::ltspice::{reload_ltspice, WrittenFile};
+use crate::state::AppState;
+use crate::workspace::{self, SCHEMATIC};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::State;
+
+/// Oldest entries are dropped beyond this many.
+const MAX_EDIT_HISTORY: usize = 50;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct EditEntry {
+    pub id: u64,
+    pub description: String,
+    pub created_at: String,
+}
+
+/// Edits applied to one file. `entries[..position]` are applied and can be
+/// undone; `entries[position..]` were undone and can be redone.
+#[derive(Serialize, Deserialize, Default)]
+pub struct EditHistory {
+    pub entries: Vec<EditEntry>,
+    pub position: usize,
+    next_id: u64,
+}
+
+fn history_dir(working_dir: &str, file: &str) -> PathBuf {
+    PathBuf::from(working_dir)
+        .join(".spicy")
+        .join("edits")
+        .join(sanitize_filename(file))
+}
+
+fn snapshot_path(dir: &Path, id: u64, side: &str) -> PathBuf {
+    dir.join(format!("{}.{}", id, side))
+}
+
+/// A file without history has an empty one. A corrupt history is an error
+/// rather than empty, since starting over would reuse snapshot ids.
+fn read_history(dir: &Path) -> Result<EditHistory, String> {
+    let path = dir.join("history.json");
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(EditHistory::default()),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    serde_json::from_str(&content)
+        .map_err(|e| format!("{} is corrupt, fix or remove it: {}", path.display(), e))
+}
+
+fn write_history(dir: &Path, history: &EditHistory) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(history).map_err(|e| e.to_string())?;
+    std::fs::write(dir.join("history.json"), json)
+        .map_err(|e| format!("Failed to write edit history: {}", e))
+}
+
+fn remove_snapshots(dir: &Path, id: u64) {
+    let _ = std::fs::remove_file(snapshot_path(dir, id, "before"));
+    let _ = std::fs::remove_file(snapshot_path(dir, id, "after"));
+}
+
+/// Records an applied edit as byte-exact snapshots of the file before and
+/// after it. Anything that was undone is no longer redoable.
+fn record_edit(
+    working_dir: &str,
+    file: &str,
+    before: &[u8],
+    after: &[u8],
+    description: &str,
+) -> Result<(), String> {
+    let dir = history_dir(working_dir, file);
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create directory: {}", e))?;
+    let mut history = read_history(&dir)?;
+
+    for entry in history.entries.drain(history.position..) {
+        remove_snapshots(&dir, entry.id);
+    }
+
+    let id = history.next_id;
+    history.next_id += 1;
+    std::fs::write(snapshot_path(&dir, id, "before"), before)
+        .map_err(|e| format!("Failed to write snapshot: {}", e))?;
+    std::fs::write(snapshot_path(&dir, id, "after"), after)
+        .map_err(|e| format!("Failed to write snapshot: {}", e))?;
+    history.entries.push(EditEntry {
+        id,
+        description: description.to_string(),
+        created_at: timestamp_now(),
+    });
+
+    if history.entries.len() > MAX_EDIT_HISTORY {
+        let excess = history.entries.len() - MAX_EDIT_HISTORY;
+        for entry in history.entries.drain(..excess) {
+            remove_snapshots(&dir, entry.id);
+        }
+    }
+    history.position = history.entries.len();
+    write_history(&dir, &history)
+}
+
+/// Writes `content` to `file`, keeping its encoding and line endings, and
+/// records the change so it can be undone.
+pub fn write_recorded(
+    state: &AppState,
+    working_dir: &str,
+    file: &str,
+    content: &str,
+    description: &str,
+) -> Result<(), String> {
+    let file_path = workspace::resolve(working_dir, file, &SCHEMATIC)?;
+    let read = |path: &Path| {
+        std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+    };
+
+    let before = read(&file_path)?;
+    state.file_formats.write(&file_path, content)?;
+    let after = read(&file_path)?;
+    record_edit(working_dir, file, &before, &after, description)
+        .map_err(|e| format!("Edit applied but not recorded for undo: {}", e))
+}
+
+/// Replaces the file with the `to` snapshot of entry `id`, provided it still
+/// matches the `from` snapshot, so changes made since are never discarded.
+async fn restore(
+    state: &AppState,
+    working_dir: &str,
+    file: &str,
+    id: u64,
+    from: &str,
+    to: &str,
+) -> Result<WrittenFile, String> {
+    let dir = history_dir(working_dir, file);
+    let file_path = workspace::resolve(working_dir, file, &SCHEMATIC)?;
+    let read = |path: PathBuf| {
+        std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+    };
+
+    let current = read(file_path.clone())?;
+    if current != read(snapshot_path(&dir, id, from))? {
+        return Err(format!(
+            "{} has changed since this edit; save or discard those changes first",
+            file
+        ));
+    }
+    std::fs::write(&file_path, read(snapshot_path(&dir, id, to))?)
+        .map_err(|e| format!("Failed to write file: {}", e))?;
+    Ok(WrittenFile {
+        content: state.file_formats.read(&file_path)?,
+        reload: reload_ltspice(state, &file_path).await,
+    })
+}
+
+fn working_directory(state: &AppState) -> Result<String, String> {
+    state
+        .working_directory
+        .lock()
+        .map_err(|e| e.to_string())?
+        .clone()
+        .ok_or_else(|| "No working directory set".to_string())
+}
+
+async fn undo(state: &AppState, dir: &str, file: &str) -> Result<WrittenFile, String> {
+    let history_path = history_dir(dir, file);
+    let mut history = read_history(&history_path)?;
+    if history.position == 0 {
+        return Err("Nothing to undo".to_string());
+    }
+    let Some(entry) = history.entries.get(history.position - 1) else {
+        return Err(format!(
+            "The edit history of {} is corrupt: it is at edit {} of {}",
+            file,
+            history.position,
+            history.entries.len()
+        ));
+    };
+
+    let written = restore(state, dir, file, entry.id, "after", "before").await?;
+    history.position -= 1;
+    write_history(&history_path, &history)?;
+    Ok(written)
+}
+
+async fn redo(state: &AppState, dir: &str, file: &str) -> Result<WrittenFile, String> {
+    let history_path = history_dir(dir, file);
+    let mut history = read_history(&history_path)?;
+    let Some(entry) = history.entries.get(history.position) else {
+        return Err("Nothing to redo".to_string());
+    };
+
+    let written = restore(state, dir, file, entry.id, "before", "after").await?;
+    history.position += 1;
+    write_history(&history_path, &history)?;
+    Ok(written)
+}
+
+/// Reverts the most recent applied edit and returns the restored content.
+#[tauri::command]
+pub async fn undo_last_edit(
+    state: State<'_, AppState>,
+    file: String,
+) -> Result<WrittenFile, String> {
+    let dir = working_directory(&state)?;
+    undo(&state, &dir, &file).await
+}
+
+/// Re-applies the most recently undone edit and returns the new content.
+#[tauri::command]
+pub async fn redo_edit(state: State<'_, AppState>, file: String) -> Result<WrittenFile, String> {
+    let dir = working_directory(&state)?;
+    redo(&state, &dir, &file).await
+}
+
+#[tauri::command]
+pub fn list_edit_history(state: State<AppState>, file: String) -> Result<EditHistory, String> {
+    let dir = state.working_directory.lock().map_err(|e| e.to_string())?;
+    let dir = dir.as_ref().ok_or("No working directory set")?;
+    read_history(&history_dir(dir, &file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn working_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("spicy-edits-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.asc"), "Version 4\nSHEET 1 880 680\n").unwrap();
+        dir
+    }
+
+    fn content(dir: &Path) -> String {
+        std::fs::read_to_string(dir.join("a.asc")).unwrap()
+    }
+
+    #[tokio::test]
+    async fn undo_and_redo_walk_the_history() {
+        let base = working_dir("walk");
+        let dir = base.to_string_lossy();
+        let state = AppState::new();
+        let original = content(&base);
+
+        write_recorded(
+            &state,
+            &dir,
+            "a.asc",
+            "Version 4\nSHEET 1 800 600\n",
+            "Resize",
+        )
+        .unwrap();
+        write_recorded(
+            &state,
+            &dir,
+            "a.asc",
+            "Version 4\nSHEET 2 800 600\n",
+            "Renumber",
+        )
+        .unwrap();
+        let history = read_history(&history_dir(&dir, "a.asc")).unwrap();
+        let descriptions: Vec<_> = history
+            .entries
+            .iter()
+            .map(|e| e.description.as_str())
+            .collect();
+        assert_eq!(
+            (descriptions, history.position),
+            (vec!["Resize", "Renumber"], 2)
+        );
+
+        assert_eq!(
+            undo(&state, &dir, "a.asc").await.unwrap().content,
+            "Version 4\nSHEET 1 800 600\n"
+        );
+        assert_eq!(undo(&state, &dir, "a.asc").await.unwrap().content, original);
+        assert_eq!(
+            undo(&state, &dir, "a.asc").await.err().unwrap(),
+            "Nothing to undo"
+        );
+        assert_eq!(
+            redo(&state, &dir, "a.asc").await.unwrap().content,
+            "Version 4\nSHEET 1 800 600\n"
+        );
+        assert_eq!(content(&base), "Version 4\nSHEET 1 800 600\n");
+
+        // A new edit drops what was undone.
+        write_recorded(
+            &state,
+            &dir,
+            "a.asc",
+            "Version 4\nSHEET 3 800 600\n",
+            "Other",
+        )
+        .unwrap();
+        assert_eq!(
+            redo(&state, &dir, "a.asc").await.err().unwrap(),
+            "Nothing to redo"
+        );
+        let history = read_history(&history_dir(&dir, "a.asc")).unwrap();
+        assert_eq!(history.entries.len(), 2);
+        assert_eq!(history.entries[1].id, 2);
+        assert!(!snapshot_path(&history_dir(&dir, "a.asc"), 1, "after").exists());
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn keeps_only_the_latest_edits() {
+        let base = working_dir("trim");
+        let dir = base.to_string_lossy();
+        for i in 0..MAX_EDIT_HISTORY + 5 {
+            let edit = format!("edit {}", i);
+            record_edit(&dir, "a.asc", b"before", edit.as_bytes(), &edit).unwrap();
+        }
+
+        let history_path = history_dir(&dir, "a.asc");
+        let history = read_history(&history_path).unwrap();
+        assert_eq!(history.entries.len(), MAX_EDIT_HISTORY);
+        assert_eq!(history.position, MAX_EDIT_HISTORY);
+        assert_eq!(history.entries[0].id, 5);
+        assert!(!snapshot_path(&history_path, 4, "after").exists());
+        assert_eq!(
+            std::fs::read_to_string(snapshot_path(&history_path, 54, "after")).unwrap(),
+            "edit 54"
+        );
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[tokio::test]
+    async fn refuses_to_undo_over_outside_changes() {
+        let base = working_dir("outside");
+        let dir = base.to_string_lossy();
+        let state = AppState::new();
+        write_recorded(
+            &state,
+            &dir,
+            "a.asc",
+            "Version 4\nSHEET 1 800 600\n",
+            "Resize",
+        )
+        .unwrap();
+        std::fs::write(base.join("a.asc"), "Version 4\nSHEET 9 800 600\n").unwrap();
+
+        let err = undo(&state, &dir, "a.asc").await.err().unwrap();
+        assert!(err.contains("has changed since this edit"), "{}", err);
+        assert_eq!(content(&base), "Version 4\nSHEET 9 800 600\n");
+        assert_eq!(
+            read_history(&history_dir(&dir, "a.asc")).unwrap().position,
+            1
+        );
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[tokio::test]
+    async fn corrupt_history_is_left_alone() {
+        let base = working_dir("corrupt");
+        let dir = base.to_string_lossy();
+        let history_path = history_dir(&dir, "a.asc");
+        std::fs::create_dir_all(&history_path).unwrap();
+        std::fs::write(history_path.join("history.json"), "{ not json").unwrap();
+
+        let err = record_edit(&dir, "a.asc", b"before", b"after", "Edit").unwrap_err();
+        assert!(err.contains("is corrupt"), "{}", err);
+        let err = undo(&AppState::new(), &dir, "a.asc").await.err().unwrap();
+        assert!(err.contains("is corrupt"), "{}", err);
+        assert_eq!(
+            std::fs::read_to_string(history_path.join("history.json")).unwrap(),
+            "{ not json"
+        );
+        assert!(!snapshot_path(&history_path, 0, "before").exists());
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn files_in_subdirectories_get_their_own_history() {
+        assert_ne!(history_dir("w", "sub/a.asc"), history_dir("w", "sub_a.asc"));
+        assert_ne!(history_dir("w", "a b.asc"), history_dir("w", "a%20b.asc"));
+        assert_eq!(sanitize_filename("a.asc"), "a.asc");
+    }
+}
@@ -1,18 +1,106 @@
+use crate::keystore::{KeyInfo, DEFAULT_NAME};
+use crate::llm::client::ClientConfig;
+use crate::llm::{ProviderConfig, ProviderKind};
+use crate::settings::{self, Settings};
 use crate::state::AppState;
-use tauri::State;
+use crate::watcher::DirectoryWatcher;
+use crate::workspace::{self, SCHEMATIC};
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, Manager, State};
 
 #[tauri::command]
-pub fn set_working_directory(state: State<AppState>, path: String) -> Result<(), String> {
-    let mut dir = state.working_directory.lock().map_err(|e| e.to_string())?;
-    *dir = Some(path);
-    Ok(())
+pub fn set_working_directory(
+    app: AppHandle,
+    state: State<AppState>,
+    path: String,
+) -> Result<(), String> {
+    {
+        let mut dir = state.working_directory.lock().map_err(|e| e.to_string())?;
+        *dir = Some(path.clone());
+    }
+    watch_working_directory(&app);
+    update(&state, |settings| settings.add_recent_directory(&path))
+}
+
+/// Replaces the watcher with one for the current working directory, which
+/// emits `files-changed` with a batch of `FileEvent`s. Without a watcher the
+/// app still works; the UI just is not told about outside changes.
+pub(crate) fn watch_working_directory(app: &AppHandle) {
+    let state = app.state::<AppState>();
+    let Ok(mut watcher) = state.watcher.lock() else {
+        return;
+    };
+    // Stop the old watcher before the new one starts.
+    *watcher = None;
+    let dir = state.working_directory.lock().ok().and_then(|d| d.clone());
+    let Some(dir) = dir else {
+        return;
+    };
+    let emitter = app.clone();
+    *watcher = DirectoryWatcher::start(dir.into(), move |events| {
+        let _ = emitter.emit("files-changed", events);
+    })
+    .ok();
+}
+
+/// Changes the settings under their lock, then saves them.
+fn update(state: &AppState, change: impl FnOnce(&mut Settings)) -> Result<(), String> {
+    change(&mut *state.settings.lock().map_err(|e| e.to_string())?);
+    state.save_settings()
+}
+
+fn current_provider(state: &AppState) -> Result<ProviderKind, String> {
+    Ok(state
+        .settings
+        .lock()
+        .map_err(|e| e.to_string())?
+        .provider
+        .kind)
+}
+
+/// Stores a key for the current provider and makes it the active one.
+/// Without a name the provider's default key is replaced.
+#[tauri::command]
+pub fn set_api_key(
+    state: State<AppState>,
+    key: String,
+    name: Option<String>,
+) -> Result<(), String> {
+    let provider = current_provider(&state)?;
+    let name = name.unwrap_or_else(|| DEFAULT_NAME.to_string());
+    state.key_store()?.set(provider, &name, &key)?;
+    state.refresh_api_key()
 }
 
+/// Deletes a key of the current provider; the active one when no name is given.
 #[tauri::command]
-pub fn set_api_key(state: State<AppState>, key: String) -> Result<(), String> {
-    let mut api_key = state.api_key.lock().map_err(|e| e.to_string())?;
-    *api_key = key;
-    Ok(())
+pub fn clear_api_key(state: State<AppState>, name: Option<String>) -> Result<(), String> {
+    let provider = current_provider(&state)?;
+    let store = state.key_store()?;
+    let name = match name {
+        Some(name) => Some(name),
+        None => store.active(provider)?,
+    };
+    let Some(name) = name else {
+        return Ok(());
+    };
+    store.delete(provider, &name)?;
+    state.refresh_api_key()
+}
+
+#[tauri::command]
+pub fn list_api_keys(state: State<AppState>) -> Result<Vec<KeyInfo>, String> {
+    state.key_store()?.list()
+}
+
+#[tauri::command]
+pub fn select_api_key(
+    state: State<AppState>,
+    provider: ProviderKind,
+    name: String,
+) -> Result<(), String> {
+    state.key_store()?.select(provider, &name)?;
+    state.refresh_api_key()
 }
 
 #[tauri::command]
@@ -21,6 +109,68 @@ pub fn has_api_key(state: State<AppState>) -> Result<bool, String> {
     Ok(!api_key.is_empty())
 }
 
+/// Chooses the chat backend: OpenRouter, an OpenAI-compatible server or
+/// the Anthropic API.
+#[tauri::command]
+pub fn set_provider(state: State<AppState>, config: ProviderConfig) -> Result<(), String> {
+    update(&state, |settings| settings.provider = config)?;
+    state.refresh_api_key()
+}
+
+#[tauri::command]
+pub fn get_provider(state: State<AppState>) -> Result<ProviderConfig, String> {
+    let settings = state.settings.lock().map_err(|e| e.to_string())?;
+    Ok(settings.provider.clone())
+}
+
+/// Sets the proxy, certificates, headers and timeouts of the chat client.
+/// The configuration is only kept when a client can be built from it.
+#[tauri::command]
+pub fn set_client_config(state: State<AppState>, config: ClientConfig) -> Result<(), String> {
+    let client = config.build()?;
+    *state.http_client.lock().map_err(|e| e.to_string())? = client;
+    update(&state, |settings| settings.client = config)
+}
+
+#[tauri::command]
+pub fn get_client_config(state: State<AppState>) -> Result<ClientConfig, String> {
+    let settings = state.settings.lock().map_err(|e| e.to_string())?;
+    Ok(settings.client.clone())
+}
+
+#[tauri::command]
+pub fn get_settings(state: State<AppState>) -> Result<Settings, String> {
+    let settings = state.settings.lock().map_err(|e| e.to_string())?;
+    Ok(settings.clone())
+}
+
+/// Merges `patch`, a partial settings object, into the settings and returns
+/// the result. Nothing changes when the merged settings are invalid.
+#[tauri::command]
+pub fn update_settings(state: State<AppState>, patch: Value) -> Result<Settings, String> {
+    let updated = {
+        let mut settings = state.settings.lock().map_err(|e| e.to_string())?;
+        let mut value = serde_json::to_value(&*settings).map_err(|e| e.to_string())?;
+        settings::merge(&mut value, patch);
+        let mut updated: Settings =
+            serde_json::from_value(value).map_err(|e| format!("Invalid settings: {}", e))?;
+        updated.version = settings::CURRENT_VERSION;
+        *state.http_client.lock().map_err(|e| e.to_string())? = updated.client.build()?;
+        *settings = updated.clone();
+        updated
+    };
+    state.refresh_api_key()?;
+    state.save_settings()?;
+    Ok(updated)
+}
+
+#[tauri::command]
+pub fn set_symbol_library_path(state: State<AppState>, path: Option<String>) -> Result<(), String> {
+    update(&state, |settings| {
+        settings.symbol_library_path = path.filter(|p| !p.is_empty())
+    })
+}
+
 fn collect_asc_files(dir: &std::path::Path, base: &std::path::Path, files: &mut Vec<String>) {
     if let Ok(entries) = std::fs::read_dir(dir) {
         for entry in entries.flatten() {
@@ -56,6 +206,6 @@ pub fn read_asc_file(state: State<AppState>, filename: String) -> Result<String,
     let dir = state.working_directory.lock().map_err(|e| e.to_string())?;
     let dir = dir.as_ref().ok_or("No working directory set")?;
 
-    let path = std::path::Path::new(dir).join(&filename);
-    std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", filename, e))
+    let path = workspace::resolve(dir, &filename, &SCHEMATIC)?;
+    state.file_formats.read(&path)
 }
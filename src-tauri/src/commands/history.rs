@@ -1,6 +1,7 @@
 use crate::state::AppState;
+use crate::workspace::{self, SCHEMATIC};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 use tauri::State;
 
@@ -36,7 +37,7 @@ pub struct SessionData {
     pub messages: Vec<StoredMessage>,
 }
 
-fn timestamp_now() -> String {
+pub(crate) fn timestamp_now() -> String {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap_or_default()
@@ -44,33 +45,45 @@ fn timestamp_now() -> String {
         .to_string()
 }
 
-fn sanitize_filename(name: &str) -> String {
-    name.chars()
-        .map(|c| match c {
-            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' | ' ' => '_',
-            _ => c,
-        })
-        .collect()
+/// Turns a relative path into a single directory name. Characters that are
+/// not allowed in names, and `%` itself, are written as `%XX`, so distinct
+/// paths such as `sub/a.asc` and `sub_a.asc` never share a directory.
+pub(crate) fn sanitize_filename(name: &str) -> String {
+    let mut sanitized = String::with_capacity(name.len());
+    for c in name.chars() {
+        match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' | ' ' | '%' => {
+                sanitized.push_str(&format!("%{:02X}", c as u32))
+            }
+            _ => sanitized.push(c),
+        }
+    }
+    sanitized
 }
 
-fn chats_dir(working_dir: &str, file: &str) -> PathBuf {
-    PathBuf::from(working_dir)
+/// Where the sessions about `file` are kept; `file` must be a schematic in
+/// the working directory.
+fn chats_dir(working_dir: &str, file: &str) -> Result<PathBuf, String> {
+    workspace::resolve(working_dir, file, &SCHEMATIC)?;
+    Ok(PathBuf::from(working_dir)
         .join(".spicy")
         .join("chats")
-        .join(sanitize_filename(file))
+        .join(sanitize_filename(file)))
 }
 
-fn read_index(dir: &PathBuf) -> SessionIndex {
+fn session_path(chat_dir: &Path, session_id: &str) -> Result<PathBuf, String> {
+    Ok(chat_dir.join(format!("{}.json", workspace::file_name(session_id)?)))
+}
+
+fn read_index(dir: &Path) -> SessionIndex {
     let index_path = dir.join("sessions.json");
     match std::fs::read_to_string(&index_path) {
-        Ok(content) => {
-            serde_json::from_str(&content).unwrap_or(SessionIndex { sessions: vec![] })
-        }
+        Ok(content) => serde_json::from_str(&content).unwrap_or(SessionIndex { sessions: vec![] }),
         Err(_) => SessionIndex { sessions: vec![] },
     }
 }
 
-fn write_index(dir: &PathBuf, index: &SessionIndex) -> Result<(), String> {
+fn write_index(dir: &Path, index: &SessionIndex) -> Result<(), String> {
     std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create directory: {}", e))?;
     let json = serde_json::to_string_pretty(index).map_err(|e| e.to_string())?;
     std::fs::write(dir.join("sessions.json"), json)
@@ -81,7 +94,7 @@ fn write_index(dir: &PathBuf, index: &SessionIndex) -> Result<(), String> {
 pub fn list_chat_sessions(state: State<AppState>, file: String) -> Result<SessionIndex, String> {
     let dir = state.working_directory.lock().map_err(|e| e.to_string())?;
     let dir = dir.as_ref().ok_or("No working directory set")?;
-    let chat_dir = chats_dir(dir, &file);
+    let chat_dir = chats_dir(dir, &file)?;
     let mut index = read_index(&chat_dir);
     // Sort by most recently updated
     index
@@ -98,7 +111,7 @@ pub fn load_chat_session(
 ) -> Result<SessionData, String> {
     let dir = state.working_directory.lock().map_err(|e| e.to_string())?;
     let dir = dir.as_ref().ok_or("No working directory set")?;
-    let session_path = chats_dir(dir, &file).join(format!("{}.json", session_id));
+    let session_path = session_path(&chats_dir(dir, &file)?, &session_id)?;
     let content = std::fs::read_to_string(&session_path)
         .map_err(|e| format!("Failed to read session: {}", e))?;
     serde_json::from_str(&content).map_err(|e| format!("Failed to parse session: {}", e))
@@ -112,15 +125,14 @@ pub fn save_chat_session(
 ) -> Result<(), String> {
     let dir = state.working_directory.lock().map_err(|e| e.to_string())?;
     let dir = dir.as_ref().ok_or("No working directory set")?;
-    let chat_dir = chats_dir(dir, &file);
+    let chat_dir = chats_dir(dir, &file)?;
+    let session_path = session_path(&chat_dir, &session.id)?;
 
-    std::fs::create_dir_all(&chat_dir)
-        .map_err(|e| format!("Failed to create directory: {}", e))?;
+    std::fs::create_dir_all(&chat_dir).map_err(|e| format!("Failed to create directory: {}", e))?;
 
     // Write session file
     let json = serde_json::to_string_pretty(&session).map_err(|e| e.to_string())?;
-    std::fs::write(chat_dir.join(format!("{}.json", session.id)), json)
-        .map_err(|e| format!("Failed to write session: {}", e))?;
+    std::fs::write(session_path, json).map_err(|e| format!("Failed to write session: {}", e))?;
 
     // Update index
     let mut index = read_index(&chat_dir);
@@ -154,10 +166,10 @@ pub fn delete_chat_session(
 ) -> Result<(), String> {
     let dir = state.working_directory.lock().map_err(|e| e.to_string())?;
     let dir = dir.as_ref().ok_or("No working directory set")?;
-    let chat_dir = chats_dir(dir, &file);
+    let chat_dir = chats_dir(dir, &file)?;
 
     // Remove session file
-    let session_path = chat_dir.join(format!("{}.json", session_id));
+    let session_path = session_path(&chat_dir, &session_id)?;
     if session_path.exists() {
         std::fs::remove_file(&session_path)
             .map_err(|e| format!("Failed to delete session: {}", e))?;
@@ -0,0 +1,63 @@
+use crate::ltspice::{self, Installation, Integration, ReloadStatus, WineConfig};
+use crate::state::AppState;
+use crate::workspace::{self, SCHEMATIC};
+use serde::Serialize;
+use std::path::Path;
+use tauri::State;
+
+/// A file a command has just written, and whether LTspice now shows it.
+#[derive(Serialize)]
+pub struct WrittenFile {
+    pub content: String,
+    pub reload: ReloadStatus,
+}
+
+/// The configured executable and Wine setup.
+pub(crate) fn ltspice_settings(state: &AppState) -> Result<(Option<String>, WineConfig), String> {
+    let settings = state.settings.lock().map_err(|e| e.to_string())?;
+    Ok((settings.ltspice_path.clone(), settings.wine.clone()))
+}
+
+pub(crate) fn locate(state: &AppState) -> Result<Option<Installation>, String> {
+    let (configured, wine) = ltspice_settings(state)?;
+    Ok(Installation::locate(
+        configured.as_deref().map(Path::new),
+        &wine,
+    ))
+}
+
+/// The LTspice backend for the current settings.
+pub(crate) fn integration(state: &AppState) -> Result<Box<dyn Integration>, String> {
+    let (configured, wine) = ltspice_settings(state)?;
+    Ok(ltspice::integration(configured.as_deref(), &wine))
+}
+
+/// Makes a running LTspice show `path` as written behind its back. Driving
+/// LTspice can take a while, so it happens on a blocking thread.
+pub(crate) async fn reload_ltspice(state: &AppState, path: &Path) -> ReloadStatus {
+    let integration = match integration(state) {
+        Ok(integration) => integration,
+        Err(message) => return ReloadStatus::Failed { message },
+    };
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || integration.reload(&path))
+        .await
+        .unwrap_or_else(|e| ReloadStatus::Failed {
+            message: e.to_string(),
+        })
+}
+
+#[tauri::command]
+pub fn locate_ltspice(state: State<AppState>) -> Result<Option<Installation>, String> {
+    locate(&state)
+}
+
+/// Opens `file` from the working directory in LTspice, simulating it when
+/// `run` is set.
+#[tauri::command]
+pub fn open_in_ltspice(state: State<AppState>, file: String, run: bool) -> Result<(), String> {
+    let dir = state.working_directory.lock().map_err(|e| e.to_string())?;
+    let dir = dir.as_ref().ok_or("No working directory set")?;
+    let path = workspace::resolve(dir, &file, &SCHEMATIC)?;
+    integration(&state)?.open(&path, run)
+}
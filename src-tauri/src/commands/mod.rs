@@ -1,3 +1,9 @@
 pub mod chat;
+pub mod edit_history;
 pub mod files;
 pub mod history;
+pub mod ltspice;
+pub mod proposals;
+pub mod schematic;
+pub mod simulation;
+pub mod tools;
@@ -0,0 +1,111 @@
+use crate::commands::chat::FileChange;
+use crate::commands::edit_history::write_recorded;
+use crate::commands::history::timestamp_now;
+use crate::commands::ltspice::{reload_ltspice, WrittenFile};
+use crate::state::AppState;
+use crate::workspace::{self, SCHEMATIC};
+use serde::Serialize;
+use similar::TextDiff;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tauri::State;
+
+/// Keeps ids unique when several proposals are made in the same millisecond.
+static NEXT_PROPOSAL: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Clone, Serialize)]
+pub struct Proposal {
+    pub id: String,
+    pub file: String,
+    /// Unified diff from the file as it was to `content`.
+    pub diff: String,
+    pub content: String,
+    pub explanation: String,
+    pub changes: Vec<FileChange>,
+    /// Content the edit was computed against, to catch later changes.
+    #[serde(skip)]
+    base: String,
+}
+
+impl Proposal {
+    pub fn new(
+        file: &str,
+        base: String,
+        content: String,
+        explanation: &str,
+        changes: Vec<FileChange>,
+    ) -> Self {
+        Self {
+            id: format!(
+                "proposal-{}-{}",
+                timestamp_now(),
+                NEXT_PROPOSAL.fetch_add(1, Ordering::Relaxed)
+            ),
+            file: file.to_string(),
+            diff: unified_diff(file, &base, &content),
+            content,
+            explanation: explanation.to_string(),
+            changes,
+            base,
+        }
+    }
+}
+
+pub fn unified_diff(file: &str, before: &str, after: &str) -> String {
+    TextDiff::from_lines(before, after)
+        .unified_diff()
+        .context_radius(3)
+        .header(&format!("a/{}", file), &format!("b/{}", file))
+        .to_string()
+}
+
+/// Writes a pending proposal, provided the file has not changed since it
+/// was made, and returns the new content.
+#[tauri::command]
+pub async fn accept_proposal(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<WrittenFile, String> {
+    let dir = state
+        .working_directory
+        .lock()
+        .map_err(|e| e.to_string())?
+        .clone()
+        .ok_or("No working directory set")?;
+    let proposal = state
+        .proposals
+        .lock()
+        .map_err(|e| e.to_string())?
+        .remove(&id)
+        .ok_or_else(|| format!("No pending proposal {}", id))?;
+
+    let path = workspace::resolve(&dir, &proposal.file, &SCHEMATIC)?;
+    let current = state.file_formats.read(&path)?;
+    if current != proposal.base {
+        return Err(format!(
+            "{} has changed since this proposal was made; ask for the change again",
+            proposal.file
+        ));
+    }
+    write_recorded(
+        &state,
+        &dir,
+        &proposal.file,
+        &proposal.content,
+        &proposal.explanation,
+    )?;
+    Ok(WrittenFile {
+        reload: reload_ltspice(&state, &path).await,
+        content: proposal.content,
+    })
+}
+
+#[tauri::command]
+pub fn reject_proposal(state: State<AppState>, id: String) -> Result<(), String> {
+    state
+        .proposals
+        .lock()
+        .map_err(|e| e.to_string())?
+        .remove(&id)
+        .map(|_| ())
+        .ok_or_else(|| format!("No pending proposal {}", id))
+}
@@ -0,0 +1,75 @@
+use crate::asc::Schematic;
+use crate::connectivity::{self, NetGraph};
+use crate::netlist;
+use crate::state::AppState;
+use crate::symbols::SymbolLibrary;
+use crate::workspace::{self, NETLIST, SCHEMATIC};
+use std::path::PathBuf;
+use tauri::State;
+
+pub fn load_schematic(state: &AppState, dir: &str, file: &str) -> Result<Schematic, String> {
+    let path = workspace::resolve(dir, file, &SCHEMATIC)?;
+    let content = state.file_formats.read(&path)?;
+    Schematic::parse(&content).map_err(|e| format!("Failed to parse {}: {}", file, e))
+}
+
+/// Symbol search path for `file`: its own directory, the working directory,
+/// the user's library, then the bundled symbols. Only the common primitives
+/// are bundled; point the user library at LTspice's `lib/sym` for the rest.
+pub fn symbol_library(state: &AppState, dir: &str, file: &str) -> Result<SymbolLibrary, String> {
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    if let Some(parent) = workspace::resolve(dir, file, &SCHEMATIC)?.parent() {
+        dirs.push(parent.to_path_buf());
+    }
+    dirs.push(PathBuf::from(dir));
+    let user = state
+        .settings
+        .lock()
+        .map_err(|e| e.to_string())?
+        .symbol_library_path
+        .clone();
+    if let Some(user) = user {
+        dirs.push(PathBuf::from(user));
+    }
+    if let Some(bundled) = state.bundled_symbols.get() {
+        dirs.push(bundled.clone());
+    }
+    dirs.dedup();
+    Ok(SymbolLibrary::new(dirs))
+}
+
+#[tauri::command]
+pub fn get_netlist_graph(state: State<AppState>, file: String) -> Result<NetGraph, String> {
+    let dir = state.working_directory.lock().map_err(|e| e.to_string())?;
+    let dir = dir.as_ref().ok_or("No working directory set")?;
+    let schematic = load_schematic(&state, dir, &file)?;
+    let library = symbol_library(&state, dir, &file)?;
+    Ok(connectivity::extract(&schematic, &library))
+}
+
+/// Netlists `file` as LTspice would, with the schematic's absolute path as
+/// the title line.
+pub fn build_netlist(state: &AppState, dir: &str, file: &str) -> Result<String, String> {
+    let schematic = load_schematic(state, dir, file)?;
+    let library = symbol_library(state, dir, file)?;
+    let graph = connectivity::extract(&schematic, &library);
+    let title = workspace::resolve(dir, file, &SCHEMATIC)?;
+    Ok(netlist::generate(
+        &title.to_string_lossy(),
+        &schematic,
+        &graph,
+        &library,
+    ))
+}
+
+/// Writes `<name>.net` next to the schematic and returns its content.
+#[tauri::command]
+pub fn export_netlist(state: State<AppState>, file: String) -> Result<String, String> {
+    let dir = state.working_directory.lock().map_err(|e| e.to_string())?;
+    let dir = dir.as_ref().ok_or("No working directory set")?;
+    let netlist = build_netlist(&state, dir, &file)?;
+    let path = workspace::resolve(dir, &workspace::with_extension(&file, "net"), &NETLIST)?;
+    std::fs::write(&path, &netlist)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(netlist)
+}
@@ -0,0 +1,380 @@
+use crate::commands::ltspice;
+use crate::commands::schematic::build_netlist;
+use crate::logfile::{self, SimulationReport};
+use crate::raw::{self, Series, Variable};
+use crate::settings::SimulatorChoice;
+use crate::simulator::ltspice::Ltspice;
+use crate::simulator::ngspice::Ngspice;
+use crate::simulator::{self, SimulationJob, SimulationRun, Simulator};
+use crate::state::AppState;
+use crate::workspace::{self, LOG, NETLIST, RAW};
+use serde::Serialize;
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::ipc::Channel;
+use tauri::State;
+
+const SIMULATION_TIMEOUT: Duration = Duration::from_secs(300);
+const DEFAULT_MAX_POINTS: usize = 2000;
+
+#[derive(Clone, Serialize)]
+#[serde(tag = "type")]
+pub enum SimulationEvent {
+    #[serde(rename = "started")]
+    Started { simulator: String },
+    #[serde(rename = "output")]
+    Output { line: String },
+    #[serde(rename = "finished")]
+    Finished { success: bool, elapsed_ms: u64 },
+}
+
+/// LTspice, unless the settings ask for ngspice, which is also the fallback
+/// when LTspice cannot be found and no choice was made.
+pub fn select_simulator(state: &AppState) -> Result<Box<dyn Simulator>, String> {
+    let choice = state.settings.lock().map_err(|e| e.to_string())?.simulator;
+    if choice != SimulatorChoice::Ngspice {
+        let integration = ltspice::integration(state)?;
+        if integration.available() {
+            return Ok(Box::new(Ltspice::new(integration)));
+        }
+        if choice == SimulatorChoice::Ltspice {
+            return Err("LTspice was not found; set its path in the settings".to_string());
+        }
+    }
+    Ngspice::locate()
+        .map(|s| Box::new(s) as Box<dyn Simulator>)
+        .ok_or_else(|| "ngspice not found on PATH".to_string())
+}
+
+/// Netlists `file`, runs it on `simulator` and records the run as the
+/// file's latest. `analysis` replaces the schematic's own analysis
+/// directives.
+pub async fn run_simulation(
+    state: &AppState,
+    simulator: Box<dyn Simulator>,
+    dir: &str,
+    file: &str,
+    analysis: Option<&str>,
+    mut on_output: impl FnMut(&str) + Send + 'static,
+) -> Result<SimulationRun, String> {
+    // The deck, waveform and log go next to the schematic, as LTspice does.
+    let beside = |extension, access| {
+        workspace::resolve(dir, &workspace::with_extension(file, extension), access)
+    };
+    let job = SimulationJob {
+        netlist_path: beside("cir", &NETLIST)?,
+        raw_path: beside("raw", &RAW)?,
+        log_path: beside("log", &LOG)?,
+        timeout: SIMULATION_TIMEOUT,
+    };
+    // A run that fails before the simulator starts must not leave the
+    // previous run's results looking current.
+    state
+        .simulations
+        .lock()
+        .map_err(|e| e.to_string())?
+        .remove(file);
+    for path in [&job.raw_path, &job.log_path] {
+        let _ = std::fs::remove_file(path);
+    }
+
+    let mut netlist = build_netlist(state, dir, file)?;
+    if let Some(analysis) = analysis.filter(|a| !a.trim().is_empty()) {
+        netlist = simulator::apply_analysis(&netlist, analysis);
+    }
+    std::fs::write(&job.netlist_path, simulator.prepare_netlist(&netlist))
+        .map_err(|e| format!("Failed to write netlist: {}", e))?;
+
+    let netlist_path = job.netlist_path.clone();
+    let outcome = tokio::task::spawn_blocking(move || simulator.run(&job, &mut on_output))
+        .await
+        .map_err(|e| e.to_string())??;
+
+    let run = SimulationRun {
+        id: format!(
+            "sim-{}",
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis()
+        ),
+        file: file.to_string(),
+        netlist_path,
+        outcome,
+    };
+    state
+        .simulations
+        .lock()
+        .map_err(|e| e.to_string())?
+        .insert(file.to_string(), run.clone());
+    Ok(run)
+}
+
+#[tauri::command]
+pub async fn simulate(
+    state: State<'_, AppState>,
+    file: String,
+    analysis: Option<String>,
+    on_event: Channel<SimulationEvent>,
+) -> Result<SimulationRun, String> {
+    let dir = state
+        .working_directory
+        .lock()
+        .map_err(|e| e.to_string())?
+        .clone()
+        .ok_or("No working directory set")?;
+
+    let simulator = select_simulator(&state)?;
+    let _ = on_event.send(SimulationEvent::Started {
+        simulator: simulator.name().to_string(),
+    });
+    let output = on_event.clone();
+    let run = run_simulation(
+        &state,
+        simulator,
+        &dir,
+        &file,
+        analysis.as_deref(),
+        move |line| {
+            let _ = output.send(SimulationEvent::Output {
+                line: line.to_string(),
+            });
+        },
+    )
+    .await?;
+    let _ = on_event.send(SimulationEvent::Finished {
+        success: run.outcome.success,
+        elapsed_ms: run.outcome.elapsed_ms,
+    });
+    Ok(run)
+}
+
+#[derive(Serialize)]
+pub struct Trace {
+    pub name: String,
+    pub kind: String,
+    /// One series per `.step` run.
+    pub steps: Vec<Series>,
+}
+
+#[derive(Serialize)]
+pub struct Waveforms {
+    pub title: String,
+    pub plotname: String,
+    pub flags: Vec<String>,
+    pub axis: Variable,
+    pub points: usize,
+    pub step_count: usize,
+    pub traces: Vec<Trace>,
+}
+
+/// Loads a .raw file relative to the working directory. `traces` limits the
+/// result to the named variables; every variable is returned otherwise.
+#[tauri::command]
+pub fn load_waveforms(
+    state: State<AppState>,
+    path: String,
+    traces: Option<Vec<String>>,
+    max_points: Option<usize>,
+) -> Result<Waveforms, String> {
+    let dir = state.working_directory.lock().map_err(|e| e.to_string())?;
+    let dir = dir.as_ref().ok_or("No working directory set")?;
+    let raw = raw::load(&workspace::resolve(dir, &path, &RAW)?)?;
+    let max_points = max_points.unwrap_or(DEFAULT_MAX_POINTS);
+
+    let selected: Vec<&Variable> = match &traces {
+        Some(names) => names
+            .iter()
+            .map(|n| {
+                raw.variable(n)
+                    .ok_or_else(|| format!("No trace named {} in {}", n, path))
+            })
+            .collect::<Result<_, _>>()?,
+        None => raw.variables.iter().skip(1).collect(),
+    };
+
+    Ok(Waveforms {
+        title: raw.title.clone(),
+        plotname: raw.plotname.clone(),
+        flags: raw.flags.clone(),
+        axis: raw.variables[0].clone(),
+        points: raw.points(),
+        step_count: raw.steps.len(),
+        traces: selected
+            .into_iter()
+            .map(|v| Trace {
+                name: v.name.clone(),
+                kind: v.kind.clone(),
+                steps: raw.series(v.index, max_points),
+            })
+            .collect(),
+    })
+}
+
+/// The .raw or .log of `file`'s latest run, or the one beside the schematic
+/// so runs made outside the app are picked up too.
+fn run_output(
+    state: &AppState,
+    dir: &str,
+    file: &str,
+    extension: &str,
+) -> Result<Option<PathBuf>, String> {
+    let recorded = state
+        .simulations
+        .lock()
+        .map_err(|e| e.to_string())?
+        .get(file)
+        .and_then(|run| match extension {
+            "raw" => run.outcome.raw_path.clone(),
+            _ => run.outcome.log_path.clone(),
+        });
+    let path = match recorded {
+        Some(path) => path,
+        None => {
+            let access = if extension == "raw" { &RAW } else { &LOG };
+            workspace::resolve(dir, &workspace::with_extension(file, extension), access)?
+        }
+    };
+    Ok(path.is_file().then_some(path))
+}
+
+/// Parses the log of `file`'s latest run.
+pub fn simulation_report(
+    state: &AppState,
+    dir: &str,
+    file: &str,
+) -> Result<SimulationReport, String> {
+    let log_path = run_output(state, dir, file, "log")?
+        .ok_or_else(|| format!("No simulation log for {}", file))?;
+    logfile::load(&log_path)
+}
+
+#[tauri::command]
+pub fn get_simulation_report(
+    state: State<AppState>,
+    file: String,
+) -> Result<SimulationReport, String> {
+    let dir = state.working_directory.lock().map_err(|e| e.to_string())?;
+    let dir = dir.as_ref().ok_or("No working directory set")?;
+    simulation_report(&state, dir, &file)
+}
+
+/// Traces summarized when the user does not name any.
+const MAX_SUMMARY_TRACES: usize = 8;
+/// Steps listed per trace before the rest are elided.
+const MAX_SUMMARY_STEPS: usize = 10;
+
+fn format_stats(stats: &raw::Stats, unit: &str) -> String {
+    let mut out = format!("min {:.4e}, max {:.4e}", stats.min, stats.max);
+    if let Some(rms) = stats.rms {
+        let _ = write!(out, ", rms {:.4e}", rms);
+    }
+    let _ = write!(out, ", final {:.4e}", stats.final_value);
+    if let Some(t) = stats.settling_time {
+        let _ = write!(out, ", settles at {:.4e} {}", t, unit);
+    }
+    out
+}
+
+/// Text summary of `file`'s latest simulation for the chat prompt: log
+/// errors and `.meas` results, operating-point node voltages, and waveform
+/// statistics for `traces` (or the first few node voltages when empty).
+/// Returns None when the file has never been simulated.
+pub fn simulation_summary(
+    state: &AppState,
+    dir: &str,
+    file: &str,
+    traces: &[String],
+) -> Result<Option<String>, String> {
+    let log = run_output(state, dir, file, "log")?;
+    let raw_path = run_output(state, dir, file, "raw")?;
+    if log.is_none() && raw_path.is_none() {
+        return Ok(None);
+    }
+
+    let mut out = String::from("Simulation results:\n");
+    if let Some(log) = log {
+        let report = logfile::load(&log)?;
+        for (title, diagnostics) in [("Errors", &report.errors), ("Warnings", &report.warnings)] {
+            if !diagnostics.is_empty() {
+                let _ = writeln!(out, "{}:", title);
+                for d in diagnostics {
+                    let _ = writeln!(out, "- {}", d.message);
+                }
+            }
+        }
+        if !report.measurements.is_empty() {
+            out.push_str("Measurements:\n");
+            for m in &report.measurements {
+                let value = m
+                    .value
+                    .map(|v| format!("{:.6e}", v))
+                    .unwrap_or_else(|| "FAILED".to_string());
+                let step = m.step.map(|s| format!(" (step {})", s)).unwrap_or_default();
+                let _ = writeln!(out, "- {}{} = {} {}", m.name, step, value, m.detail);
+            }
+        }
+    }
+
+    let Some(raw_path) = raw_path else {
+        return Ok(Some(out));
+    };
+    let raw = match raw::load(&raw_path) {
+        Ok(raw) => raw,
+        Err(e) => {
+            let _ = writeln!(out, "Waveforms unavailable: {}", e);
+            return Ok(Some(out));
+        }
+    };
+
+    if raw
+        .plotname
+        .to_ascii_lowercase()
+        .contains("operating point")
+    {
+        out.push_str("Operating point:\n");
+        for v in raw.variables.iter().skip(1) {
+            if let Some(value) = raw.values[v.index].magnitude().first() {
+                let _ = writeln!(out, "- {} = {:.6e}", v.name, value);
+            }
+        }
+        return Ok(Some(out));
+    }
+
+    let selected: Vec<&Variable> = if traces.is_empty() {
+        raw.variables
+            .iter()
+            .filter(|v| v.kind == "voltage")
+            .take(MAX_SUMMARY_TRACES)
+            .collect()
+    } else {
+        traces.iter().filter_map(|t| raw.variable(t)).collect()
+    };
+    let axis = &raw.variables[0];
+    let unit = if axis.kind == "time" { "s" } else { "" };
+    let _ = writeln!(
+        out,
+        "{} ({} vs {}, {} step{}):",
+        raw.plotname,
+        if raw.is_complex() { "dB" } else { "value" },
+        axis.name,
+        raw.steps.len(),
+        if raw.steps.len() == 1 { "" } else { "s" }
+    );
+    for v in selected {
+        let stats = raw.stats(v.index);
+        if let [single] = stats.as_slice() {
+            let _ = writeln!(out, "- {}: {}", v.name, format_stats(single, unit));
+            continue;
+        }
+        let _ = writeln!(out, "- {}:", v.name);
+        for (i, s) in stats.iter().take(MAX_SUMMARY_STEPS).enumerate() {
+            let _ = writeln!(out, "  step {}: {}", i + 1, format_stats(s, unit));
+        }
+        if stats.len() > MAX_SUMMARY_STEPS {
+            let _ = writeln!(out, "  ... {} more steps", stats.len() - MAX_SUMMARY_STEPS);
+        }
+    }
+    Ok(Some(out))
+}
@@ -0,0 +1,144 @@
+use crate::commands::chat::{file_context, pin_positions_context};
+use crate::commands::schematic::{load_schematic, symbol_library};
+use crate::connectivity;
+use crate::state::AppState;
+use crate::workspace::{self, SCHEMATIC};
+use serde_json::{json, Value};
+
+fn function(name: &str, description: &str, parameters: Value) -> Value {
+    json!({
+        "type": "function",
+        "function": {
+            "name": name,
+            "description": description,
+            "parameters": parameters,
+        }
+    })
+}
+
+/// Tool schemas sent with every request in tool mode.
+pub fn definitions() -> Vec<Value> {
+    let operation = json!({
+        "type": "object",
+        "properties": {
+            "op": {
+                "type": "string",
+                "enum": ["set_value", "insert_series", "add_parallel", "remove_component", "add_directive", "rename"]
+            }
+        },
+        "required": ["op"]
+    });
+    let line_edit = json!({
+        "type": "object",
+        "properties": {
+            "start": { "type": "integer" },
+            "end": { "type": "integer" },
+            "replacement": { "type": "string" }
+        },
+        "required": ["start", "end", "replacement"]
+    });
+    let change = json!({
+        "type": "object",
+        "properties": {
+            "component": { "type": "string" },
+            "filename": { "type": "string" },
+            "description": { "type": "string" }
+        },
+        "required": ["filename", "description"]
+    });
+
+    vec![
+        function(
+            "read_file",
+            "Returns a schematic with line numbers, followed by its pin positions.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "file": {
+                        "type": "string",
+                        "description": "Path relative to the working directory; the active file when omitted."
+                    }
+                }
+            }),
+        ),
+        function(
+            "list_components",
+            "Lists every component of the active file with its symbol, rotation, pin coordinates and nets.",
+            json!({ "type": "object", "properties": {} }),
+        ),
+        function(
+            "get_net",
+            "Returns the pins, labels and points of one net of the active file.",
+            json!({
+                "type": "object",
+                "properties": { "name": { "type": "string" } },
+                "required": ["name"]
+            }),
+        ),
+        function(
+            "apply_edits",
+            "Edits the active file with either operations or line edits, as described in the system prompt. \
+             The result is checked before it is written; refused edits report the problems found.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "operations": { "type": "array", "items": operation },
+                    "edits": { "type": "array", "items": line_edit },
+                    "explanation": { "type": "string" },
+                    "changes": { "type": "array", "items": change }
+                },
+                "required": ["explanation"]
+            }),
+        ),
+        function(
+            "run_simulation",
+            "Simulates the active file as saved and returns errors, .meas results and waveform statistics.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "analysis": {
+                        "type": "string",
+                        "description": "Analysis directive to run instead of the schematic's own, e.g. .tran 10m."
+                    }
+                }
+            }),
+        ),
+    ]
+}
+
+/// Runs a tool that only reads the circuit. Returns None for tools that
+/// change files or run simulations, which the chat loop handles itself.
+pub fn query(
+    state: &AppState,
+    dir: &str,
+    active_file: Option<&str>,
+    name: &str,
+    args: &Value,
+) -> Option<Result<String, String>> {
+    let active = || active_file.ok_or_else(|| "No file is open".to_string());
+    Some(match name {
+        "read_file" => match args["file"].as_str() {
+            Some(file) => file_context(state, dir, file),
+            None => active().and_then(|file| file_context(state, dir, file)),
+        },
+        "list_components" => active().and_then(|file| {
+            let content = state
+                .file_formats
+                .read(&workspace::resolve(dir, file, &SCHEMATIC)?)?;
+            let library = symbol_library(state, dir, file)?;
+            Ok(pin_positions_context(&content, &library)
+                .unwrap_or_else(|| "The file has no components.".to_string()))
+        }),
+        "get_net" => active().and_then(|file| {
+            let net_name = args["name"].as_str().ok_or("get_net needs a name")?;
+            let schematic = load_schematic(state, dir, file)?;
+            let library = symbol_library(state, dir, file)?;
+            let graph = connectivity::extract(&schematic, &library);
+            let net = graph
+                .net(net_name)
+                .ok_or_else(|| format!("No net named {}", net_name))?;
+            serde_json::to_string(net).map_err(|e| e.to_string())
+        }),
+        _ => return None,
+    })
+}
@@ -0,0 +1,405 @@
+use crate::asc::{Point, Rotation, Schematic};
+use crate::symbols::{SymbolDef, SymbolLibrary};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PinRef {
+    pub component: String,
+    pub pin: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlacedPin {
+    pub name: String,
+    pub at: Point,
+    pub spice_order: u32,
+    pub net: String,
+    /// False when nothing else (wire, flag or other pin) touches the pin.
+    pub connected: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Component {
+    /// Position of the symbol among the schematic's symbols, which tells
+    /// apart two symbols given the same InstName.
+    pub index: usize,
+    pub inst_name: String,
+    pub symbol: String,
+    pub at: Point,
+    pub rotation: Rotation,
+    pub pins: Vec<PlacedPin>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Net {
+    pub name: String,
+    pub labels: Vec<String>,
+    pub pins: Vec<PinRef>,
+    pub points: Vec<Point>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NetGraph {
+    pub components: Vec<Component>,
+    pub nets: Vec<Net>,
+    /// Symbols whose pin definitions could not be found.
+    pub unresolved: Vec<String>,
+    /// Why symbol files that were found could not be loaded.
+    pub symbol_errors: Vec<String>,
+}
+
+impl NetGraph {
+    pub fn component(&self, inst_name: &str) -> Option<&Component> {
+        self.components.iter().find(|c| c.inst_name == inst_name)
+    }
+
+    /// The component placed from the schematic's `index`th symbol.
+    pub fn component_at(&self, index: usize) -> Option<&Component> {
+        self.components.iter().find(|c| c.index == index)
+    }
+
+    pub fn net(&self, name: &str) -> Option<&Net> {
+        self.nets.iter().find(|n| n.name == name)
+    }
+}
+
+struct DisjointSet {
+    parent: Vec<usize>,
+}
+
+impl DisjointSet {
+    fn find(&mut self, i: usize) -> usize {
+        let mut root = i;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        let mut i = i;
+        while self.parent[i] != root {
+            let next = self.parent[i];
+            self.parent[i] = root;
+            i = next;
+        }
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parent[b] = a;
+        }
+    }
+}
+
+#[derive(Default)]
+struct Points {
+    index: HashMap<Point, usize>,
+    points: Vec<Point>,
+    // Number of wires, flags and pins touching each point.
+    attachments: Vec<usize>,
+}
+
+impl Points {
+    fn add(&mut self, p: Point) -> usize {
+        *self.index.entry(p).or_insert_with(|| {
+            self.points.push(p);
+            self.attachments.push(0);
+            self.points.len() - 1
+        })
+    }
+}
+
+/// Ground wins over any other label; otherwise the alphabetically first.
+fn label_name(labels: &[String]) -> String {
+    if labels.iter().any(|l| l == "0") {
+        return "0".to_string();
+    }
+    labels.iter().min().cloned().unwrap_or_default()
+}
+
+/// Places every symbol's pins and groups wires, flags and pins into nets.
+///
+/// Wires join at shared endpoints and wherever an endpoint, pin or flag lies
+/// on another wire (T-junctions); wires that merely cross do not connect.
+/// Flags with the same label join their nets, and the label names the net
+/// (`0` is ground). Remaining nets are numbered `N001`, `N002`, ... in
+/// component order, and pins touching nothing get `NC_01`, `NC_02`, ...
+pub fn extract(schematic: &Schematic, library: &SymbolLibrary) -> NetGraph {
+    let mut points = Points::default();
+    let mut graph = NetGraph::default();
+
+    let mut defs: HashMap<&str, Option<SymbolDef>> = HashMap::new();
+    let mut placed: Vec<(Component, Vec<usize>)> = Vec::new();
+    for (index, symbol) in schematic.symbols().enumerate() {
+        let def = defs.entry(symbol.name.as_str()).or_insert_with(|| {
+            match library.lookup(&symbol.name) {
+                Ok(def) => def,
+                Err(e) => {
+                    graph.symbol_errors.push(e);
+                    None
+                }
+            }
+        });
+        let inst_name = symbol.inst_name().unwrap_or(&symbol.name).to_string();
+        let Some(def) = def else {
+            if !graph.unresolved.contains(&symbol.name) {
+                graph.unresolved.push(symbol.name.clone());
+            }
+            continue;
+        };
+
+        let mut pins = def.pins.clone();
+        pins.sort_by_key(|p| p.spice_order);
+        let mut indices = Vec::new();
+        let placed_pins = pins
+            .into_iter()
+            .map(|pin| {
+                let offset = symbol.rotation.apply(pin.offset);
+                let at = Point::new(symbol.at.x + offset.x, symbol.at.y + offset.y);
+                let i = points.add(at);
+                points.attachments[i] += 1;
+                indices.push(i);
+                PlacedPin {
+                    name: pin.name,
+                    at,
+                    spice_order: pin.spice_order,
+                    net: String::new(),
+                    connected: false,
+                }
+            })
+            .collect();
+
+        placed.push((
+            Component {
+                index,
+                inst_name,
+                symbol: symbol.name.clone(),
+                at: symbol.at,
+                rotation: symbol.rotation,
+                pins: placed_pins,
+            },
+            indices,
+        ));
+    }
+
+    let wires: Vec<_> = schematic.wires().collect();
+    for wire in &wires {
+        points.add(wire.start);
+        points.add(wire.end);
+    }
+    let flags: Vec<_> = schematic.flags().collect();
+    for flag in &flags {
+        let i = points.add(flag.at);
+        points.attachments[i] += 1;
+    }
+
+    let mut sets = DisjointSet {
+        parent: (0..points.points.len()).collect(),
+    };
+    for wire in &wires {
+        let start = points.index[&wire.start];
+        for (i, &p) in points.points.iter().enumerate() {
+            if wire.contains(p) {
+                sets.union(start, i);
+                points.attachments[i] += 1;
+            }
+        }
+    }
+
+    let mut by_label: HashMap<&str, usize> = HashMap::new();
+    let mut labels: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+    for flag in &flags {
+        let i = points.index[&flag.at];
+        if let Some(&other) = by_label.get(flag.label.as_str()) {
+            sets.union(other, i);
+        } else {
+            by_label.insert(&flag.label, i);
+        }
+    }
+    for flag in &flags {
+        let root = sets.find(points.index[&flag.at]);
+        let entry = labels.entry(root).or_default();
+        if !entry.contains(&flag.label) {
+            entry.push(flag.label.clone());
+        }
+    }
+
+    // Name nets in component order so numbering is stable across calls.
+    let mut names: HashMap<usize, String> = HashMap::new();
+    let mut numbered = 0;
+    let mut unconnected = 0;
+    for (component, indices) in &mut placed {
+        for (pin, &i) in component.pins.iter_mut().zip(indices.iter()) {
+            let root = sets.find(i);
+            pin.connected = points.attachments[i] > 1;
+            let name = names.entry(root).or_insert_with(|| {
+                if let Some(labels) = labels.get(&root) {
+                    return label_name(labels);
+                }
+                if points.attachments[i] <= 1 {
+                    unconnected += 1;
+                    return format!("NC_{:02}", unconnected);
+                }
+                numbered += 1;
+                format!("N{:03}", numbered)
+            });
+            pin.net = name.clone();
+        }
+    }
+    // Labelled nets without any pins still show up so flags can be listed.
+    for (&root, labels) in &labels {
+        names.entry(root).or_insert_with(|| label_name(labels));
+    }
+
+    let mut nets: BTreeMap<usize, Net> = BTreeMap::new();
+    for (&root, name) in &names {
+        nets.insert(
+            root,
+            Net {
+                name: name.clone(),
+                labels: labels.get(&root).cloned().unwrap_or_default(),
+                pins: Vec::new(),
+                points: Vec::new(),
+            },
+        );
+    }
+    for (i, &p) in points.points.iter().enumerate() {
+        if let Some(net) = nets.get_mut(&sets.find(i)) {
+            net.points.push(p);
+        }
+    }
+    for (component, indices) in &placed {
+        for (pin, &i) in component.pins.iter().zip(indices.iter()) {
+            if let Some(net) = nets.get_mut(&sets.find(i)) {
+                net.pins.push(PinRef {
+                    component: component.inst_name.clone(),
+                    pin: pin.name.clone(),
+                });
+            }
+        }
+    }
+
+    graph.components = placed.into_iter().map(|(c, _)| c).collect();
+    graph.nets = nets.into_values().collect();
+    graph.nets.sort_by(|a, b| a.name.cmp(&b.name));
+    graph
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn library() -> SymbolLibrary {
+        SymbolLibrary::new(vec![
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("resources/sym")
+        ])
+    }
+
+    fn graph(body: &str) -> NetGraph {
+        let text = format!("Version 4\nSHEET 1 880 680\n{}", body);
+        extract(&Schematic::parse(&text).unwrap(), &library())
+    }
+
+    fn pin<'a>(graph: &'a NetGraph, component: &str, name: &str) -> &'a PlacedPin {
+        let component = graph.component(component).unwrap();
+        component.pins.iter().find(|p| p.name == name).unwrap()
+    }
+
+    #[test]
+    fn places_pins_for_every_orientation() {
+        // The diode's anode sits at (16,0) and its cathode at (16,64).
+        let cases = [
+            ("R0", (16, 0), (16, 64)),
+            ("R90", (0, 16), (-64, 16)),
+            ("R180", (-16, 0), (-16, -64)),
+            ("R270", (0, -16), (64, -16)),
+            ("M0", (-16, 0), (-16, 64)),
+            ("M90", (0, -16), (-64, -16)),
+            ("M180", (16, 0), (16, -64)),
+            ("M270", (0, 16), (64, 16)),
+        ];
+        for (rotation, a, k) in cases {
+            let graph = graph(&format!(
+                "SYMBOL diode 160 320 {}\nSYMATTR InstName D1\n",
+                rotation
+            ));
+            let at = |(x, y)| Point::new(160 + x, 320 + y);
+            assert_eq!(pin(&graph, "D1", "A").at, at(a), "{}", rotation);
+            assert_eq!(pin(&graph, "D1", "K").at, at(k), "{}", rotation);
+            assert_eq!(graph.component("D1").unwrap().rotation.as_str(), rotation);
+        }
+    }
+
+    #[test]
+    fn wire_ending_on_another_wire_joins_it() {
+        let graph = graph("WIRE 0 0 160 0\nWIRE 80 0 80 80\nFLAG 0 0 a\nFLAG 80 80 b\n");
+        assert_eq!(graph.nets.len(), 1);
+        assert_eq!(graph.nets[0].name, "a");
+        assert_eq!(graph.nets[0].labels, ["a", "b"]);
+    }
+
+    #[test]
+    fn crossing_wires_stay_apart() {
+        let graph = graph("WIRE 0 0 160 0\nWIRE 80 -80 80 80\nFLAG 0 0 a\nFLAG 80 80 b\n");
+        let names: Vec<_> = graph.nets.iter().map(|n| n.name.as_str()).collect();
+        assert_eq!(names, ["a", "b"]);
+    }
+
+    #[test]
+    fn pin_lying_on_a_wire_connects() {
+        let graph =
+            graph("WIRE 0 0 160 0\nFLAG 0 0 a\nSYMBOL res 64 -16 R0\nSYMATTR InstName R1\n");
+        let a = pin(&graph, "R1", "A");
+        assert_eq!(
+            (a.at, a.net.as_str(), a.connected),
+            (Point::new(80, 0), "a", true)
+        );
+        let b = pin(&graph, "R1", "B");
+        assert_eq!((b.net.as_str(), b.connected), ("NC_01", false));
+        assert_eq!(
+            graph.net("a").unwrap().pins,
+            [PinRef {
+                component: "R1".to_string(),
+                pin: "A".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn flags_with_the_same_label_merge_nets() {
+        let graph = graph(
+            "FLAG 16 16 out\nFLAG 400 16 out\n\
+             SYMBOL res 0 0 R0\nSYMATTR InstName R1\n\
+             SYMBOL res 384 0 R0\nSYMATTR InstName R2\n",
+        );
+        assert_eq!(pin(&graph, "R1", "A").net, "out");
+        assert_eq!(pin(&graph, "R2", "A").net, "out");
+        assert_eq!(graph.net("out").unwrap().pins.len(), 2);
+        assert_eq!(pin(&graph, "R1", "B").net, "NC_01");
+        assert_eq!(pin(&graph, "R2", "B").net, "NC_02");
+    }
+
+    #[test]
+    fn ground_names_the_net_over_other_labels() {
+        let graph = graph(
+            "WIRE 16 96 16 160\nFLAG 16 160 0\nFLAG 16 96 agnd\n\
+             SYMBOL res 0 0 R0\nSYMATTR InstName R1\n\
+             SYMBOL res 0 144 R0\nSYMATTR InstName R2\n",
+        );
+        assert_eq!(pin(&graph, "R1", "B").net, "0");
+        assert_eq!(pin(&graph, "R2", "A").net, "0");
+        let ground = graph.net("0").unwrap();
+        assert_eq!(ground.labels, ["0", "agnd"]);
+        assert!(graph.net("agnd").is_none());
+        // Unlabelled nets are numbered in component order.
+        assert_eq!(pin(&graph, "R1", "A").net, "NC_01");
+    }
+
+    #[test]
+    fn reports_symbols_it_cannot_find() {
+        let graph = graph("SYMBOL nosuchpart 0 0 R0\nSYMATTR InstName X1\n");
+        assert_eq!(graph.unresolved, ["nosuchpart"]);
+        assert!(graph.components.is_empty());
+    }
+}
@@ -0,0 +1,163 @@
+use crate::logfile::SimulationReport;
+use serde::Serialize;
+
+/// Relative tolerance for `=` goals.
+const EQUAL_TOLERANCE: f64 = 0.01;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessEq,
+    Greater,
+    GreaterEq,
+    Equal,
+}
+
+impl Comparison {
+    fn holds(self, actual: f64, target: f64) -> bool {
+        match self {
+            Comparison::Less => actual < target,
+            Comparison::LessEq => actual <= target,
+            Comparison::Greater => actual > target,
+            Comparison::GreaterEq => actual >= target,
+            Comparison::Equal => {
+                (actual - target).abs() <= EQUAL_TOLERANCE * target.abs().max(f64::MIN_POSITIVE)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Goal {
+    /// A `.meas` result compared against a target, e.g. `Vout_pp < 50m`.
+    Measurement {
+        name: String,
+        comparison: Comparison,
+        target: f64,
+    },
+    /// Anything else, such as "gain of 20 dB at 1 kHz"; left to the model.
+    Described(String),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GoalResult {
+    pub goal: String,
+    /// None when the goal cannot be checked mechanically.
+    pub met: Option<bool>,
+    pub actual: Option<f64>,
+}
+
+/// Parses a SPICE number with an optional scale suffix and unit: `50m`,
+/// `4.7k`, `2Meg`, `10uF`, `1e-3`.
+pub fn parse_value(text: &str) -> Option<f64> {
+    let text = text.trim();
+    let bytes = text.as_bytes();
+    let end = text
+        .char_indices()
+        .find(|&(i, c)| {
+            // `e` only counts as an exponent when digits follow, so `5e` is
+            // not misread and `1e-3` keeps its sign.
+            let exponent = i > 0
+                && (c == 'e' || c == 'E')
+                && text[i + 1..].starts_with(|n: char| n.is_ascii_digit() || n == '-' || n == '+');
+            let sign = (c == '-' || c == '+') && (i == 0 || matches!(bytes[i - 1], b'e' | b'E'));
+            !(c.is_ascii_digit() || c == '.' || sign || exponent)
+        })
+        .map(|(i, _)| i)
+        .unwrap_or(text.len());
+    let number: f64 = text[..end].parse().ok()?;
+    let suffix = text[end..].to_ascii_lowercase();
+    let scale = if suffix.starts_with("meg") {
+        1e6
+    } else {
+        match suffix.chars().next() {
+            Some('t') => 1e12,
+            Some('g') => 1e9,
+            Some('k') => 1e3,
+            Some('m') => 1e-3,
+            Some('u') | Some('µ') | Some('μ') => 1e-6,
+            Some('n') => 1e-9,
+            Some('p') => 1e-12,
+            Some('f') => 1e-15,
+            _ => 1.0,
+        }
+    };
+    Some(number * scale)
+}
+
+/// Recognizes `[.meas] <name> <op> <value>` with `<`, `<=`, `>`, `>=`, `=`
+/// or `==`; everything else becomes a described goal.
+pub fn parse(text: &str) -> Goal {
+    let described = || Goal::Described(text.trim().to_string());
+    let body = text.trim();
+    let body = body
+        .strip_prefix(".meas ")
+        .or_else(|| body.strip_prefix(".MEAS "))
+        .unwrap_or(body);
+
+    let Some(at) = body.find(['<', '>', '=']) else {
+        return described();
+    };
+    let name = body[..at].trim();
+    let rest = &body[at..];
+    let (comparison, len) = match rest.get(..2) {
+        Some("<=") => (Comparison::LessEq, 2),
+        Some(">=") => (Comparison::GreaterEq, 2),
+        Some("==") => (Comparison::Equal, 2),
+        _ => match rest.as_bytes()[0] {
+            b'<' => (Comparison::Less, 1),
+            b'>' => (Comparison::Greater, 1),
+            _ => (Comparison::Equal, 1),
+        },
+    };
+    let valid_name =
+        !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    match (valid_name, parse_value(&rest[len..])) {
+        (true, Some(target)) => Goal::Measurement {
+            name: name.to_string(),
+            comparison,
+            target,
+        },
+        _ => described(),
+    }
+}
+
+/// Checks each goal against the measurements in `report`. A stepped
+/// measurement must hold in every step; `actual` is the first value that
+/// fails, or the first value when all pass.
+pub fn evaluate(goals: &[String], report: Option<&SimulationReport>) -> Vec<GoalResult> {
+    goals
+        .iter()
+        .map(|text| {
+            let Goal::Measurement {
+                name,
+                comparison,
+                target,
+            } = parse(text)
+            else {
+                return GoalResult {
+                    goal: text.clone(),
+                    met: None,
+                    actual: None,
+                };
+            };
+            let values: Vec<Option<f64>> = report
+                .map(|r| {
+                    r.measurements
+                        .iter()
+                        .filter(|m| m.name.eq_ignore_ascii_case(&name))
+                        .map(|m| m.value)
+                        .collect()
+                })
+                .unwrap_or_default();
+            let failing = values
+                .iter()
+                .find(|v| !v.is_some_and(|v| comparison.holds(v, target)));
+            GoalResult {
+                goal: text.clone(),
+                met: Some(!values.is_empty() && failing.is_none()),
+                actual: failing.or(values.first()).copied().flatten(),
+            }
+        })
+        .collect()
+}
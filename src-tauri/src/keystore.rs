@@ -0,0 +1,305 @@
+use crate::llm::ProviderKind;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+const SERVICE: &str = "spicy";
+const INDEX_FILE: &str = "keys.json";
+const SECRETS_FILE: &str = "secrets.json";
+
+pub const DEFAULT_NAME: &str = "default";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyInfo {
+    pub provider: ProviderKind,
+    pub name: String,
+    pub active: bool,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct ProviderKeys {
+    active: Option<String>,
+    names: Vec<String>,
+}
+
+/// Keys encrypted with ChaCha20-Poly1305 under a key derived from the
+/// machine id and a random salt, so a copied file is useless elsewhere.
+#[derive(Default, Serialize, Deserialize)]
+struct SecretsFile {
+    salt: String,
+    /// Hex nonce and ciphertext per account.
+    entries: BTreeMap<String, (String, String)>,
+}
+
+fn provider_id(provider: ProviderKind) -> String {
+    serde_json::to_value(provider)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+fn account(provider: ProviderKind, name: &str) -> String {
+    format!("{}:{}", provider_id(provider), name)
+}
+
+fn machine_id() -> Result<String, String> {
+    ["/etc/machine-id", "/var/lib/dbus/machine-id"]
+        .iter()
+        .find_map(|path| std::fs::read_to_string(path).ok())
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty())
+        .ok_or_else(|| "No secret store or machine id available to protect the key".to_string())
+}
+
+fn cipher(salt: &[u8]) -> Result<ChaCha20Poly1305, String> {
+    let mut hasher = Sha256::new();
+    hasher.update(SERVICE.as_bytes());
+    hasher.update(machine_id()?.as_bytes());
+    hasher.update(std::env::var("USER").unwrap_or_default().as_bytes());
+    hasher.update(salt);
+    Ok(ChaCha20Poly1305::new(Key::from_slice(&hasher.finalize())))
+}
+
+/// Reads a key file, empty when it does not exist yet. A file that cannot be
+/// read or parsed is an error so that it is never overwritten with a fresh one.
+fn read_json<T: Default + for<'de> Deserialize<'de>>(path: &Path) -> Result<T, String> {
+    let json = match std::fs::read_to_string(path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(T::default()),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    serde_json::from_str(&json)
+        .map_err(|e| format!("{} is corrupt, fix or remove it: {}", path.display(), e))
+}
+
+fn write_private(path: &Path, contents: &str) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create config directory: {}", e))?;
+    }
+    std::fs::write(path, contents)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600));
+    }
+    Ok(())
+}
+
+/// API keys in the platform secret store (Secret Service, Keychain,
+/// Credential Manager), or in an encrypted file where none is reachable, as
+/// on headless Linux. Key names are not secret; they are listed in
+/// `keys.json` because secret stores cannot enumerate their entries.
+pub struct KeyStore {
+    dir: PathBuf,
+}
+
+impl KeyStore {
+    pub fn new(config_dir: &Path) -> Self {
+        Self {
+            dir: config_dir.to_path_buf(),
+        }
+    }
+
+    fn index(&self) -> Result<BTreeMap<String, ProviderKeys>, String> {
+        read_json(&self.dir.join(INDEX_FILE))
+    }
+
+    fn save_index(&self, index: &BTreeMap<String, ProviderKeys>) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(index).map_err(|e| e.to_string())?;
+        write_private(&self.dir.join(INDEX_FILE), &json)
+    }
+
+    pub fn list(&self) -> Result<Vec<KeyInfo>, String> {
+        let mut index = self.index()?;
+        let mut keys = Vec::new();
+        for provider in [
+            ProviderKind::OpenRouter,
+            ProviderKind::OpenAi,
+            ProviderKind::Anthropic,
+        ] {
+            if let Some(entry) = index.remove(&provider_id(provider)) {
+                for name in entry.names {
+                    keys.push(KeyInfo {
+                        provider,
+                        active: entry.active.as_deref() == Some(name.as_str()),
+                        name,
+                    });
+                }
+            }
+        }
+        Ok(keys)
+    }
+
+    /// Name of the key used for `provider`.
+    pub fn active(&self, provider: ProviderKind) -> Result<Option<String>, String> {
+        Ok(self
+            .index()?
+            .remove(&provider_id(provider))
+            .and_then(|entry| entry.active))
+    }
+
+    /// The active key for `provider`, if one is stored.
+    pub fn active_key(&self, provider: ProviderKind) -> Result<Option<String>, String> {
+        match self.active(provider)? {
+            Some(name) => self.get(provider, &name),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get(&self, provider: ProviderKind, name: &str) -> Result<Option<String>, String> {
+        let account = account(provider, name);
+        if let Ok(entry) = keyring::Entry::new(SERVICE, &account) {
+            match entry.get_password() {
+                Ok(key) => return Ok(Some(key)),
+                Err(keyring::Error::NoEntry) => {}
+                Err(keyring::Error::NoStorageAccess(_) | keyring::Error::PlatformFailure(_)) => {}
+                Err(e) => return Err(format!("Failed to read API key: {}", e)),
+            }
+        }
+        self.read_fallback(&account)
+    }
+
+    /// Stores a key and makes it the active one for its provider.
+    pub fn set(&self, provider: ProviderKind, name: &str, key: &str) -> Result<(), String> {
+        let account = account(provider, name);
+        let stored = keyring::Entry::new(SERVICE, &account)
+            .and_then(|entry| entry.set_password(key))
+            .is_ok();
+        if stored {
+            self.remove_fallback(&account)?;
+        } else {
+            self.write_fallback(&account, key)?;
+        }
+
+        let mut index = self.index()?;
+        let entry = index.entry(provider_id(provider)).or_default();
+        if !entry.names.iter().any(|n| n == name) {
+            entry.names.push(name.to_string());
+        }
+        entry.active = Some(name.to_string());
+        self.save_index(&index)
+    }
+
+    pub fn select(&self, provider: ProviderKind, name: &str) -> Result<(), String> {
+        let mut index = self.index()?;
+        let entry = index.entry(provider_id(provider)).or_default();
+        if !entry.names.iter().any(|n| n == name) {
+            return Err(format!("No API key named {}", name));
+        }
+        entry.active = Some(name.to_string());
+        self.save_index(&index)
+    }
+
+    /// Deletes a key from both the secret store and the fallback file. When
+    /// it was active, the provider falls back to its first remaining key.
+    pub fn delete(&self, provider: ProviderKind, name: &str) -> Result<(), String> {
+        let account = account(provider, name);
+        if let Ok(entry) = keyring::Entry::new(SERVICE, &account) {
+            match entry.delete_credential() {
+                Ok(())
+                | Err(keyring::Error::NoEntry)
+                | Err(keyring::Error::NoStorageAccess(_))
+                | Err(keyring::Error::PlatformFailure(_)) => {}
+                Err(e) => return Err(format!("Failed to delete API key: {}", e)),
+            }
+        }
+        self.remove_fallback(&account)?;
+
+        let mut index = self.index()?;
+        if let Some(entry) = index.get_mut(&provider_id(provider)) {
+            entry.names.retain(|n| n != name);
+            if entry.active.as_deref() == Some(name) {
+                entry.active = entry.names.first().cloned();
+            }
+        }
+        self.save_index(&index)
+    }
+
+    fn read_fallback(&self, account: &str) -> Result<Option<String>, String> {
+        let secrets: SecretsFile = read_json(&self.dir.join(SECRETS_FILE))?;
+        let Some((nonce, data)) = secrets.entries.get(account) else {
+            return Ok(None);
+        };
+        let salt = hex::decode(&secrets.salt).map_err(|e| e.to_string())?;
+        let nonce = hex::decode(nonce).map_err(|e| e.to_string())?;
+        let data = hex::decode(data).map_err(|e| e.to_string())?;
+        let key = cipher(&salt)?
+            .decrypt(Nonce::from_slice(&nonce), data.as_slice())
+            .map_err(|_| "Failed to decrypt the stored API key".to_string())?;
+        String::from_utf8(key).map(Some).map_err(|e| e.to_string())
+    }
+
+    fn write_fallback(&self, account: &str, key: &str) -> Result<(), String> {
+        let path = self.dir.join(SECRETS_FILE);
+        let mut secrets: SecretsFile = read_json(&path)?;
+        if secrets.salt.is_empty() {
+            let mut salt = [0u8; 16];
+            OsRng.fill_bytes(&mut salt);
+            secrets.salt = hex::encode(salt);
+        }
+        let salt = hex::decode(&secrets.salt).map_err(|e| e.to_string())?;
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let data = cipher(&salt)?
+            .encrypt(&nonce, key.as_bytes())
+            .map_err(|_| "Failed to encrypt the API key".to_string())?;
+        secrets
+            .entries
+            .insert(account.to_string(), (hex::encode(nonce), hex::encode(data)));
+        let json = serde_json::to_string_pretty(&secrets).map_err(|e| e.to_string())?;
+        write_private(&path, &json)
+    }
+
+    fn remove_fallback(&self, account: &str) -> Result<(), String> {
+        let path = self.dir.join(SECRETS_FILE);
+        let mut secrets: SecretsFile = read_json(&path)?;
+        if secrets.entries.remove(account).is_none() {
+            return Ok(());
+        }
+        let json = serde_json::to_string_pretty(&secrets).map_err(|e| e.to_string())?;
+        write_private(&path, &json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corrupt_files_are_not_overwritten() {
+        let dir = std::env::temp_dir().join(format!("spicy-keystore-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(INDEX_FILE), "{ not json").unwrap();
+        std::fs::write(dir.join(SECRETS_FILE), "{ not json").unwrap();
+
+        let store = KeyStore::new(&dir);
+        assert!(store.list().is_err());
+        assert!(store
+            .select(ProviderKind::OpenRouter, DEFAULT_NAME)
+            .is_err());
+        assert!(store.write_fallback("openrouter:default", "key").is_err());
+        assert!(store.remove_fallback("openrouter:default").is_err());
+        assert_eq!(
+            std::fs::read_to_string(dir.join(INDEX_FILE)).unwrap(),
+            "{ not json"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.join(SECRETS_FILE)).unwrap(),
+            "{ not json"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_files_start_empty() {
+        let dir = std::env::temp_dir().join(format!("spicy-keystore-empty-{}", std::process::id()));
+        let store = KeyStore::new(&dir);
+        assert!(store.list().unwrap().is_empty());
+        assert_eq!(store.active(ProviderKind::Anthropic).unwrap(), None);
+    }
+}
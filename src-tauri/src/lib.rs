@@ -1,5 +1,22 @@
+pub mod asc;
 mod commands;
+pub mod connectivity;
+pub mod goals;
+pub mod keystore;
+pub mod llm;
+pub mod logfile;
+pub mod ltspice;
+pub mod netlist;
+pub mod operations;
+pub mod raw;
+pub mod settings;
+pub mod simulator;
 mod state;
+pub mod symbols;
+pub mod textfile;
+pub mod validation;
+pub mod watcher;
+pub mod workspace;
 
 use state::AppState;
 use tauri::Manager;
@@ -13,6 +30,18 @@ pub fn run() {
         .plugin(tauri_plugin_fs::init())
         .manage(AppState::new())
         .setup(|app| {
+            if let Ok(resources) = app.path().resource_dir() {
+                let _ = app
+                    .state::<AppState>()
+                    .bundled_symbols
+                    .set(resources.join("sym"));
+            }
+            if let Ok(config_dir) = app.path().app_config_dir() {
+                app.state::<AppState>().load_settings(config_dir);
+                commands::files::watch_working_directory(app.handle());
+                let _ = app.state::<AppState>().refresh_api_key();
+            }
+
             let window = app.get_webview_window("main").unwrap();
 
             #[cfg(target_os = "macos")]
@@ -23,10 +52,8 @@ pub fn run() {
                 if let Ok(handle) = window.window_handle() {
                     if let RawWindowHandle::AppKit(h) = handle.as_raw() {
                         unsafe {
-                            let ns_view =
-                                h.ns_view.as_ptr() as *const objc2::runtime::AnyObject;
-                            let ns_window: *const NSWindow =
-                                objc2::msg_send![ns_view, window];
+                            let ns_view = h.ns_view.as_ptr() as *const objc2::runtime::AnyObject;
+                            let ns_window: *const NSWindow = objc2::msg_send![ns_view, window];
                             let ns_window = &*ns_window;
                             ns_window.setOpaque(false);
                             ns_window.setBackgroundColor(Some(&NSColor::clearColor()));
@@ -43,8 +70,7 @@ pub fn run() {
                             let wv = webview.inner() as *mut objc2::runtime::AnyObject;
                             let key = NSString::from_str("drawsBackground");
                             let no = NSNumber::new_bool(false);
-                            let _: () =
-                                objc2::msg_send![wv, setValue: &*no, forKey: &*key];
+                            let _: () = objc2::msg_send![wv, setValue: &*no, forKey: &*key];
                         }
                     })
                     .ok();
@@ -56,9 +82,31 @@ pub fn run() {
             commands::files::set_working_directory,
             commands::files::set_api_key,
             commands::files::has_api_key,
+            commands::files::clear_api_key,
+            commands::files::list_api_keys,
+            commands::files::select_api_key,
+            commands::files::set_provider,
+            commands::files::get_provider,
+            commands::files::set_client_config,
+            commands::files::get_client_config,
+            commands::files::get_settings,
+            commands::files::update_settings,
+            commands::files::set_symbol_library_path,
             commands::files::list_asc_files,
             commands::files::read_asc_file,
             commands::chat::send_chat_message_stream,
+            commands::schematic::get_netlist_graph,
+            commands::schematic::export_netlist,
+            commands::simulation::simulate,
+            commands::simulation::load_waveforms,
+            commands::simulation::get_simulation_report,
+            commands::proposals::accept_proposal,
+            commands::proposals::reject_proposal,
+            commands::ltspice::locate_ltspice,
+            commands::ltspice::open_in_ltspice,
+            commands::edit_history::undo_last_edit,
+            commands::edit_history::redo_edit,
+            commands::edit_history::list_edit_history,
             commands::history::list_chat_sessions,
             commands::history::load_chat_session,
             commands::history::save_chat_session,
@@ -0,0 +1,335 @@
+use super::{
+    sse_data, CompletionRequest, Delta, FunctionCall, Message, Provider, Reply, ToolCall, Usage,
+};
+use serde_json::{json, Value};
+
+const ANTHROPIC_URL: &str = "https://api.anthropic.com";
+const API_VERSION: &str = "2023-06-01";
+
+/// Smallest thinking budget the Messages API accepts.
+const MIN_THINKING_BUDGET: u32 = 1024;
+
+/// The native Anthropic Messages API, with extended thinking.
+pub struct Anthropic {
+    base_url: String,
+    api_key: String,
+}
+
+impl Anthropic {
+    pub fn new(base_url: Option<String>, api_key: String) -> Self {
+        Self {
+            base_url: base_url.unwrap_or_else(|| ANTHROPIC_URL.to_string()),
+            api_key,
+        }
+    }
+}
+
+fn text_block(text: &str) -> Value {
+    json!({ "type": "text", "text": text })
+}
+
+/// Converts OpenAI-style messages: system messages move to the `system`
+/// field, tool calls and results become content blocks, and consecutive
+/// messages of the same role merge, since roles have to alternate.
+fn convert_messages(messages: &[Message]) -> (String, Vec<Value>) {
+    let mut system = Vec::new();
+    let mut out: Vec<(String, Vec<Value>)> = Vec::new();
+    for message in messages {
+        let (role, blocks) = match message.role.as_str() {
+            "system" => {
+                system.push(message.content.as_str());
+                continue;
+            }
+            "tool" => (
+                "user",
+                vec![json!({
+                    "type": "tool_result",
+                    "tool_use_id": message.tool_call_id.clone().unwrap_or_default(),
+                    "content": message.content,
+                })],
+            ),
+            "assistant" => {
+                let mut blocks = message.thinking.clone();
+                if !message.content.is_empty() {
+                    blocks.push(text_block(&message.content));
+                }
+                for call in &message.tool_calls {
+                    blocks.push(json!({
+                        "type": "tool_use",
+                        "id": call.id,
+                        "name": call.function.name,
+                        "input": call.arguments().unwrap_or_else(|_| json!({})),
+                    }));
+                }
+                ("assistant", blocks)
+            }
+            _ => ("user", vec![text_block(&message.content)]),
+        };
+        if blocks.is_empty() {
+            continue;
+        }
+        match out.last_mut() {
+            Some((last, content)) if last == role => content.extend(blocks),
+            _ => out.push((role.to_string(), blocks)),
+        }
+    }
+    let messages = out
+        .into_iter()
+        .map(|(role, content)| json!({ "role": role, "content": content }))
+        .collect();
+    (system.join("\n\n"), messages)
+}
+
+fn convert_tool(tool: &Value) -> Value {
+    let function = &tool["function"];
+    json!({
+        "name": function["name"],
+        "description": function["description"],
+        "input_schema": function["parameters"],
+    })
+}
+
+impl Provider for Anthropic {
+    fn name(&self) -> &str {
+        "anthropic"
+    }
+
+    fn request(
+        &self,
+        client: &reqwest::Client,
+        completion: &CompletionRequest,
+    ) -> reqwest::RequestBuilder {
+        let (system, messages) = convert_messages(&completion.messages);
+        let mut body = json!({
+            "model": completion.model,
+            "max_tokens": completion.max_tokens,
+            "messages": messages,
+            "stream": true,
+        });
+        if !system.is_empty() {
+            body["system"] = json!(system);
+        }
+        if !completion.tools.is_empty() {
+            body["tools"] = completion.tools.iter().map(convert_tool).collect();
+        }
+        // The budget has to leave room for the answer within max_tokens.
+        let budget = completion.max_tokens / 4;
+        if budget >= MIN_THINKING_BUDGET {
+            body["thinking"] = json!({ "type": "enabled", "budget_tokens": budget });
+        }
+
+        let url = format!("{}/v1/messages", self.base_url.trim_end_matches('/'));
+        client
+            .post(url)
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", API_VERSION)
+            .header("content-type", "application/json")
+            .json(&body)
+    }
+
+    fn parse_line(&self, line: &str, reply: &mut Reply) -> Vec<Delta> {
+        let Some(event) = sse_data(line).and_then(|d| serde_json::from_str::<Value>(d).ok()) else {
+            return vec![];
+        };
+
+        match event["type"].as_str().unwrap_or("") {
+            "message_start" => {
+                let usage = &event["message"]["usage"];
+                reply.usage = Some(Usage {
+                    input_tokens: usage["input_tokens"].as_u64().unwrap_or(0),
+                    output_tokens: usage["output_tokens"].as_u64().unwrap_or(0),
+                });
+            }
+            "content_block_start" => {
+                let block = &event["content_block"];
+                match block["type"].as_str().unwrap_or("") {
+                    "tool_use" => reply.tool_calls.push(ToolCall {
+                        id: block["id"].as_str().unwrap_or_default().to_string(),
+                        kind: "function".to_string(),
+                        function: FunctionCall {
+                            name: block["name"].as_str().unwrap_or_default().to_string(),
+                            arguments: String::new(),
+                        },
+                    }),
+                    "thinking" => reply
+                        .thinking
+                        .push(json!({ "type": "thinking", "thinking": "", "signature": "" })),
+                    "redacted_thinking" => reply.thinking.push(block.clone()),
+                    _ => {}
+                }
+            }
+            // Blocks arrive one after another, so each delta belongs to the
+            // most recently started block of its kind.
+            "content_block_delta" => {
+                let delta = &event["delta"];
+                match delta["type"].as_str().unwrap_or("") {
+                    "text_delta" => {
+                        let text = delta["text"].as_str().unwrap_or_default();
+                        return vec![Delta::Text(text.to_string())];
+                    }
+                    "thinking_delta" => {
+                        let text = delta["thinking"].as_str().unwrap_or_default();
+                        if let Some(block) = reply.thinking.last_mut() {
+                            let so_far = block["thinking"].as_str().unwrap_or_default();
+                            block["thinking"] = json!(format!("{}{}", so_far, text));
+                        }
+                        return vec![Delta::Thinking(text.to_string())];
+                    }
+                    "signature_delta" => {
+                        if let Some(block) = reply.thinking.last_mut() {
+                            let so_far = block["signature"].as_str().unwrap_or_default();
+                            let signature = delta["signature"].as_str().unwrap_or_default();
+                            block["signature"] = json!(format!("{}{}", so_far, signature));
+                        }
+                    }
+                    "input_json_delta" => {
+                        if let Some(call) = reply.tool_calls.last_mut() {
+                            let json = delta["partial_json"].as_str().unwrap_or_default();
+                            call.function.arguments.push_str(json);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            "message_delta" => {
+                if let Some(output) = event["usage"]["output_tokens"].as_u64() {
+                    reply.usage.get_or_insert_with(Usage::default).output_tokens = output;
+                }
+            }
+            "message_stop" => return vec![Delta::Done],
+            "error" => {
+                let message = event["error"]["message"]
+                    .as_str()
+                    .unwrap_or("Unknown API error");
+                return vec![Delta::Error(message.to_string())];
+            }
+            _ => {}
+        }
+        vec![]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::replay;
+
+    #[test]
+    fn streams_thinking_text_and_a_tool_call() {
+        let (deltas, reply) = replay(
+            &Anthropic::new(None, String::new()),
+            include_str!("../../tests/fixtures/sse/anthropic_thinking_tool.sse"),
+        );
+        assert_eq!(
+            deltas,
+            [
+                Delta::Thinking("R1 sets the ".to_string()),
+                Delta::Thinking("corner.".to_string()),
+                Delta::Text("Raising R1 ".to_string()),
+                Delta::Text("lowers fc.".to_string()),
+                Delta::Done,
+            ]
+        );
+        assert_eq!(
+            reply.thinking,
+            [
+                json!({
+                    "type": "thinking",
+                    "thinking": "R1 sets the corner.",
+                    "signature": "EqQBCgIYAhIM1gbcDa",
+                }),
+                json!({ "type": "redacted_thinking", "data": "EmwKAhgB" }),
+            ]
+        );
+
+        assert_eq!(reply.tool_calls.len(), 1);
+        let call = &reply.tool_calls[0];
+        assert_eq!(
+            (
+                call.id.as_str(),
+                call.kind.as_str(),
+                call.function.name.as_str()
+            ),
+            ("toolu_01", "function", "apply_edits")
+        );
+        assert_eq!(
+            call.arguments().unwrap(),
+            json!({ "operations": [{ "op": "set_value", "component": "R1", "value": "2.2k" }] })
+        );
+        let usage = reply.usage.unwrap();
+        assert_eq!((usage.input_tokens, usage.output_tokens), (1200, 87));
+    }
+
+    #[test]
+    fn reports_stream_errors() {
+        let (deltas, reply) = replay(
+            &Anthropic::new(None, String::new()),
+            include_str!("../../tests/fixtures/sse/anthropic_overloaded.sse"),
+        );
+        assert_eq!(deltas, [Delta::Error("Overloaded".to_string())]);
+        assert_eq!(reply.usage.unwrap().input_tokens, 90);
+    }
+
+    fn tool_call(id: &str, name: &str, arguments: &str) -> ToolCall {
+        ToolCall {
+            id: id.to_string(),
+            kind: "function".to_string(),
+            function: FunctionCall {
+                name: name.to_string(),
+                arguments: arguments.to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn converts_and_merges_messages() {
+        let thinking = json!({ "type": "thinking", "thinking": "Look first.", "signature": "sig" });
+        let messages = [
+            Message::new("system", "You edit schematics."),
+            Message::new("user", "Lower the corner."),
+            Message::new("system", "The file is rc.asc."),
+            Message::new("user", "By half."),
+            Message {
+                thinking: vec![thinking.clone()],
+                tool_calls: vec![
+                    tool_call("toolu_1", "read_file", r#"{"file": "rc.asc"}"#),
+                    tool_call("toolu_2", "list_nets", ""),
+                ],
+                ..Message::new("assistant", "Reading the file.")
+            },
+            Message {
+                tool_call_id: Some("toolu_1".to_string()),
+                ..Message::new("tool", "Version 4")
+            },
+            Message {
+                tool_call_id: Some("toolu_2".to_string()),
+                ..Message::new("tool", "N001, out, 0")
+            },
+            Message::new("assistant", ""),
+            Message::new("user", "Go ahead."),
+        ];
+
+        let (system, converted) = convert_messages(&messages);
+        assert_eq!(system, "You edit schematics.\n\nThe file is rc.asc.");
+        assert_eq!(
+            converted,
+            [
+                json!({ "role": "user", "content": [
+                    { "type": "text", "text": "Lower the corner." },
+                    { "type": "text", "text": "By half." },
+                ]}),
+                json!({ "role": "assistant", "content": [
+                    thinking,
+                    { "type": "text", "text": "Reading the file." },
+                    { "type": "tool_use", "id": "toolu_1", "name": "read_file", "input": { "file": "rc.asc" } },
+                    { "type": "tool_use", "id": "toolu_2", "name": "list_nets", "input": {} },
+                ]}),
+                json!({ "role": "user", "content": [
+                    { "type": "tool_result", "tool_use_id": "toolu_1", "content": "Version 4" },
+                    { "type": "tool_result", "tool_use_id": "toolu_2", "content": "N001, out, 0" },
+                    { "type": "text", "text": "Go ahead." },
+                ]}),
+            ]
+        );
+    }
+}
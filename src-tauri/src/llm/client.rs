@@ -0,0 +1,74 @@
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// How the HTTP client reaches the provider.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClientConfig {
+    /// Proxy for plain HTTP requests, and for HTTPS when `https_proxy` is unset.
+    pub http_proxy: Option<String>,
+    pub https_proxy: Option<String>,
+    /// PEM files trusted in addition to the system roots.
+    pub root_certificates: Vec<String>,
+    /// Sent with every request, e.g. `HTTP-Referer` and `X-Title`.
+    pub headers: BTreeMap<String, String>,
+    pub connect_timeout_secs: Option<u64>,
+    /// Longest wait for the next chunk of a streamed reply.
+    pub read_timeout_secs: Option<u64>,
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
+}
+
+impl ClientConfig {
+    pub fn build(&self) -> Result<reqwest::Client, String> {
+        let mut builder = reqwest::Client::builder();
+
+        let http_proxy = non_empty(&self.http_proxy);
+        let https_proxy = non_empty(&self.https_proxy).or(http_proxy);
+        if let Some(url) = http_proxy {
+            let proxy = reqwest::Proxy::http(url)
+                .map_err(|e| format!("Invalid HTTP proxy {}: {}", url, e))?;
+            builder = builder.proxy(proxy);
+        }
+        if let Some(url) = https_proxy {
+            let proxy = reqwest::Proxy::https(url)
+                .map_err(|e| format!("Invalid HTTPS proxy {}: {}", url, e))?;
+            builder = builder.proxy(proxy);
+        }
+
+        for path in &self.root_certificates {
+            let pem = std::fs::read(path)
+                .map_err(|e| format!("Failed to read certificate {}: {}", path, e))?;
+            let certificates = reqwest::Certificate::from_pem_bundle(&pem)
+                .map_err(|e| format!("Invalid certificate {}: {}", path, e))?;
+            for certificate in certificates {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            let header_name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| format!("Invalid header name: {}", name))?;
+            let header_value = HeaderValue::from_str(value)
+                .map_err(|_| format!("Invalid value for header {}", name))?;
+            headers.insert(header_name, header_value);
+        }
+        builder = builder.default_headers(headers);
+
+        if let Some(secs) = self.connect_timeout_secs.filter(|s| *s > 0) {
+            builder = builder.connect_timeout(Duration::from_secs(secs));
+        }
+        if let Some(secs) = self.read_timeout_secs.filter(|s| *s > 0) {
+            builder = builder.read_timeout(Duration::from_secs(secs));
+        }
+
+        builder
+            .build()
+            .map_err(|e| format!("Failed to create HTTP client: {}", e))
+    }
+}
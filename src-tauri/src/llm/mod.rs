@@ -0,0 +1,180 @@
+pub mod anthropic;
+pub mod client;
+pub mod openai;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// One message of a conversation in the OpenAI chat format, which the chat
+/// loop keeps; other providers convert it when building requests.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Message {
+    pub role: String,
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+    /// Provider-specific reasoning blocks that must accompany the message
+    /// when it is sent back (Anthropic thinking with its signature).
+    #[serde(skip)]
+    pub thinking: Vec<Value>,
+}
+
+impl Message {
+    pub fn new(role: &str, content: impl Into<String>) -> Self {
+        Self {
+            role: role.to_string(),
+            content: content.into(),
+            ..Self::default()
+        }
+    }
+}
+
+/// A function call requested by the model, in the OpenAI wire format.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct ToolCall {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub function: FunctionCall,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct FunctionCall {
+    pub name: String,
+    /// JSON text, streamed in fragments.
+    pub arguments: String,
+}
+
+impl ToolCall {
+    pub fn arguments(&self) -> Result<Value, String> {
+        if self.function.arguments.trim().is_empty() {
+            return Ok(json!({}));
+        }
+        serde_json::from_str(&self.function.arguments)
+            .map_err(|e| format!("Invalid arguments for {}: {}", self.function.name, e))
+    }
+}
+
+/// Everything needed for one streamed completion, independent of provider.
+pub struct CompletionRequest {
+    pub model: String,
+    pub max_tokens: u32,
+    pub messages: Vec<Message>,
+    /// Function tools in the OpenAI format.
+    pub tools: Vec<Value>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct Usage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+/// What one completion produced.
+#[derive(Default)]
+pub struct Reply {
+    pub text: String,
+    pub tool_calls: Vec<ToolCall>,
+    pub thinking: Vec<Value>,
+    pub usage: Option<Usage>,
+}
+
+/// Something to pass on while a completion streams in.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Delta {
+    Thinking(String),
+    /// Reply text; the caller collects it into `Reply::text`.
+    Text(String),
+    Error(String),
+    Done,
+}
+
+/// A chat completion API that streams its replies.
+pub trait Provider: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn default_model(&self) -> Option<&str> {
+        None
+    }
+
+    /// Builds the HTTP request for one streamed completion.
+    fn request(
+        &self,
+        client: &reqwest::Client,
+        completion: &CompletionRequest,
+    ) -> reqwest::RequestBuilder;
+
+    /// Parses one line of the response stream. Tool calls, reasoning blocks
+    /// and usage are recorded in `reply`; text is returned for the caller.
+    fn parse_line(&self, line: &str, reply: &mut Reply) -> Vec<Delta>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderKind {
+    #[default]
+    OpenRouter,
+    /// Any server speaking the OpenAI chat completions API, such as a
+    /// gateway, llama.cpp or Ollama.
+    #[serde(rename = "openai")]
+    OpenAi,
+    Anthropic,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProviderConfig {
+    pub kind: ProviderKind,
+    /// Overrides the provider's own endpoint; required for `openai`.
+    pub base_url: Option<String>,
+    /// Used when a chat message does not name a model.
+    pub model: Option<String>,
+}
+
+impl ProviderConfig {
+    /// Environment variable read when no API key has been set.
+    pub fn key_variable(&self) -> &'static str {
+        match self.kind {
+            ProviderKind::OpenRouter => "OPENROUTER_API_KEY",
+            ProviderKind::OpenAi => "OPENAI_API_KEY",
+            ProviderKind::Anthropic => "ANTHROPIC_API_KEY",
+        }
+    }
+
+    /// Local OpenAI-compatible servers usually run without a key.
+    pub fn needs_key(&self) -> bool {
+        self.kind != ProviderKind::OpenAi
+    }
+
+    pub fn build(&self, api_key: String) -> Result<Box<dyn Provider>, String> {
+        let base_url = self.base_url.clone().filter(|u| !u.trim().is_empty());
+        Ok(match self.kind {
+            ProviderKind::OpenRouter => {
+                Box::new(openai::OpenAiCompatible::openrouter(base_url, api_key))
+            }
+            ProviderKind::OpenAi => {
+                let base_url = base_url.ok_or("An OpenAI-compatible provider needs a base URL")?;
+                Box::new(openai::OpenAiCompatible::new(base_url, api_key))
+            }
+            ProviderKind::Anthropic => Box::new(anthropic::Anthropic::new(base_url, api_key)),
+        })
+    }
+}
+
+/// Payload of an SSE `data:` line.
+fn sse_data(line: &str) -> Option<&str> {
+    line.strip_prefix("data:").map(str::trim_start)
+}
+
+/// Feeds a recorded response stream through `provider` line by line.
+#[cfg(test)]
+fn replay(provider: &dyn Provider, stream: &str) -> (Vec<Delta>, Reply) {
+    let mut reply = Reply::default();
+    let mut deltas = Vec::new();
+    for line in stream.lines() {
+        deltas.extend(provider.parse_line(line, &mut reply));
+    }
+    (deltas, reply)
+}
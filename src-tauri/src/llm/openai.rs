@@ -0,0 +1,232 @@
+use super::{sse_data, CompletionRequest, Delta, Provider, Reply, ToolCall, Usage};
+use serde_json::{json, Value};
+
+const OPENROUTER_URL: &str = "https://openrouter.ai/api/v1";
+
+/// The OpenAI chat completions API, as spoken by OpenRouter, gateways and
+/// local servers.
+pub struct OpenAiCompatible {
+    name: &'static str,
+    base_url: String,
+    api_key: String,
+    default_model: Option<&'static str>,
+}
+
+impl OpenAiCompatible {
+    pub fn new(base_url: String, api_key: String) -> Self {
+        Self {
+            name: "openai",
+            base_url,
+            api_key,
+            default_model: None,
+        }
+    }
+
+    pub fn openrouter(base_url: Option<String>, api_key: String) -> Self {
+        Self {
+            name: "openrouter",
+            base_url: base_url.unwrap_or_else(|| OPENROUTER_URL.to_string()),
+            api_key,
+            default_model: Some("google/gemini-3.1-pro-preview"),
+        }
+    }
+}
+
+/// Merges one `delta.tool_calls` entry. The id and name arrive in the first
+/// fragment of a call; the arguments are spread over many.
+fn merge_tool_call(tool_calls: &mut Vec<ToolCall>, delta: &Value) {
+    let index = delta["index"].as_u64().unwrap_or(0) as usize;
+    if tool_calls.len() <= index {
+        tool_calls.resize_with(index + 1, ToolCall::default);
+    }
+    let call = &mut tool_calls[index];
+    if let Some(id) = delta["id"].as_str() {
+        call.id = id.to_string();
+    }
+    if let Some(kind) = delta["type"].as_str() {
+        call.kind = kind.to_string();
+    } else if call.kind.is_empty() {
+        call.kind = "function".to_string();
+    }
+    if let Some(name) = delta["function"]["name"].as_str() {
+        call.function.name.push_str(name);
+    }
+    if let Some(arguments) = delta["function"]["arguments"].as_str() {
+        call.function.arguments.push_str(arguments);
+    }
+}
+
+impl Provider for OpenAiCompatible {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn default_model(&self) -> Option<&str> {
+        self.default_model
+    }
+
+    fn request(
+        &self,
+        client: &reqwest::Client,
+        completion: &CompletionRequest,
+    ) -> reqwest::RequestBuilder {
+        let mut body = json!({
+            "model": completion.model,
+            "max_tokens": completion.max_tokens,
+            "messages": completion.messages,
+            "stream": true,
+            "stream_options": { "include_usage": true },
+        });
+        if !completion.tools.is_empty() {
+            body["tools"] = json!(completion.tools);
+        }
+
+        let url = format!("{}/chat/completions", self.base_url.trim_end_matches('/'));
+        let mut request = client
+            .post(url)
+            .header("content-type", "application/json")
+            .json(&body);
+        if !self.api_key.is_empty() {
+            request = request.header("Authorization", format!("Bearer {}", self.api_key));
+        }
+        request
+    }
+
+    fn parse_line(&self, line: &str, reply: &mut Reply) -> Vec<Delta> {
+        let Some(data) = sse_data(line) else {
+            return vec![];
+        };
+        if data == "[DONE]" {
+            return vec![Delta::Done];
+        }
+        let Ok(parsed) = serde_json::from_str::<Value>(data) else {
+            return vec![];
+        };
+
+        if let Some(err) = parsed.get("error") {
+            let message = err["message"].as_str().unwrap_or("Unknown API error");
+            return vec![Delta::Error(message.to_string())];
+        }
+
+        if let Some(usage) = parsed.get("usage").filter(|u| u.is_object()) {
+            reply.usage = Some(Usage {
+                input_tokens: usage["prompt_tokens"].as_u64().unwrap_or(0),
+                output_tokens: usage["completion_tokens"].as_u64().unwrap_or(0),
+            });
+        }
+
+        let mut deltas = Vec::new();
+        if let Some(choice) = parsed["choices"].as_array().and_then(|c| c.first()) {
+            let delta = &choice["delta"];
+            // OpenRouter calls it `reasoning`; llama.cpp and others use
+            // `reasoning_content`.
+            let reasoning = delta["reasoning"]
+                .as_str()
+                .or_else(|| delta["reasoning_content"].as_str());
+            if let Some(reasoning) = reasoning.filter(|r| !r.is_empty()) {
+                deltas.push(Delta::Thinking(reasoning.to_string()));
+            }
+            if let Some(content) = delta["content"].as_str().filter(|c| !c.is_empty()) {
+                deltas.push(Delta::Text(content.to_string()));
+            }
+            if let Some(calls) = delta["tool_calls"].as_array() {
+                for call in calls {
+                    merge_tool_call(&mut reply.tool_calls, call);
+                }
+            }
+        }
+        deltas
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::replay;
+
+    fn provider() -> OpenAiCompatible {
+        OpenAiCompatible::new("http://localhost:8080/v1".to_string(), String::new())
+    }
+
+    #[test]
+    fn streams_text_reasoning_and_usage() {
+        let (deltas, reply) = replay(
+            &provider(),
+            include_str!("../../tests/fixtures/sse/openrouter_text.sse"),
+        );
+        assert_eq!(
+            deltas,
+            [
+                Delta::Thinking("The corner is 1/(2*pi*R*C).".to_string()),
+                Delta::Text("The cutoff is ".to_string()),
+                Delta::Text("1.6 kHz.".to_string()),
+                Delta::Done,
+            ]
+        );
+        assert!(reply.tool_calls.is_empty());
+        let usage = reply.usage.unwrap();
+        assert_eq!((usage.input_tokens, usage.output_tokens), (812, 9));
+    }
+
+    #[test]
+    fn reads_reasoning_content_from_local_servers() {
+        let (deltas, reply) = replay(
+            &provider(),
+            include_str!("../../tests/fixtures/sse/llamacpp_reasoning.sse"),
+        );
+        assert_eq!(
+            deltas,
+            [
+                Delta::Thinking("Lower C raises fc.".to_string()),
+                Delta::Text("Use 47n.".to_string()),
+                Delta::Done,
+            ]
+        );
+        assert_eq!(reply.usage.unwrap().output_tokens, 12);
+    }
+
+    #[test]
+    fn assembles_interleaved_tool_call_fragments() {
+        let (deltas, reply) = replay(
+            &provider(),
+            include_str!("../../tests/fixtures/sse/openai_tool_calls.sse"),
+        );
+        assert_eq!(deltas, [Delta::Done]);
+
+        let calls: Vec<_> = reply
+            .tool_calls
+            .iter()
+            .map(|c| (c.id.as_str(), c.kind.as_str(), c.function.name.as_str()))
+            .collect();
+        assert_eq!(
+            calls,
+            [
+                ("call_a", "function", "read_file"),
+                ("call_b", "function", "list_nets"),
+            ]
+        );
+        assert_eq!(
+            reply.tool_calls[0].arguments().unwrap(),
+            json!({ "file": "sub/rc.asc" })
+        );
+        assert_eq!(reply.tool_calls[1].arguments().unwrap(), json!({}));
+        let usage = reply.usage.unwrap();
+        assert_eq!((usage.input_tokens, usage.output_tokens), (1540, 38));
+    }
+
+    #[test]
+    fn reports_errors_and_skips_noise() {
+        let mut reply = Reply::default();
+        let provider = provider();
+        assert_eq!(
+            provider.parse_line(
+                r#"data: {"error":{"message":"Rate limit exceeded","code":429}}"#,
+                &mut reply
+            ),
+            [Delta::Error("Rate limit exceeded".to_string())]
+        );
+        assert_eq!(provider.parse_line(": keep-alive", &mut reply), []);
+        assert_eq!(provider.parse_line("data: {truncated", &mut reply), []);
+        assert!(reply.usage.is_none());
+    }
+}
@@ -0,0 +1,390 @@
+use crate::textfile;
+use serde::Serialize;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Measurement {
+    pub name: String,
+    /// The measured expression when the simulator prints it, e.g. `MAX(v(out))`.
+    pub expression: Option<String>,
+    /// `.step` run the result belongs to, 1-based.
+    pub step: Option<usize>,
+    /// None when the measurement failed or its result is not numeric.
+    pub value: Option<f64>,
+    /// Whatever follows the value, e.g. `FROM 0 TO 0.001` or `at= 1e-03`.
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub message: String,
+    /// Node named in the message, such as the one a singular matrix points at.
+    pub node: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StepParam {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Step {
+    pub index: usize,
+    pub params: Vec<StepParam>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SimulationReport {
+    pub measurements: Vec<Measurement>,
+    pub warnings: Vec<Diagnostic>,
+    pub errors: Vec<Diagnostic>,
+    pub steps: Vec<Step>,
+    pub elapsed_seconds: Option<f64>,
+}
+
+impl SimulationReport {
+    pub fn has_errors(&self) -> bool {
+        !self.errors.is_empty()
+    }
+}
+
+/// Phrases that mark a line as an error whatever its prefix. ngspice reports
+/// a singular matrix as a warning, but the run never recovers from it.
+const ERROR_MARKERS: [&str; 11] = [
+    "singular matrix",
+    "time step too small",
+    "timestep too small",
+    "gmin stepping failed",
+    "source stepping failed",
+    "iteration limit reached",
+    "unknown subcircuit",
+    "unknown parameter",
+    "can't find definition",
+    "could not open",
+    "fatal",
+];
+
+/// `name = value` lines LTspice prints that are solver statistics or
+/// convergence progress rather than `.meas` results.
+const STATISTICS: [&str; 11] = [
+    "gmin",
+    "tnom",
+    "temp",
+    "method",
+    "totiter",
+    "traniter",
+    "tranpoints",
+    "accept",
+    "rejected",
+    "fillins",
+    "solver",
+];
+
+fn is_identifier(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Reads the leading number of a result, accepting LTspice's AC form
+/// `(-6.02dB,0°)` by taking the magnitude.
+fn leading_number(s: &str) -> Option<f64> {
+    let s = s.trim_start_matches('(');
+    let end = s
+        .char_indices()
+        .find(|&(i, c)| {
+            !(c.is_ascii_digit() || c == '.' || (i > 0 && "eE".contains(c)) || "+-".contains(c))
+        })
+        .map(|(i, _)| i)
+        .unwrap_or(s.len());
+    s[..end].parse().ok()
+}
+
+fn node_of(message: &str) -> Option<String> {
+    let lower = message.to_ascii_lowercase();
+    let at = lower.find("node ")? + "node ".len();
+    let node = message[at..]
+        .split_whitespace()
+        .next()?
+        .trim_matches(['"', '\'', '.', ',', ':']);
+    (!node.is_empty()).then(|| node.to_string())
+}
+
+fn push_unique(list: &mut Vec<Diagnostic>, message: &str) {
+    if !list.iter().any(|d| d.message == message) {
+        list.push(Diagnostic {
+            message: message.to_string(),
+            node: node_of(message),
+        });
+    }
+}
+
+fn parse_step(line: &str, index: usize) -> Step {
+    let params = line
+        .split_whitespace()
+        .skip(1)
+        .filter_map(|assignment| assignment.split_once('='))
+        .map(|(name, value)| StepParam {
+            name: name.to_string(),
+            value: value.to_string(),
+        })
+        .collect();
+    Step { index, params }
+}
+
+fn parse_elapsed(line: &str) -> Option<f64> {
+    let rest = line.strip_prefix("Total elapsed time")?;
+    rest.split([':', '='])
+        .nth(1)
+        .and_then(|v| v.split_whitespace().next())
+        .and_then(|v| v.parse().ok())
+}
+
+/// Splits `value rest...` into the parsed value and the remaining text.
+fn split_result(result: &str) -> (Option<f64>, String) {
+    let result = result.trim();
+    if result.contains("FAIL") {
+        return (None, result.to_string());
+    }
+    let (value, detail) = result
+        .split_once(char::is_whitespace)
+        .unwrap_or((result, ""));
+    (leading_number(value), detail.trim().to_string())
+}
+
+/// Recognizes a single-line `.meas` result in either simulator's format:
+///
+/// - LTspice: `vpk: MAX(v(out))=0.99 FROM 0 TO 0.001`, `tr=1.2e-05 FROM ...`
+/// - ngspice: `vpk                 =  9.900000e-01 at=  1.000000e-03`
+/// - LTspice failures: `Measurement "vmin" FAIL'ed`
+fn parse_measurement(line: &str) -> Option<Measurement> {
+    if let Some(rest) = line.strip_prefix("Measurement \"") {
+        let (name, result) = rest.split_once('"')?;
+        return Some(Measurement {
+            name: name.to_string(),
+            expression: None,
+            step: None,
+            value: None,
+            detail: result.trim().to_string(),
+        });
+    }
+    if let Some((name, rest)) = line.split_once(':') {
+        let name = name.trim();
+        if is_identifier(name) && (rest.contains('=') || rest.contains("FAIL")) {
+            let (expression, result) = match rest.split_once('=') {
+                Some((expression, result)) => (Some(expression.trim().to_string()), result),
+                None => (None, rest),
+            };
+            let (value, detail) = split_result(result);
+            return Some(Measurement {
+                name: name.to_string(),
+                expression,
+                step: None,
+                value,
+                detail,
+            });
+        }
+    }
+
+    let (name, result) = line.split_once('=')?;
+    let name = name.trim();
+    if !is_identifier(name) || STATISTICS.contains(&name.to_ascii_lowercase().as_str()) {
+        return None;
+    }
+    let (value, detail) = split_result(result);
+    value.map(|value| Measurement {
+        name: name.to_string(),
+        expression: None,
+        step: None,
+        value: Some(value),
+        detail,
+    })
+}
+
+/// A stepped `.meas` table: `Measurement: name`, a header row starting with
+/// `step`, then one row per step.
+struct Table {
+    name: String,
+    expression: Option<String>,
+}
+
+/// Turns the text of an LTspice or ngspice `.log` file into a report.
+pub fn parse(text: &str) -> SimulationReport {
+    let mut report = SimulationReport::default();
+    let mut table: Option<Table> = None;
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            table = None;
+            continue;
+        }
+
+        if let Some(t) = &mut table {
+            let mut fields = line.split_whitespace();
+            let first = fields.next().unwrap_or("");
+            if first.eq_ignore_ascii_case("step") {
+                t.expression = fields.next().map(String::from);
+                continue;
+            }
+            if let Ok(step) = first.parse::<usize>() {
+                let (value, detail) = split_result(&fields.collect::<Vec<_>>().join(" "));
+                report.measurements.push(Measurement {
+                    name: t.name.clone(),
+                    expression: t.expression.clone(),
+                    step: Some(step),
+                    value,
+                    detail,
+                });
+                continue;
+            }
+            table = None;
+        }
+
+        if let Some(name) = line.strip_prefix("Measurement:") {
+            table = Some(Table {
+                name: name.trim().to_string(),
+                expression: None,
+            });
+            continue;
+        }
+        if line.to_ascii_lowercase().starts_with(".step") {
+            let index = report.steps.len() + 1;
+            report.steps.push(parse_step(line, index));
+            continue;
+        }
+        if let Some(elapsed) = parse_elapsed(line) {
+            report.elapsed_seconds = Some(elapsed);
+            continue;
+        }
+
+        let lower = line.to_ascii_lowercase();
+        if lower.starts_with("error") || ERROR_MARKERS.iter().any(|m| lower.contains(m)) {
+            push_unique(&mut report.errors, line);
+        } else if lower.starts_with("warning") || lower.starts_with("questionable") {
+            push_unique(&mut report.warnings, line);
+        } else if let Some(measurement) = parse_measurement(line) {
+            report.measurements.push(measurement);
+        }
+    }
+
+    report
+}
+
+pub fn load(path: &Path) -> Result<SimulationReport, String> {
+    let bytes =
+        std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Ok(parse(&textfile::decode(&bytes, None).content))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> SimulationReport {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/log")
+            .join(name);
+        load(&path).unwrap()
+    }
+
+    fn measurement<'a>(report: &'a SimulationReport, name: &str) -> &'a Measurement {
+        report.measurements.iter().find(|m| m.name == name).unwrap()
+    }
+
+    #[test]
+    fn reads_ltspice_measurements_and_skips_statistics() {
+        let report = fixture("ltspice_tran.log");
+        let names: Vec<&str> = report
+            .measurements
+            .iter()
+            .map(|m| m.name.as_str())
+            .collect();
+        assert_eq!(names, ["vpk", "tr", "gain", "vmin"]);
+
+        let vpk = measurement(&report, "vpk");
+        assert_eq!(vpk.expression.as_deref(), Some("MAX(v(out))"));
+        assert_eq!(vpk.value, Some(0.993262));
+        assert_eq!(vpk.detail, "FROM 0 TO 0.005");
+        assert_eq!(measurement(&report, "tr").value, Some(1.23457e-05));
+        assert_eq!(measurement(&report, "gain").value, Some(-6.02));
+        assert_eq!(measurement(&report, "vmin").value, None);
+
+        assert_eq!(report.elapsed_seconds, Some(0.123));
+        assert!(report.errors.is_empty());
+        assert_eq!(report.warnings.len(), 1);
+        assert_eq!(report.warnings[0].node.as_deref(), Some("N003"));
+    }
+
+    #[test]
+    fn reads_ltspice_stepped_tables() {
+        let report = fixture("ltspice_stepped.log");
+        assert_eq!(report.steps.len(), 3);
+        assert_eq!(report.steps[1].params[0].name, "rload");
+        assert_eq!(report.steps[1].params[0].value, "1k");
+
+        let vpk: Vec<_> = report
+            .measurements
+            .iter()
+            .filter(|m| m.name == "vpk")
+            .map(|m| (m.step, m.value))
+            .collect();
+        assert_eq!(
+            vpk,
+            [
+                (Some(1), Some(0.0909091)),
+                (Some(2), Some(0.5)),
+                (Some(3), Some(0.909091))
+            ]
+        );
+        let tcross = measurement(&report, "tcross");
+        assert_eq!(tcross.expression.as_deref(), Some("v(out)=0.45"));
+        assert_eq!((tcross.step, tcross.value), (Some(1), None));
+        assert_eq!(report.measurements.len(), 6);
+    }
+
+    #[test]
+    fn reads_ltspice_convergence_failures() {
+        let report = fixture("ltspice_failed.log");
+        assert!(report.measurements.is_empty(), "{:?}", report.measurements);
+        let messages: Vec<&str> = report.errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Singular matrix:  Check node n002",
+                "Gmin stepping failed",
+                "Source stepping failed",
+                "Analysis: Time step too small; initial timepoint: trouble with node n002",
+            ]
+        );
+        assert_eq!(report.errors[0].node.as_deref(), Some("n002"));
+    }
+
+    #[test]
+    fn reads_ngspice_measurements() {
+        let report = fixture("ngspice_tran.log");
+        let names: Vec<&str> = report
+            .measurements
+            .iter()
+            .map(|m| m.name.as_str())
+            .collect();
+        assert_eq!(names, ["vpk", "tr"]);
+        let vpk = measurement(&report, "vpk");
+        assert_eq!(vpk.value, Some(0.993262));
+        assert_eq!(vpk.detail, "at=  5.000000e-03");
+        assert!(report.errors.is_empty());
+        assert_eq!(report.warnings.len(), 1);
+    }
+
+    #[test]
+    fn reads_ngspice_errors() {
+        let report = fixture("ngspice_failed.log");
+        assert_eq!(report.errors.len(), 3, "{:?}", report.errors);
+        assert!(report.errors[0]
+            .message
+            .starts_with("Error: unknown subckt"));
+        // A singular matrix is fatal even though ngspice calls it a warning.
+        assert_eq!(report.errors[1].node.as_deref(), Some("out"));
+        assert_eq!(report.errors[2].node.as_deref(), Some("out"));
+        assert!(report.warnings.is_empty());
+    }
+}
@@ -0,0 +1,562 @@
+use crate::simulator::{find_on_path, run_streaming};
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Set to `stub` to replace LTspice with a backend that does nothing, for
+/// machines without it such as CI.
+pub const BACKEND_VARIABLE: &str = "SPICY_LTSPICE";
+
+/// How often the output files are checked once LTspice has exited.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// What became of a request to show a file's saved content in LTspice.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ReloadStatus {
+    Reloaded,
+    /// LTspice was not running, so nothing showed stale content.
+    NotRunning,
+    /// There is no way to make LTspice reload on this platform; it picks up
+    /// the change when the file is next opened.
+    Unsupported,
+    /// LTspice is running but no window showing the file could be brought
+    /// to the front, so it may still show the old content.
+    Unknown,
+    /// The stub backend was used.
+    Skipped,
+    Failed {
+        message: String,
+    },
+}
+
+/// How to run the Windows build of LTspice on other platforms.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WineConfig {
+    /// The `wine` launcher; found on `PATH` when unset.
+    pub executable: Option<String>,
+    /// The Wine prefix LTspice is installed in; `WINEPREFIX` or `~/.wine`
+    /// when unset.
+    pub prefix: Option<String>,
+}
+
+impl WineConfig {
+    fn executable(&self) -> Option<PathBuf> {
+        match self.executable.as_deref().filter(|e| !e.is_empty()) {
+            Some(executable) => Some(PathBuf::from(executable)),
+            None => find_on_path("wine"),
+        }
+    }
+
+    fn prefix(&self) -> Option<PathBuf> {
+        self.prefix
+            .as_deref()
+            .filter(|p| !p.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("WINEPREFIX").map(PathBuf::from))
+            .or_else(|| home().map(|h| h.join(".wine")))
+    }
+}
+
+/// An LTspice executable, run directly or, for the Windows build on Linux,
+/// through Wine.
+#[derive(Debug, Clone, Serialize)]
+pub struct Installation {
+    pub executable: PathBuf,
+    pub wine: Option<PathBuf>,
+    pub wine_prefix: Option<PathBuf>,
+}
+
+/// `path` in Windows form for a program under Wine: files inside the
+/// prefix's `drive_c` map to `C:`, everything else to drive `Z:`, which
+/// Wine maps to the host root.
+pub fn windows_path(path: &Path, prefix: Option<&Path>) -> String {
+    let drive_c = prefix.map(|p| p.join("drive_c"));
+    let (drive, rest) = match drive_c.as_deref().and_then(|c| path.strip_prefix(c).ok()) {
+        Some(rest) => ("C:", rest),
+        None => ("Z:", path.strip_prefix("/").unwrap_or(path)),
+    };
+    format!("{}\\{}", drive, rest.to_string_lossy().replace('/', "\\"))
+}
+
+/// The host path of a Windows path printed by a program under Wine, for the
+/// `C:` and `Z:` drives.
+pub fn unix_path(path: &str, prefix: Option<&Path>) -> Option<PathBuf> {
+    let (drive, rest) = path.split_at_checked(2)?;
+    let rest = rest.trim_start_matches('\\').replace('\\', "/");
+    match drive.to_ascii_uppercase().as_str() {
+        "Z:" => Some(Path::new("/").join(rest)),
+        "C:" => Some(prefix?.join("drive_c").join(rest)),
+        _ => None,
+    }
+}
+
+/// Where the LTspice installers put the executable, relative to a Windows
+/// drive root.
+const WINDOWS_LOCATIONS: [&str; 3] = [
+    "Program Files/ADI/LTspice/LTspice.exe",
+    "Program Files/LTC/LTspiceXVII/XVIIx64.exe",
+    "Program Files (x86)/LTC/LTspiceIV/scad3.exe",
+];
+
+fn home() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
+
+fn is_windows_executable(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("exe"))
+}
+
+impl Installation {
+    /// Uses `configured` when it exists, otherwise searches the usual
+    /// install locations for the platform.
+    pub fn locate(configured: Option<&Path>, wine: &WineConfig) -> Option<Self> {
+        if let Some(path) = configured.filter(|p| p.exists()) {
+            return Self::for_executable(path.to_path_buf(), wine);
+        }
+        Self::candidates(wine)
+            .into_iter()
+            .find(|p| p.exists())
+            .and_then(|p| Self::for_executable(p, wine))
+    }
+
+    fn for_executable(executable: PathBuf, wine: &WineConfig) -> Option<Self> {
+        if cfg!(windows) || !is_windows_executable(&executable) {
+            return Some(Self {
+                executable,
+                wine: None,
+                wine_prefix: None,
+            });
+        }
+        Some(Self {
+            executable,
+            wine: Some(wine.executable()?),
+            wine_prefix: wine.prefix(),
+        })
+    }
+
+    fn candidates(wine: &WineConfig) -> Vec<PathBuf> {
+        let mut candidates = Vec::new();
+        if cfg!(windows) {
+            if let Some(local) = std::env::var_os("LOCALAPPDATA") {
+                candidates.push(PathBuf::from(local).join("Programs/ADI/LTspice/LTspice.exe"));
+            }
+            for root in ["C:/", "D:/"] {
+                candidates.extend(WINDOWS_LOCATIONS.iter().map(|l| Path::new(root).join(l)));
+            }
+        } else if cfg!(target_os = "macos") {
+            candidates.push(PathBuf::from(
+                "/Applications/LTspice.app/Contents/MacOS/LTspice",
+            ));
+            if let Some(home) = home() {
+                candidates.push(home.join("Applications/LTspice.app/Contents/MacOS/LTspice"));
+            }
+        } else {
+            if let Some(drive_c) = wine.prefix().map(|p| p.join("drive_c")) {
+                if let Ok(user) = std::env::var("USER") {
+                    candidates.push(
+                        drive_c
+                            .join("users")
+                            .join(user)
+                            .join("AppData/Local/Programs/ADI/LTspice/LTspice.exe"),
+                    );
+                }
+                candidates.extend(WINDOWS_LOCATIONS.iter().map(|l| drive_c.join(l)));
+            }
+        }
+        candidates
+    }
+
+    pub fn command(&self) -> Command {
+        match &self.wine {
+            Some(wine) => {
+                let mut command = Command::new(wine);
+                if let Some(prefix) = &self.wine_prefix {
+                    command.env("WINEPREFIX", prefix);
+                }
+                command.arg(&self.executable);
+                command
+            }
+            None => Command::new(&self.executable),
+        }
+    }
+
+    /// `path` as LTspice sees it.
+    pub fn path_arg(&self, path: &Path) -> OsString {
+        if self.wine.is_none() {
+            return path.as_os_str().to_os_string();
+        }
+        windows_path(path, self.wine_prefix.as_deref()).into()
+    }
+
+    /// Rewrites the Windows paths in a line LTspice printed under Wine to
+    /// host paths.
+    pub fn host_paths(&self, line: &str) -> String {
+        if self.wine.is_none() {
+            return line.to_string();
+        }
+        line.split(' ')
+            .map(|word| {
+                let path = word.trim_matches(|c| c == '"' || c == '\'');
+                match unix_path(path, self.wine_prefix.as_deref()) {
+                    Some(host) if path.contains('\\') => {
+                        word.replace(path, &host.to_string_lossy())
+                    }
+                    _ => word.to_string(),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+fn file_size(path: &Path) -> Option<u64> {
+    std::fs::metadata(path).ok().map(|m| m.len())
+}
+
+/// Waits until the log exists and neither file has grown since the last
+/// check, or until `deadline`. The Wine launcher can return before LTspice
+/// has finished writing.
+fn wait_for_output(raw_path: &Path, log_path: &Path, deadline: Instant) {
+    let mut last = None;
+    loop {
+        let sizes = (file_size(raw_path), file_size(log_path));
+        if (sizes.1.is_some() && last == Some(sizes)) || Instant::now() >= deadline {
+            return;
+        }
+        last = Some(sizes);
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// A way of driving LTspice.
+pub trait Integration: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Whether there is an LTspice to drive.
+    fn available(&self) -> bool;
+
+    /// Opens `schematic` in an LTspice window, simulating it when `run` is set.
+    fn open(&self, schematic: &Path, run: bool) -> Result<(), String>;
+
+    /// Makes a running LTspice show the saved content of `schematic`.
+    fn reload(&self, schematic: &Path) -> ReloadStatus;
+
+    /// Simulates a netlist or schematic without a window, writing an ASCII
+    /// `.raw` and a `.log` beside it, and returns once they are written.
+    /// Paths in the output are host paths. Returns whether LTspice exited
+    /// cleanly.
+    fn batch(
+        &self,
+        file: &Path,
+        timeout: Duration,
+        on_output: &mut dyn FnMut(&str),
+    ) -> Result<bool, String>;
+}
+
+/// The installed LTspice.
+pub struct Desktop {
+    installation: Option<Installation>,
+}
+
+impl Desktop {
+    pub fn new(installation: Option<Installation>) -> Self {
+        Self { installation }
+    }
+
+    fn installation(&self) -> Result<&Installation, String> {
+        self.installation
+            .as_ref()
+            .ok_or_else(|| "LTspice was not found; set its path in the settings".to_string())
+    }
+}
+
+/// Brings the window showing the file (named by file name or stem) to the
+/// front and clicks File > Revert to Saved, which acts on the frontmost
+/// document.
+#[cfg(target_os = "macos")]
+const REVERT_SCRIPT: &str = r#"on run argv
+    set fileName to item 1 of argv
+    set stemName to item 2 of argv
+    tell application "System Events"
+        if not (exists process "LTspice") then return "not running"
+        tell process "LTspice"
+            set matching to every window whose name is fileName or name is stemName
+            if (count of matching) is 0 then return "unknown"
+            perform action "AXRaise" of item 1 of matching
+            set frontmost to true
+            delay 0.3
+            if name of window 1 is not in {fileName, stemName} then return "unknown"
+            click menu item "Revert to Saved" of menu "File" of menu bar 1
+        end tell
+    end tell
+    return "reloaded"
+end run"#;
+
+impl Integration for Desktop {
+    fn name(&self) -> &str {
+        "ltspice"
+    }
+
+    fn available(&self) -> bool {
+        self.installation.is_some()
+    }
+
+    fn open(&self, schematic: &Path, run: bool) -> Result<(), String> {
+        let installation = self.installation()?;
+        let mut command = installation.command();
+        if run {
+            command.arg("-Run");
+        }
+        command
+            .arg(installation.path_arg(schematic))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map(|_| ())
+            .map_err(|e| format!("Failed to start LTspice: {}", e))
+    }
+
+    #[cfg(target_os = "macos")]
+    fn reload(&self, schematic: &Path) -> ReloadStatus {
+        let output = Command::new("osascript")
+            .arg("-e")
+            .arg(REVERT_SCRIPT)
+            .arg(schematic.file_name().unwrap_or_default())
+            .arg(schematic.file_stem().unwrap_or_default())
+            .output();
+        match output {
+            Ok(output) if output.status.success() => {
+                match String::from_utf8_lossy(&output.stdout).trim() {
+                    "reloaded" => ReloadStatus::Reloaded,
+                    "not running" => ReloadStatus::NotRunning,
+                    _ => ReloadStatus::Unknown,
+                }
+            }
+            Ok(output) => ReloadStatus::Failed {
+                message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            },
+            Err(e) => ReloadStatus::Failed {
+                message: e.to_string(),
+            },
+        }
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    fn reload(&self, _schematic: &Path) -> ReloadStatus {
+        ReloadStatus::Unsupported
+    }
+
+    fn batch(
+        &self,
+        file: &Path,
+        timeout: Duration,
+        on_output: &mut dyn FnMut(&str),
+    ) -> Result<bool, String> {
+        let installation = self.installation()?;
+        let mut command = installation.command();
+        command
+            .arg("-b")
+            .arg("-ascii")
+            .arg(installation.path_arg(file));
+        if let Some(dir) = file.parent() {
+            command.current_dir(dir);
+        }
+        let started = Instant::now();
+        let exited_ok = run_streaming(command, timeout, &mut |line| {
+            on_output(&installation.host_paths(line))
+        })?;
+        // After a failure or timeout there is nothing left to wait for.
+        if exited_ok {
+            let (raw, log) = (file.with_extension("raw"), file.with_extension("log"));
+            wait_for_output(&raw, &log, started + timeout);
+        }
+        Ok(exited_ok)
+    }
+}
+
+/// Does nothing and records what it was asked to do.
+#[derive(Default)]
+pub struct Stub {
+    calls: Mutex<Vec<String>>,
+}
+
+impl Stub {
+    pub fn calls(&self) -> Vec<String> {
+        self.calls.lock().map(|c| c.clone()).unwrap_or_default()
+    }
+
+    fn record(&self, call: String) {
+        if let Ok(mut calls) = self.calls.lock() {
+            calls.push(call);
+        }
+    }
+}
+
+impl Integration for Stub {
+    fn name(&self) -> &str {
+        "stub"
+    }
+
+    fn available(&self) -> bool {
+        true
+    }
+
+    fn open(&self, schematic: &Path, run: bool) -> Result<(), String> {
+        self.record(format!("open {} run={}", schematic.display(), run));
+        Ok(())
+    }
+
+    fn reload(&self, schematic: &Path) -> ReloadStatus {
+        self.record(format!("reload {}", schematic.display()));
+        ReloadStatus::Skipped
+    }
+
+    fn batch(
+        &self,
+        file: &Path,
+        _timeout: Duration,
+        _on_output: &mut dyn FnMut(&str),
+    ) -> Result<bool, String> {
+        self.record(format!("batch {}", file.display()));
+        Ok(true)
+    }
+}
+
+/// The stub when `SPICY_LTSPICE=stub`, otherwise the installed LTspice,
+/// preferring the executable at `configured`.
+pub fn integration(configured: Option<&str>, wine: &WineConfig) -> Box<dyn Integration> {
+    if std::env::var(BACKEND_VARIABLE).is_ok_and(|v| v == "stub") {
+        return Box::new(Stub::default());
+    }
+    let installation = Installation::locate(configured.map(Path::new), wine);
+    Box::new(Desktop::new(installation))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn under_wine() -> Installation {
+        Installation {
+            executable: PathBuf::from(
+                "/home/user/.wine/drive_c/Program Files/ADI/LTspice/LTspice.exe",
+            ),
+            wine: Some(PathBuf::from("/usr/bin/wine")),
+            wine_prefix: Some(PathBuf::from("/home/user/.wine")),
+        }
+    }
+
+    #[test]
+    fn paths_inside_the_prefix_map_to_drive_c() {
+        let prefix = Path::new("/home/user/.wine");
+        let path = Path::new("/home/user/.wine/drive_c/users/user/Documents/rc.asc");
+        let windows = windows_path(path, Some(prefix));
+        assert_eq!(windows, "C:\\users\\user\\Documents\\rc.asc");
+        assert_eq!(unix_path(&windows, Some(prefix)).as_deref(), Some(path));
+    }
+
+    #[test]
+    fn paths_outside_the_prefix_map_to_drive_z() {
+        let prefix = Path::new("/home/user/.wine");
+        let path = Path::new("/home/user/projects/filter/rc.net");
+        let windows = windows_path(path, Some(prefix));
+        assert_eq!(windows, "Z:\\home\\user\\projects\\filter\\rc.net");
+        assert_eq!(unix_path(&windows, Some(prefix)).as_deref(), Some(path));
+        assert_eq!(windows_path(path, None), windows);
+        assert_eq!(unix_path(&windows, None).as_deref(), Some(path));
+    }
+
+    #[test]
+    fn other_drives_and_missing_prefix_have_no_host_path() {
+        assert_eq!(unix_path("D:\\data\\rc.raw", None), None);
+        assert_eq!(unix_path("C:\\users\\rc.raw", None), None);
+        assert_eq!(
+            unix_path("z:\\tmp\\rc.raw", None),
+            Some(PathBuf::from("/tmp/rc.raw"))
+        );
+        assert_eq!(unix_path("Z", None), None);
+    }
+
+    #[test]
+    fn host_paths_rewrites_wine_paths_only() {
+        let installation = under_wine();
+        assert_eq!(
+            installation.host_paths("Circuit: Z:\\tmp\\run\\rc.net"),
+            "Circuit: /tmp/run/rc.net"
+        );
+        assert_eq!(
+            installation.host_paths("Writing \"C:\\users\\user\\rc.raw\" now"),
+            "Writing \"/home/user/.wine/drive_c/users/user/rc.raw\" now"
+        );
+        assert_eq!(
+            installation.host_paths("Total elapsed time: 0.1 seconds. C: drive"),
+            "Total elapsed time: 0.1 seconds. C: drive"
+        );
+
+        let native = Installation {
+            wine: None,
+            wine_prefix: None,
+            ..under_wine()
+        };
+        assert_eq!(
+            native.host_paths("Circuit: Z:\\tmp\\rc.net"),
+            "Circuit: Z:\\tmp\\rc.net"
+        );
+    }
+
+    #[test]
+    fn path_arg_is_windows_form_under_wine() {
+        let path = Path::new("/tmp/run/rc.net");
+        assert_eq!(
+            under_wine().path_arg(path),
+            OsString::from("Z:\\tmp\\run\\rc.net")
+        );
+    }
+
+    #[test]
+    fn desktop_without_installation_is_unavailable() {
+        let desktop = Desktop::new(None);
+        assert!(!desktop.available());
+        assert!(desktop
+            .batch(
+                Path::new("/tmp/rc.net"),
+                Duration::from_secs(1),
+                &mut |_| {}
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn stub_records_calls() {
+        let stub = Stub::default();
+        assert!(stub.available());
+        let schematic = Path::new("/tmp/rc.asc");
+        stub.open(schematic, true).unwrap();
+        assert_eq!(stub.reload(schematic), ReloadStatus::Skipped);
+        let exited_ok = stub
+            .batch(
+                Path::new("/tmp/rc.net"),
+                Duration::from_secs(1),
+                &mut |_| {},
+            )
+            .unwrap();
+        assert!(exited_ok);
+        assert_eq!(
+            stub.calls(),
+            vec![
+                "open /tmp/rc.asc run=true",
+                "reload /tmp/rc.asc",
+                "batch /tmp/rc.net",
+            ]
+        );
+    }
+}
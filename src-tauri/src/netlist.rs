@@ -0,0 +1,147 @@
+use crate::asc::Schematic;
+use crate::connectivity::NetGraph;
+use crate::symbols::{SymbolDef, SymbolLibrary};
+use std::collections::HashMap;
+
+/// Instance attributes appended after the node list, in LTspice's order.
+/// `SpiceModel` takes the place of `Value` when both are present.
+const TRAILING_ATTRS: [&str; 3] = ["Value2", "SpiceLine", "SpiceLine2"];
+
+/// Instance name as it appears in the netlist: LTspice prepends the prefix
+/// unless the InstName already starts with it (`U1` → `XU1`).
+pub fn instance_name(prefix: &str, inst_name: &str) -> String {
+    let starts_with_prefix = inst_name
+        .get(..prefix.len())
+        .is_some_and(|head| head.eq_ignore_ascii_case(prefix));
+    if prefix.is_empty() || starts_with_prefix {
+        inst_name.to_string()
+    } else {
+        format!("{}{}", prefix, inst_name)
+    }
+}
+
+/// Emits a SPICE deck in the same shape LTspice writes: a title comment,
+/// one instance line per symbol in file order, `.lib` lines for symbols
+/// with a `ModelFile`, every `!` TEXT directive, then `.backanno` and `.end`.
+pub fn generate(
+    title: &str,
+    schematic: &Schematic,
+    graph: &NetGraph,
+    library: &SymbolLibrary,
+) -> String {
+    let mut out = format!("* {}\n", title);
+    let mut defs: HashMap<&str, Result<Option<SymbolDef>, String>> = HashMap::new();
+    let mut model_files: Vec<String> = Vec::new();
+
+    for (index, symbol) in schematic.symbols().enumerate() {
+        let inst_name = symbol.inst_name().unwrap_or(&symbol.name);
+        let def = defs
+            .entry(symbol.name.as_str())
+            .or_insert_with(|| library.lookup(&symbol.name));
+        let (Ok(Some(def)), Some(component)) = (&*def, graph.component_at(index)) else {
+            let reason = match def {
+                Err(e) => e.clone(),
+                _ => format!("symbol {} not found", symbol.name),
+            };
+            out.push_str(&format!("* {}: {}, not netlisted\n", inst_name, reason));
+            continue;
+        };
+
+        let attr = |key: &str| {
+            symbol
+                .attr(key)
+                .or_else(|| def.attr(key))
+                .filter(|v| !v.trim().is_empty())
+        };
+
+        // A Prefix set on the instance overrides the symbol's.
+        let prefix = attr("Prefix").unwrap_or(&def.prefix);
+        let mut fields = vec![instance_name(prefix, inst_name)];
+        let mut pins: Vec<_> = component.pins.iter().collect();
+        pins.sort_by_key(|p| p.spice_order);
+        fields.extend(pins.iter().map(|p| p.net.clone()));
+        if let Some(model) = attr("SpiceModel").or_else(|| attr("Value")) {
+            fields.push(model.to_string());
+        }
+        for key in TRAILING_ATTRS {
+            if let Some(value) = attr(key) {
+                fields.push(value.to_string());
+            }
+        }
+        out.push_str(&fields.join(" "));
+        out.push('\n');
+
+        if let Some(file) = attr("ModelFile") {
+            if !model_files.iter().any(|f| f == file) {
+                model_files.push(file.to_string());
+            }
+        }
+    }
+
+    for file in &model_files {
+        out.push_str(&format!(".lib {}\n", file));
+    }
+    for directive in schematic.directives() {
+        for line in directive.body().lines() {
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    out.push_str(".backanno\n.end\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectivity;
+    use std::path::Path;
+
+    /// Netlists an asc fixture against the bundled symbols plus the test
+    /// symbols, and compares it with the deck in `tests/fixtures/net`.
+    fn assert_deck(name: &str) {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let fixtures = root.join("tests/fixtures");
+        let bytes = std::fs::read(fixtures.join("asc").join(format!("{}.asc", name))).unwrap();
+        let schematic = Schematic::parse(&crate::textfile::decode(&bytes, None).content).unwrap();
+        let library = SymbolLibrary::new(vec![fixtures.join("sym"), root.join("resources/sym")]);
+        let graph = connectivity::extract(&schematic, &library);
+        let title = format!("C:\\Users\\spicy\\{}.asc", name);
+        let expected =
+            std::fs::read_to_string(fixtures.join("net").join(format!("{}.net", name))).unwrap();
+        assert_eq!(generate(&title, &schematic, &graph, &library), expected);
+    }
+
+    #[test]
+    fn matches_ltspice_for_rc_filter() {
+        assert_deck("rc_filter");
+    }
+
+    #[test]
+    fn matches_ltspice_for_inverting_amp() {
+        // U1's own Prefix, SpiceModel in place of Value, SpiceLine after it,
+        // and a directive spanning two lines.
+        assert_deck("inverting_amp");
+    }
+
+    #[test]
+    fn matches_ltspice_for_lc_utf16() {
+        assert_deck("lc_utf16");
+    }
+
+    #[test]
+    fn matches_ltspice_for_netlist_rules() {
+        // Pins in SpiceOrder rather than file order, an instance Prefix
+        // overriding the symbol's, two symbols sharing an InstName, Value2
+        // and SpiceLine, a ModelFile and a comment that is not netlisted.
+        assert_deck("netlist_rules");
+    }
+
+    #[test]
+    fn prefix_is_prepended_once() {
+        assert_eq!(instance_name("X", "U1"), "XU1");
+        assert_eq!(instance_name("X", "xU1"), "xU1");
+        assert_eq!(instance_name("R", "R1"), "R1");
+        assert_eq!(instance_name("", "U1"), "U1");
+    }
+}
@@ -0,0 +1,875 @@
+use crate::asc::{Item, Point, Rotation, Schematic, Symbol, SymbolLine, Text, Window, Wire};
+use crate::connectivity::{self, NetGraph};
+use crate::symbols::{SymbolDef, SymbolLibrary};
+use crate::validation::{Rule, Violation, GRID};
+use serde::Deserialize;
+
+/// Distances tried between a component and one added in parallel to it.
+const PARALLEL_SPACINGS: [i32; 2] = [96, 160];
+
+const ROTATIONS: [Rotation; 4] = [Rotation::R0, Rotation::R90, Rotation::R180, Rotation::R270];
+
+/// A schematic change described by intent. The geometry is worked out from
+/// the symbols' pin tables, so the model never computes coordinates.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Operation {
+    SetValue {
+        component: String,
+        value: String,
+    },
+    /// Cuts a wire of `net` and places a new two-pin component in the gap,
+    /// first pin towards the wire's start. `wire` picks the wire by its
+    /// endpoints; otherwise the longest one with room is used.
+    InsertSeries {
+        symbol: String,
+        value: String,
+        net: String,
+        wire: Option<[i32; 4]>,
+        name: Option<String>,
+    },
+    /// Places a new two-pin component beside `component` and wires it
+    /// across the same two pins.
+    AddParallel {
+        component: String,
+        symbol: String,
+        value: String,
+        name: Option<String>,
+    },
+    /// Deletes the component. With `bridge`, a wire joins its two pins.
+    RemoveComponent {
+        component: String,
+        #[serde(default)]
+        bridge: bool,
+    },
+    AddDirective {
+        text: String,
+    },
+    /// Renames an InstName, along with references to it in directives.
+    Rename {
+        from: String,
+        to: String,
+    },
+}
+
+/// Reads the `operations` array of a model response.
+pub fn parse(values: &[serde_json::Value]) -> Result<Vec<Operation>, Vec<Violation>> {
+    let mut parsed = Vec::new();
+    let mut violations = Vec::new();
+    for (i, value) in values.iter().enumerate() {
+        match Operation::deserialize(value) {
+            Ok(op) => parsed.push(op),
+            Err(e) => violations.push(Violation::new(
+                Rule::MalformedEdit,
+                format!("Operation {}: {}", i + 1, e),
+            )),
+        }
+    }
+    if violations.is_empty() {
+        Ok(parsed)
+    } else {
+        Err(violations)
+    }
+}
+
+/// Applies `operations` in order, stopping at the first that cannot be
+/// carried out.
+pub fn apply(
+    schematic: &mut Schematic,
+    operations: &[Operation],
+    library: &SymbolLibrary,
+) -> Result<(), Violation> {
+    for (i, op) in operations.iter().enumerate() {
+        apply_one(schematic, op, library).map_err(|e| {
+            Violation::new(
+                Rule::InvalidOperation,
+                format!("Operation {}: {}", i + 1, e),
+            )
+        })?;
+    }
+    Ok(())
+}
+
+fn apply_one(
+    schematic: &mut Schematic,
+    op: &Operation,
+    library: &SymbolLibrary,
+) -> Result<(), String> {
+    match op {
+        Operation::SetValue { component, value } => {
+            find_symbol(schematic, component)?.set_attr("Value", value.as_str());
+            Ok(())
+        }
+        Operation::InsertSeries {
+            symbol,
+            value,
+            net,
+            wire,
+            name,
+        } => {
+            let part = NewPart::resolve(schematic, library, symbol, value, name)?;
+            insert_series(schematic, library, part, net, *wire)
+        }
+        Operation::AddParallel {
+            component,
+            symbol,
+            value,
+            name,
+        } => {
+            let part = NewPart::resolve(schematic, library, symbol, value, name)?;
+            add_parallel(schematic, library, part, component)
+        }
+        Operation::RemoveComponent { component, bridge } => {
+            remove_component(schematic, library, component, *bridge)
+        }
+        Operation::AddDirective { text } => add_directive(schematic, text),
+        Operation::Rename { from, to } => rename(schematic, from, to),
+    }
+}
+
+fn find_symbol<'a>(schematic: &'a mut Schematic, name: &str) -> Result<&'a mut Symbol, String> {
+    schematic
+        .symbol_mut(name)
+        .ok_or_else(|| format!("No component named {}", name))
+}
+
+fn add(a: Point, b: Point) -> Point {
+    Point::new(a.x + b.x, a.y + b.y)
+}
+
+fn sub(a: Point, b: Point) -> Point {
+    Point::new(a.x - b.x, a.y - b.y)
+}
+
+fn scale(p: Point, k: i32) -> Point {
+    Point::new(p.x * k, p.y * k)
+}
+
+/// Unit direction and length from `a` to `b`, if they differ along one axis.
+fn direction(a: Point, b: Point) -> Option<(Point, i32)> {
+    let d = sub(b, a);
+    match (d.x, d.y) {
+        (0, 0) => None,
+        (0, dy) => Some((Point::new(0, dy.signum()), dy.abs())),
+        (dx, 0) => Some((Point::new(dx.signum(), 0), dx.abs())),
+        _ => None,
+    }
+}
+
+/// A two-pin symbol turned so that its first pin leads to its second.
+struct Placement {
+    rotation: Rotation,
+    /// Offset of the first pin from the symbol origin.
+    first: Point,
+    length: i32,
+}
+
+fn orient(def: &SymbolDef, dir: Point) -> Result<Placement, String> {
+    let mut pins: Vec<_> = def.pins.iter().collect();
+    if pins.len() != 2 {
+        return Err(format!(
+            "{} has {} pins; only two-pin components can be placed",
+            def.name,
+            pins.len()
+        ));
+    }
+    pins.sort_by_key(|p| p.spice_order);
+    ROTATIONS
+        .into_iter()
+        .find_map(|rotation| {
+            let first = rotation.apply(pins[0].offset);
+            let (d, length) = direction(first, rotation.apply(pins[1].offset))?;
+            (d == dir).then_some(Placement {
+                rotation,
+                first,
+                length,
+            })
+        })
+        .ok_or_else(|| format!("The pins of {} are not in line", def.name))
+}
+
+/// A component about to be added.
+struct NewPart {
+    def: SymbolDef,
+    inst_name: String,
+    value: String,
+}
+
+impl NewPart {
+    fn resolve(
+        schematic: &Schematic,
+        library: &SymbolLibrary,
+        symbol: &str,
+        value: &str,
+        name: &Option<String>,
+    ) -> Result<Self, String> {
+        let def = library
+            .lookup(symbol)?
+            .ok_or_else(|| format!("No pin layout for symbol {}", symbol))?;
+        let inst_name = match name {
+            Some(name) if schematic.symbol(name).is_some() => {
+                return Err(format!("{} already exists", name))
+            }
+            Some(name) => name.clone(),
+            None => next_inst_name(schematic, &def.prefix),
+        };
+        Ok(Self {
+            def,
+            inst_name,
+            value: value.to_string(),
+        })
+    }
+
+    fn symbol(&self, placement: &Placement, first_pin: Point) -> Symbol {
+        let mut symbol = Symbol::new(
+            self.def.name.clone(),
+            sub(first_pin, placement.first),
+            placement.rotation,
+        );
+        symbol.lines.extend(
+            side_windows(&self.def, placement.rotation)
+                .into_iter()
+                .map(SymbolLine::Window),
+        );
+        symbol.set_attr("InstName", self.inst_name.as_str());
+        symbol.set_attr("Value", self.value.as_str());
+        symbol
+    }
+}
+
+/// Label positions for a part lying on its side, as LTspice writes them for
+/// a turned resistor: name and value either side of the body, centred
+/// between the pins. Upright parts keep the symbol's own.
+fn side_windows(def: &SymbolDef, rotation: Rotation) -> Vec<Window> {
+    let (name_x, name_align, value_x, value_align) = match rotation {
+        Rotation::R90 => (0, "VBottom", 32, "VTop"),
+        Rotation::R270 => (32, "VTop", 0, "VBottom"),
+        _ => return Vec::new(),
+    };
+    let pins = def.pins.len().max(1) as i32;
+    let middle = def.pins.iter().map(|p| p.offset.y).sum::<i32>() / pins;
+    vec![
+        Window::new(0, Point::new(name_x, middle), name_align, 2),
+        Window::new(3, Point::new(value_x, middle), value_align, 2),
+    ]
+}
+
+/// First unused `<letter><n>`, e.g. `R3` when `R1` and `R2` exist.
+/// Subcircuits (prefix `X`) are numbered as `U` like LTspice does, and a
+/// name written with its prefix, such as `XU1`, counts as `U1`.
+fn next_inst_name(schematic: &Schematic, prefix: &str) -> String {
+    let prefix = prefix.to_ascii_uppercase();
+    let letter = match prefix.as_str() {
+        "" | "X" => "U",
+        other => other,
+    };
+    let number = |name: &str| {
+        let name = name.to_ascii_uppercase();
+        let name = name
+            .strip_prefix(prefix.as_str())
+            .filter(|rest| rest.starts_with(letter))
+            .unwrap_or(&name);
+        name.strip_prefix(letter)?.parse::<u32>().ok()
+    };
+    let next = schematic
+        .symbols()
+        .filter_map(|s| number(s.inst_name()?))
+        .max()
+        .unwrap_or(0)
+        + 1;
+    format!("{}{}", letter, next)
+}
+
+/// Position in the section order the prompt asks for: wires, flags,
+/// symbols, then text.
+fn section(item: &Item) -> u8 {
+    match item {
+        Item::Version { .. } | Item::Sheet(_) => 0,
+        Item::Wire(_) => 1,
+        Item::Flag(_) | Item::IoPin(_) => 2,
+        Item::Symbol(_) => 3,
+        _ => 4,
+    }
+}
+
+fn insert_item(schematic: &mut Schematic, item: Item) {
+    let rank = section(&item);
+    let at = schematic
+        .items
+        .iter()
+        .rposition(|i| section(i) <= rank)
+        .map_or(0, |i| i + 1);
+    schematic.items.insert(at, item);
+}
+
+fn add_wire(schematic: &mut Schematic, a: Point, b: Point) {
+    if a != b {
+        insert_item(schematic, Item::Wire(Wire::new(a, b)));
+    }
+}
+
+fn remove_wire(schematic: &mut Schematic, wire: &Wire) {
+    if let Some(i) = schematic
+        .items
+        .iter()
+        .position(|item| matches!(item, Item::Wire(w) if w == wire))
+    {
+        schematic.items.remove(i);
+    }
+}
+
+/// Every point the graph knows about: pins, flags and wire endpoints.
+fn occupied(graph: &NetGraph) -> impl Iterator<Item = Point> + '_ {
+    graph.nets.iter().flat_map(|n| n.points.iter().copied())
+}
+
+/// Where the pins of a part fit on `wire` without cutting off anything
+/// attached along it, as close to the middle as the grid allows.
+fn fit_on_wire(wire: &Wire, part: &NewPart, graph: &NetGraph) -> Option<(Placement, Point)> {
+    let (dir, length) = direction(wire.start, wire.end)?;
+    let placement = orient(&part.def, dir).ok()?;
+    let slack = length - placement.length;
+    if slack < 0 {
+        return None;
+    }
+    let along = |p: Point| {
+        let d = sub(p, wire.start);
+        d.x * dir.x + d.y * dir.y
+    };
+    let attached: Vec<i32> = occupied(graph)
+        .filter(|&p| wire.contains(p))
+        .map(along)
+        .collect();
+
+    let mut offsets: Vec<i32> = (0..=slack).step_by(GRID as usize).collect();
+    offsets.sort_by_key(|k| (k - slack / 2).abs());
+    let offset = offsets
+        .into_iter()
+        .find(|&k| !attached.iter().any(|&t| t > k && t < k + placement.length))?;
+    let first_pin = add(wire.start, scale(dir, offset));
+    Some((placement, first_pin))
+}
+
+fn insert_series(
+    schematic: &mut Schematic,
+    library: &SymbolLibrary,
+    part: NewPart,
+    net: &str,
+    wire: Option<[i32; 4]>,
+) -> Result<(), String> {
+    let graph = connectivity::extract(schematic, library);
+    let points = &graph
+        .net(net)
+        .ok_or_else(|| format!("No net named {}", net))?
+        .points;
+    let mut wires: Vec<Wire> = schematic
+        .wires()
+        .filter(|w| points.contains(&w.start))
+        .cloned()
+        .collect();
+    if let Some([x1, y1, x2, y2]) = wire {
+        let (a, b) = (Point::new(x1, y1), Point::new(x2, y2));
+        wires.retain(|w| (w.start, w.end) == (a, b) || (w.start, w.end) == (b, a));
+        if wires.is_empty() {
+            return Err(format!(
+                "No wire {} {} {} {} on net {}",
+                x1, y1, x2, y2, net
+            ));
+        }
+    }
+    wires.sort_by_key(|w| std::cmp::Reverse(direction(w.start, w.end).map_or(0, |(_, l)| l)));
+
+    let (wire, placement, first_pin) = wires
+        .into_iter()
+        .find_map(|w| {
+            let (placement, first_pin) = fit_on_wire(&w, &part, &graph)?;
+            Some((w, placement, first_pin))
+        })
+        .ok_or_else(|| {
+            format!(
+                "No wire on net {} has room for {} without cutting off other connections",
+                net, part.inst_name
+            )
+        })?;
+
+    let dir = direction(wire.start, wire.end).map_or(Point::default(), |(d, _)| d);
+    let second_pin = add(first_pin, scale(dir, placement.length));
+    remove_wire(schematic, &wire);
+    add_wire(schematic, wire.start, first_pin);
+    add_wire(schematic, second_pin, wire.end);
+    insert_item(schematic, Item::Symbol(part.symbol(&placement, first_pin)));
+    Ok(())
+}
+
+fn add_parallel(
+    schematic: &mut Schematic,
+    library: &SymbolLibrary,
+    part: NewPart,
+    component: &str,
+) -> Result<(), String> {
+    let graph = connectivity::extract(schematic, library);
+    let reference = graph
+        .component(component)
+        .ok_or_else(|| format!("No component named {}", component))?;
+    let [p1, p2] = reference.pins.as_slice() else {
+        return Err(format!(
+            "{} has {} pins; parallel parts need a two-pin component",
+            component,
+            reference.pins.len()
+        ));
+    };
+    let (p1, p2) = (p1.at, p2.at);
+    let (dir, length) =
+        direction(p1, p2).ok_or_else(|| format!("The pins of {} are not in line", component))?;
+    let placement = orient(&part.def, dir)?;
+
+    // Try either side of the component, nearest first. The second pins are
+    // joined through the column halfway between the two parts when their
+    // lengths differ, so the wire never runs over either body.
+    let across = Point::new(dir.y.abs(), dir.x.abs());
+    let sides = PARALLEL_SPACINGS
+        .into_iter()
+        .flat_map(|d| [d, -d])
+        .map(|d| scale(across, d));
+    for side in sides {
+        let q1 = add(p1, side);
+        let q2 = add(q1, scale(dir, placement.length));
+        let mut wires = vec![(p1, q1)];
+        if placement.length == length {
+            wires.push((p2, q2));
+        } else {
+            let half = Point::new(side.x / 2, side.y / 2);
+            let (a, b) = (add(p2, half), sub(q2, half));
+            wires.extend([(p2, a), (a, b), (b, q2)]);
+        }
+        if collides(schematic, &graph, &wires, &[q1, q2], &[p1, p2]) {
+            continue;
+        }
+        for (a, b) in wires {
+            add_wire(schematic, a, b);
+        }
+        insert_item(schematic, Item::Symbol(part.symbol(&placement, q1)));
+        return Ok(());
+    }
+    Err(format!(
+        "No room beside {} for {}",
+        component, part.inst_name
+    ))
+}
+
+/// True if the new wires or pins would touch anything already drawn, other
+/// than at the `allowed` points.
+fn collides(
+    schematic: &Schematic,
+    graph: &NetGraph,
+    wires: &[(Point, Point)],
+    pins: &[Point],
+    allowed: &[Point],
+) -> bool {
+    let new_wires: Vec<Wire> = wires.iter().map(|&(a, b)| Wire::new(a, b)).collect();
+    let touches_new = occupied(graph)
+        .filter(|p| !allowed.contains(p))
+        .any(|p| pins.contains(&p) || new_wires.iter().any(|w| w.contains(p)));
+    let new_points = wires
+        .iter()
+        .flat_map(|&(a, b)| [a, b])
+        .chain(pins.iter().copied())
+        .filter(|p| !allowed.contains(p));
+    touches_new
+        || new_points
+            .into_iter()
+            .any(|p| schematic.wires().any(|w| w.contains(p)))
+}
+
+fn remove_component(
+    schematic: &mut Schematic,
+    library: &SymbolLibrary,
+    component: &str,
+    bridge: bool,
+) -> Result<(), String> {
+    let index = schematic
+        .items
+        .iter()
+        .position(|item| matches!(item, Item::Symbol(s) if s.inst_name() == Some(component)))
+        .ok_or_else(|| format!("No component named {}", component))?;
+
+    let mut bridge_pins = None;
+    if bridge {
+        let graph = connectivity::extract(schematic, library);
+        let pins = graph
+            .component(component)
+            .map(|c| c.pins.iter().map(|p| p.at).collect::<Vec<_>>())
+            .unwrap_or_default();
+        match pins.as_slice() {
+            &[a, b] if direction(a, b).is_some() => bridge_pins = Some((a, b)),
+            _ => {
+                return Err(format!(
+                    "Only a two-pin component with its pins in line can be bridged; {} is not",
+                    component
+                ))
+            }
+        }
+    }
+
+    schematic.items.remove(index);
+    if let Some((a, b)) = bridge_pins {
+        add_wire(schematic, a, b);
+    }
+    Ok(())
+}
+
+/// Adds a directive below everything else on the sheet.
+fn add_directive(schematic: &mut Schematic, text: &str) -> Result<(), String> {
+    let body = text.trim().trim_start_matches('!');
+    if body.is_empty() {
+        return Err("The directive is empty".to_string());
+    }
+
+    let points: Vec<Point> = schematic
+        .items
+        .iter()
+        .flat_map(|item| match item {
+            Item::Wire(w) => vec![w.start, w.end],
+            Item::Flag(f) => vec![f.at],
+            Item::Symbol(s) => vec![s.at],
+            Item::Text(t) => vec![t.at],
+            _ => vec![],
+        })
+        .collect();
+    let left = points.iter().map(|p| p.x).min().unwrap_or(0);
+    let bottom = points.iter().map(|p| p.y).max().unwrap_or(0);
+    let at = Point::new(
+        left.div_euclid(GRID) * GRID,
+        (bottom + 6 * GRID).div_euclid(GRID) * GRID,
+    );
+
+    let content = format!("!{}", body.replace('\n', "\\n"));
+    insert_item(schematic, Item::Text(Text::new(at, "Left", 2, content)));
+    Ok(())
+}
+
+fn rename(schematic: &mut Schematic, from: &str, to: &str) -> Result<(), String> {
+    if schematic.symbol(to).is_some() {
+        return Err(format!("{} already exists", to));
+    }
+    find_symbol(schematic, from)?.set_attr("InstName", to);
+    for item in &mut schematic.items {
+        if let Item::Text(t) = item {
+            if t.is_directive() {
+                t.content = replace_word(&t.content, from, to);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Replaces `from` where it stands as a whole word, so renaming `R1` turns
+/// `I(R1)` into `I(R10)` but leaves `R12` alone.
+fn replace_word(text: &str, from: &str, to: &str) -> String {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for (i, _) in text.match_indices(from) {
+        let before = text[..i].chars().next_back();
+        let after = text[i + from.len()..].chars().next();
+        if before.is_some_and(is_word) || after.is_some_and(is_word) {
+            continue;
+        }
+        out.push_str(&text[last..i]);
+        out.push_str(to);
+        last = i + from.len();
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::check_schematic;
+    use serde_json::json;
+    use std::path::Path;
+
+    fn library() -> SymbolLibrary {
+        SymbolLibrary::new(vec![
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("resources/sym")
+        ])
+    }
+
+    fn fixture(name: &str) -> Schematic {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/asc")
+            .join(name);
+        let bytes = std::fs::read(path).unwrap();
+        Schematic::parse(&crate::textfile::decode(&bytes, None).content).unwrap()
+    }
+
+    fn run(schematic: &mut Schematic, op: serde_json::Value) -> Result<(), String> {
+        let operations = parse(&[op]).map_err(|v| v[0].message.clone())?;
+        apply(schematic, &operations, &library()).map_err(|v| v.message)
+    }
+
+    fn net(graph: &NetGraph, component: &str, pin: &str) -> String {
+        let component = graph.component(component).unwrap();
+        let pin = component.pins.iter().find(|p| p.name == pin).unwrap();
+        pin.net.clone()
+    }
+
+    fn windows(schematic: &Schematic, name: &str) -> Vec<(i32, Point, String)> {
+        schematic
+            .symbol(name)
+            .unwrap()
+            .windows()
+            .map(|w| (w.id, w.offset, w.align.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn set_value_changes_the_value_in_place() {
+        let mut schematic = fixture("rc_filter.asc");
+        run(
+            &mut schematic,
+            json!({ "op": "set_value", "component": "R1", "value": "2.2k" }),
+        )
+        .unwrap();
+        let r1 = schematic.symbol("R1").unwrap();
+        assert_eq!(r1.attr("Value"), Some("2.2k"));
+        assert_eq!(r1.windows().count(), 2);
+    }
+
+    #[test]
+    fn insert_series_splits_the_longest_wire() {
+        let mut schematic = fixture("rc_filter.asc");
+        run(
+            &mut schematic,
+            json!({ "op": "insert_series", "symbol": "res", "value": "100", "net": "N001" }),
+        )
+        .unwrap();
+
+        let r2 = schematic.symbol("R2").unwrap();
+        assert_eq!((r2.at, r2.rotation), (Point::new(160, 80), Rotation::R90));
+        assert_eq!(
+            windows(&schematic, "R2"),
+            [
+                (0, Point::new(0, 56), "VBottom".to_string()),
+                (3, Point::new(32, 56), "VTop".to_string()),
+            ]
+        );
+        let graph = connectivity::extract(&schematic, &library());
+        assert_eq!(net(&graph, "R2", "A"), net(&graph, "R1", "B"));
+        assert_eq!(net(&graph, "R2", "B"), net(&graph, "V1", "+"));
+        assert_ne!(net(&graph, "R2", "A"), net(&graph, "R2", "B"));
+        assert_eq!(check_schematic(&schematic, &library()), []);
+    }
+
+    #[test]
+    fn insert_series_turns_a_part_either_way() {
+        let mut schematic = Schematic::parse(
+            "Version 4\nSHEET 1 880 680\nWIRE 0 0 160 0\nFLAG 0 0 a\nFLAG 160 0 b\n",
+        )
+        .unwrap();
+        run(
+            &mut schematic,
+            json!({ "op": "insert_series", "symbol": "res", "value": "1k", "net": "a" }),
+        )
+        .unwrap();
+
+        assert_eq!(schematic.symbol("R1").unwrap().rotation, Rotation::R270);
+        assert_eq!(
+            windows(&schematic, "R1"),
+            [
+                (0, Point::new(32, 56), "VTop".to_string()),
+                (3, Point::new(0, 56), "VBottom".to_string()),
+            ]
+        );
+        let graph = connectivity::extract(&schematic, &library());
+        assert_eq!(net(&graph, "R1", "A"), "a");
+        assert_eq!(net(&graph, "R1", "B"), "b");
+    }
+
+    #[test]
+    fn insert_series_refuses_what_does_not_fit() {
+        let mut schematic = fixture("rc_filter.asc");
+        let before = schematic.clone();
+        let err = run(
+            &mut schematic,
+            json!({ "op": "insert_series", "symbol": "res", "value": "1k", "net": "0" }),
+        )
+        .unwrap_err();
+        assert!(err.contains("No wire on net 0 has room"), "{}", err);
+
+        let err = run(
+            &mut schematic,
+            json!({ "op": "insert_series", "symbol": "res", "value": "1k", "net": "nowhere" }),
+        )
+        .unwrap_err();
+        assert!(err.contains("No net named nowhere"), "{}", err);
+
+        let err = run(
+            &mut schematic,
+            json!({ "op": "insert_series", "symbol": "res", "value": "1k", "net": "N001", "name": "C1" }),
+        )
+        .unwrap_err();
+        assert!(err.contains("C1 already exists"), "{}", err);
+        assert_eq!(schematic, before);
+    }
+
+    #[test]
+    fn add_parallel_across_an_upright_part() {
+        let mut schematic = fixture("rc_filter.asc");
+        run(
+            &mut schematic,
+            json!({ "op": "add_parallel", "component": "C1", "symbol": "cap", "value": "10n" }),
+        )
+        .unwrap();
+
+        assert_eq!(schematic.symbol("C2").unwrap().rotation, Rotation::R0);
+        assert_eq!(windows(&schematic, "C2"), []);
+        let graph = connectivity::extract(&schematic, &library());
+        assert_eq!(net(&graph, "C2", "A"), "out");
+        assert_eq!(net(&graph, "C2", "B"), "0");
+        assert_eq!(check_schematic(&schematic, &library()), []);
+    }
+
+    #[test]
+    fn add_parallel_across_a_part_on_its_side() {
+        let mut schematic = fixture("rc_filter.asc");
+        run(
+            &mut schematic,
+            json!({ "op": "add_parallel", "component": "R1", "symbol": "res", "value": "10k" }),
+        )
+        .unwrap();
+
+        assert_eq!(schematic.symbol("R2").unwrap().rotation, Rotation::R90);
+        assert_eq!(windows(&schematic, "R2").len(), 2);
+        let graph = connectivity::extract(&schematic, &library());
+        assert_eq!(net(&graph, "R2", "A"), net(&graph, "R1", "A"));
+        assert_eq!(net(&graph, "R2", "B"), net(&graph, "R1", "B"));
+        assert_eq!(check_schematic(&schematic, &library()), []);
+    }
+
+    #[test]
+    fn add_parallel_reports_no_room() {
+        // Flags on both sides sit where every candidate wire would run.
+        let mut schematic = Schematic::parse(
+            "Version 4\nSHEET 1 880 680\nFLAG 64 16 a\nFLAG -32 16 b\n\
+             SYMBOL res 0 0 R0\nSYMATTR InstName R1\nSYMATTR Value 1k\n",
+        )
+        .unwrap();
+        let err = run(
+            &mut schematic,
+            json!({ "op": "add_parallel", "component": "R1", "symbol": "res", "value": "1k" }),
+        )
+        .unwrap_err();
+        assert!(err.contains("No room beside R1 for R2"), "{}", err);
+    }
+
+    #[test]
+    fn remove_component_with_and_without_bridge() {
+        let mut schematic = fixture("rc_filter.asc");
+        run(
+            &mut schematic,
+            json!({ "op": "remove_component", "component": "C1" }),
+        )
+        .unwrap();
+        assert!(schematic.symbol("C1").is_none());
+
+        run(
+            &mut schematic,
+            json!({ "op": "remove_component", "component": "R1", "bridge": true }),
+        )
+        .unwrap();
+        let graph = connectivity::extract(&schematic, &library());
+        assert!(graph.component("R1").is_none());
+        assert_eq!(net(&graph, "V1", "+"), "out");
+
+        let mut amp = fixture("inverting_amp.asc");
+        let err = run(
+            &mut amp,
+            json!({ "op": "remove_component", "component": "U1", "bridge": true }),
+        )
+        .unwrap_err();
+        assert!(err.contains("Only a two-pin component"), "{}", err);
+        assert!(amp.symbol("U1").is_some());
+    }
+
+    #[test]
+    fn add_directive_goes_below_the_drawing() {
+        let mut schematic = fixture("rc_filter.asc");
+        run(
+            &mut schematic,
+            json!({ "op": "add_directive", "text": ".meas tran imax MAX I(R1)" }),
+        )
+        .unwrap();
+        let text = schematic.directives().last().unwrap();
+        assert_eq!(text.content, "!.meas tran imax MAX I(R1)");
+        assert_eq!(text.at, Point::new(16, 416));
+
+        let err = run(
+            &mut schematic,
+            json!({ "op": "add_directive", "text": " ! " }),
+        )
+        .unwrap_err();
+        assert!(err.contains("empty"), "{}", err);
+    }
+
+    #[test]
+    fn rename_updates_directives_and_refuses_collisions() {
+        let mut schematic = fixture("rc_filter.asc");
+        run(
+            &mut schematic,
+            json!({ "op": "add_directive", "text": ".meas tran imax MAX I(R1)*R12" }),
+        )
+        .unwrap();
+        run(
+            &mut schematic,
+            json!({ "op": "rename", "from": "R1", "to": "R10" }),
+        )
+        .unwrap();
+        assert!(schematic.symbol("R10").is_some());
+        assert_eq!(
+            schematic.directives().last().unwrap().content,
+            "!.meas tran imax MAX I(R10)*R12"
+        );
+
+        let err = run(
+            &mut schematic,
+            json!({ "op": "rename", "from": "R10", "to": "C1" }),
+        )
+        .unwrap_err();
+        assert!(err.contains("C1 already exists"), "{}", err);
+        let err = run(
+            &mut schematic,
+            json!({ "op": "rename", "from": "R1", "to": "R2" }),
+        )
+        .unwrap_err();
+        assert!(err.contains("No component named R1"), "{}", err);
+    }
+
+    #[test]
+    fn replace_word_leaves_longer_names_alone() {
+        assert_eq!(
+            replace_word("I(R1) V(R12) R1_x R1", "R1", "R9"),
+            "I(R9) V(R12) R1_x R9"
+        );
+    }
+
+    #[test]
+    fn next_inst_name_counts_names_written_with_their_prefix() {
+        let mut schematic = fixture("inverting_amp.asc");
+        assert_eq!(next_inst_name(&schematic, "R"), "R3");
+        assert_eq!(next_inst_name(&schematic, "X"), "U2");
+        assert_eq!(next_inst_name(&schematic, "C"), "C1");
+
+        let mut opamp = Symbol::new("Opamps\\\\opamp2", Point::new(320, 16), Rotation::R0);
+        opamp.set_attr("InstName", "XU3");
+        schematic.items.push(Item::Symbol(opamp));
+        assert_eq!(next_inst_name(&schematic, "X"), "U4");
+    }
+
+    #[test]
+    fn rejects_malformed_operations() {
+        let err = parse(&[json!({ "op": "explode" })]).unwrap_err();
+        assert_eq!(err[0].rule, Rule::MalformedEdit);
+    }
+}
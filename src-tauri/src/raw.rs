@@ -0,0 +1,637 @@
+use serde::Serialize;
+use std::ops::Range;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Variable {
+    pub index: usize,
+    pub name: String,
+    /// `time`, `frequency`, `voltage`, `device_current`, ...
+    pub kind: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Values {
+    Real(Vec<f64>),
+    Complex(Vec<(f64, f64)>),
+}
+
+impl Values {
+    pub fn len(&self) -> usize {
+        match self {
+            Values::Real(v) => v.len(),
+            Values::Complex(v) => v.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Real part, or magnitude for complex data.
+    pub fn magnitude(&self) -> Vec<f64> {
+        match self {
+            Values::Real(v) => v.clone(),
+            Values::Complex(v) => v.iter().map(|(re, im)| re.hypot(*im)).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RawFile {
+    pub title: String,
+    pub date: String,
+    pub plotname: String,
+    pub flags: Vec<String>,
+    pub command: String,
+    pub variables: Vec<Variable>,
+    /// One entry per variable, in header order. Variable 0 is the sweep axis.
+    pub values: Vec<Values>,
+    /// Point ranges of each `.step` run; a single range when not stepped.
+    pub steps: Vec<Range<usize>>,
+}
+
+impl RawFile {
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.flags.iter().any(|f| f.eq_ignore_ascii_case(flag))
+    }
+
+    pub fn is_complex(&self) -> bool {
+        self.has_flag("complex")
+    }
+
+    pub fn points(&self) -> usize {
+        self.values.first().map(Values::len).unwrap_or(0)
+    }
+
+    /// The sweep axis (time, frequency or swept source) as real values.
+    pub fn axis(&self) -> Vec<f64> {
+        self.values
+            .first()
+            .map(Values::magnitude)
+            .unwrap_or_default()
+    }
+
+    pub fn variable(&self, name: &str) -> Option<&Variable> {
+        self.variables
+            .iter()
+            .find(|v| v.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn trace(&self, name: &str) -> Option<&Values> {
+        self.variable(name).and_then(|v| self.values.get(v.index))
+    }
+}
+
+fn is_utf16le(bytes: &[u8]) -> bool {
+    bytes.len() >= 2 && bytes[0] != 0 && bytes[1] == 0
+}
+
+fn decode_utf16le(bytes: &[u8]) -> String {
+    char::decode_utf16(
+        bytes
+            .chunks_exact(2)
+            .map(|unit| u16::from_le_bytes([unit[0], unit[1]])),
+    )
+    .map(|c| c.unwrap_or('\u{FFFD}'))
+    .collect()
+}
+
+/// The section after the header.
+enum Data<'a> {
+    Binary(&'a [u8]),
+    /// `Values:` text, decoded like the header; LTspice writes the whole
+    /// file as UTF-16LE.
+    Ascii(String),
+}
+
+/// Splits the text header from the data section.
+fn split_header(bytes: &[u8]) -> Result<(String, Data<'_>), String> {
+    const MARKERS: [(&str, bool); 2] = [("Binary:", true), ("Values:", false)];
+
+    if is_utf16le(bytes) {
+        let mut header = String::new();
+        for (i, unit) in bytes.chunks_exact(2).enumerate() {
+            let c = u16::from_le_bytes([unit[0], unit[1]]);
+            header.push(char::from_u32(c as u32).unwrap_or('\u{FFFD}'));
+            if c == u16::from(b'\n') {
+                let line = header.trim_end().rsplit('\n').next().unwrap_or("");
+                if let Some(&(_, binary)) = MARKERS.iter().find(|(m, _)| line.trim() == *m) {
+                    let rest = &bytes[(i + 1) * 2..];
+                    let data = if binary {
+                        Data::Binary(rest)
+                    } else {
+                        Data::Ascii(decode_utf16le(rest))
+                    };
+                    return Ok((header, data));
+                }
+            }
+        }
+    } else {
+        let mut start = 0;
+        while let Some(offset) = bytes[start..].iter().position(|&b| b == b'\n') {
+            let end = start + offset;
+            let line = String::from_utf8_lossy(&bytes[start..end]);
+            if let Some(&(_, binary)) = MARKERS.iter().find(|(m, _)| line.trim() == *m) {
+                let header = String::from_utf8_lossy(&bytes[..end]).into_owned();
+                let rest = &bytes[end + 1..];
+                let data = if binary {
+                    Data::Binary(rest)
+                } else {
+                    Data::Ascii(String::from_utf8_lossy(rest).into_owned())
+                };
+                return Ok((header, data));
+            }
+            start = end + 1;
+        }
+    }
+    Err("No 'Binary:' or 'Values:' section found".to_string())
+}
+
+fn read_f64(bytes: &[u8]) -> f64 {
+    f64::from_le_bytes(bytes[..8].try_into().unwrap_or([0; 8]))
+}
+
+fn read_f32(bytes: &[u8]) -> f64 {
+    f32::from_le_bytes(bytes[..4].try_into().unwrap_or([0; 4])) as f64
+}
+
+/// Decodes binary data. LTspice stores the axis as a double and every other
+/// variable as a float32 unless the `double` flag is set; ngspice stores
+/// everything as doubles. `declared_points` is the header's point count; an
+/// LTspice file whose data is exactly that many double rows is read as
+/// doubles. The number of points read comes from the data length, since
+/// LTspice leaves the count stale when a run is aborted.
+fn parse_binary(
+    data: &[u8],
+    vars: usize,
+    complex: bool,
+    all_double: bool,
+    declared_points: usize,
+    fast_access: bool,
+) -> Vec<Values> {
+    if complex {
+        let points = data.len() / (16 * vars);
+        let mut values = vec![Vec::with_capacity(points); vars];
+        for p in 0..points {
+            for (v, column) in values.iter_mut().enumerate() {
+                let at = (p * vars + v) * 16;
+                column.push((read_f64(&data[at..]), read_f64(&data[at + 8..])));
+            }
+        }
+        return values.into_iter().map(Values::Complex).collect();
+    }
+
+    let compressed_row = 8 + 4 * (vars - 1);
+    let double_row = 8 * vars;
+    let compressed = !all_double && declared_points * double_row != data.len();
+    let row = if compressed {
+        compressed_row
+    } else {
+        double_row
+    };
+    let points = data.len() / row;
+    let width = |v: usize| if v == 0 || !compressed { 8 } else { 4 };
+
+    let mut values = vec![Vec::with_capacity(points); vars];
+    if fast_access {
+        // Column-major: every point of variable 0, then variable 1, ...
+        let mut at = 0;
+        for (v, column) in values.iter_mut().enumerate() {
+            for _ in 0..points {
+                column.push(if width(v) == 8 {
+                    read_f64(&data[at..])
+                } else {
+                    read_f32(&data[at..])
+                });
+                at += width(v);
+            }
+        }
+    } else {
+        for p in 0..points {
+            let mut at = p * row;
+            for (v, column) in values.iter_mut().enumerate() {
+                column.push(if width(v) == 8 {
+                    read_f64(&data[at..])
+                } else {
+                    read_f32(&data[at..])
+                });
+                at += width(v);
+            }
+        }
+    }
+    values.into_iter().map(Values::Real).collect()
+}
+
+fn parse_ascii(text: &str, vars: usize, complex: bool) -> Result<Vec<Values>, String> {
+    let mut real = vec![Vec::new(); vars];
+    let mut cplx = vec![Vec::new(); vars];
+    let mut tokens = text.split_whitespace();
+    let number = |s: &str| {
+        s.parse::<f64>()
+            .map_err(|_| format!("Invalid value '{}'", s))
+    };
+
+    // Each point is its index followed by one value per variable.
+    while tokens.next().is_some() {
+        for v in 0..vars {
+            let Some(token) = tokens.next() else {
+                return Err("Truncated Values section".to_string());
+            };
+            if complex {
+                let (re, im) = token.split_once(',').unwrap_or((token, "0"));
+                cplx[v].push((number(re)?, number(im)?));
+            } else {
+                real[v].push(number(token)?);
+            }
+        }
+    }
+
+    Ok(if complex {
+        cplx.into_iter().map(Values::Complex).collect()
+    } else {
+        real.into_iter().map(Values::Real).collect()
+    })
+}
+
+/// Splits a stepped run wherever the axis returns to its starting value.
+fn segment_steps(axis: &[f64], stepped: bool) -> Vec<Range<usize>> {
+    if axis.is_empty() {
+        return vec![];
+    }
+    let mut steps = Vec::new();
+    let mut start = 0;
+    for i in 1..axis.len() {
+        if stepped && axis[i] == axis[0] {
+            steps.push(start..i);
+            start = i;
+        }
+    }
+    steps.push(start..axis.len());
+    steps
+}
+
+pub fn parse(bytes: &[u8]) -> Result<RawFile, String> {
+    let (header, data) = split_header(bytes)?;
+
+    let mut raw = RawFile {
+        title: String::new(),
+        date: String::new(),
+        plotname: String::new(),
+        flags: Vec::new(),
+        command: String::new(),
+        variables: Vec::new(),
+        values: Vec::new(),
+        steps: Vec::new(),
+    };
+    let mut declared_vars = 0;
+    let mut declared_points = 0;
+    let mut in_variables = false;
+
+    for line in header.lines() {
+        let line = line.trim_end_matches('\r');
+        if in_variables && line.starts_with(char::is_whitespace) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() >= 3 {
+                raw.variables.push(Variable {
+                    index: raw.variables.len(),
+                    name: fields[1].to_string(),
+                    kind: fields[2].to_string(),
+                });
+            }
+            continue;
+        }
+        in_variables = false;
+
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim() {
+            "Title" => raw.title = value.to_string(),
+            "Date" => raw.date = value.to_string(),
+            "Plotname" => raw.plotname = value.to_string(),
+            "Flags" => raw.flags = value.split_whitespace().map(String::from).collect(),
+            "Command" => raw.command = value.to_string(),
+            "No. Variables" => {
+                declared_vars = value
+                    .parse()
+                    .map_err(|_| format!("Invalid variable count '{}'", value))?
+            }
+            "No. Points" => {
+                declared_points = value
+                    .parse()
+                    .map_err(|_| format!("Invalid point count '{}'", value))?
+            }
+            "Variables" => in_variables = true,
+            _ => {}
+        }
+    }
+
+    let vars = raw.variables.len();
+    if vars == 0 || vars != declared_vars {
+        return Err(format!(
+            "Header declares {} variables but lists {}",
+            declared_vars, vars
+        ));
+    }
+
+    let complex = raw.is_complex();
+    raw.values = match data {
+        Data::Binary(data) => {
+            // Only LTspice compresses; ngspice's command line is just its version.
+            let all_double = raw.has_flag("double") || !raw.command.contains("LTspice");
+            let fast_access = raw.has_flag("fastaccess");
+            parse_binary(
+                data,
+                vars,
+                complex,
+                all_double,
+                declared_points,
+                fast_access,
+            )
+        }
+        Data::Ascii(text) => parse_ascii(&text, vars, complex)?,
+    };
+
+    // LTspice flips the sign of some time points as a compression marker.
+    if raw.variables[0].kind == "time" {
+        if let Some(Values::Real(time)) = raw.values.first_mut() {
+            time.iter_mut().for_each(|t| *t = t.abs());
+        }
+    }
+    raw.steps = segment_steps(&raw.axis(), raw.has_flag("stepped"));
+    Ok(raw)
+}
+
+pub fn load(path: &std::path::Path) -> Result<RawFile, String> {
+    let bytes =
+        std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    parse(&bytes).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Series {
+    pub x: Vec<f64>,
+    pub y: Vec<f64>,
+    /// Phase in degrees, for complex (AC) data.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phase: Option<Vec<f64>>,
+}
+
+/// Reduces a series to at most `max_points` by keeping the minimum and
+/// maximum of each bucket, so peaks and glitches survive charting.
+pub fn decimate(x: &[f64], y: &[f64], max_points: usize) -> (Vec<f64>, Vec<f64>) {
+    if y.len() <= max_points || max_points < 4 {
+        return (x.to_vec(), y.to_vec());
+    }
+    let buckets = max_points / 2;
+    let mut out_x = Vec::with_capacity(max_points);
+    let mut out_y = Vec::with_capacity(max_points);
+    for b in 0..buckets {
+        let start = b * y.len() / buckets;
+        let end = ((b + 1) * y.len() / buckets).max(start + 1);
+        let slice = &y[start..end];
+        let (mut lo, mut hi) = (0, 0);
+        for (i, v) in slice.iter().enumerate() {
+            if *v < slice[lo] {
+                lo = i;
+            }
+            if *v > slice[hi] {
+                hi = i;
+            }
+        }
+        let (first, second) = if lo <= hi { (lo, hi) } else { (hi, lo) };
+        out_x.push(x[start + first]);
+        out_y.push(y[start + first]);
+        if second != first {
+            out_x.push(x[start + second]);
+            out_y.push(y[start + second]);
+        }
+    }
+    (out_x, out_y)
+}
+
+impl RawFile {
+    /// One decimated series per step for the variable at `index`. Complex
+    /// values are returned as magnitude in dB with the phase alongside.
+    pub fn series(&self, index: usize, max_points: usize) -> Vec<Series> {
+        let axis = self.axis();
+        let Some(values) = self.values.get(index) else {
+            return vec![];
+        };
+        self.steps
+            .iter()
+            .map(|range| {
+                let x = &axis[range.clone()];
+                match values {
+                    Values::Real(v) => {
+                        let (x, y) = decimate(x, &v[range.clone()], max_points);
+                        Series { x, y, phase: None }
+                    }
+                    Values::Complex(v) => {
+                        let v = &v[range.clone()];
+                        let db: Vec<f64> = v
+                            .iter()
+                            .map(|(re, im)| 20.0 * re.hypot(*im).log10())
+                            .collect();
+                        let phase: Vec<f64> = v
+                            .iter()
+                            .map(|(re, im)| im.atan2(*re).to_degrees())
+                            .collect();
+                        // Bode plots are smooth; a stride keeps x shared by
+                        // magnitude and phase.
+                        let stride = v.len().div_ceil(max_points.max(1)).max(1);
+                        Series {
+                            x: x.iter().step_by(stride).copied().collect(),
+                            y: db.into_iter().step_by(stride).collect(),
+                            phase: Some(phase.into_iter().step_by(stride).collect()),
+                        }
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Stats {
+    pub min: f64,
+    pub max: f64,
+    /// Time-weighted RMS; None for complex data, where it has no meaning.
+    pub rms: Option<f64>,
+    pub final_value: f64,
+    /// Axis value after which the trace stays within 2% of its span around
+    /// the final value. None when it is still moving at the end.
+    pub settling_time: Option<f64>,
+}
+
+const SETTLING_BAND: f64 = 0.02;
+
+fn rms(x: &[f64], y: &[f64]) -> f64 {
+    let span = x[x.len() - 1] - x[0];
+    if span <= 0.0 {
+        return (y.iter().map(|v| v * v).sum::<f64>() / y.len() as f64).sqrt();
+    }
+    let area: f64 = x
+        .windows(2)
+        .zip(y.windows(2))
+        .map(|(x, y)| (x[1] - x[0]) * (y[0] * y[0] + y[1] * y[1]) / 2.0)
+        .sum();
+    (area / span).sqrt()
+}
+
+fn settling_time(x: &[f64], y: &[f64], min: f64, max: f64) -> Option<f64> {
+    let last = y.len() - 1;
+    let band = (max - min) * SETTLING_BAND;
+    match y.iter().rposition(|v| (v - y[last]).abs() > band) {
+        None => Some(x[0]),
+        Some(i) if i + 1 < last => Some(x[i + 1]),
+        Some(_) => None,
+    }
+}
+
+impl RawFile {
+    /// Summary statistics per step for the variable at `index`. Complex
+    /// values are measured as magnitude in dB.
+    pub fn stats(&self, index: usize) -> Vec<Stats> {
+        let axis = self.axis();
+        let Some(values) = self.values.get(index) else {
+            return vec![];
+        };
+        let (y, complex) = match values {
+            Values::Real(v) => (v.clone(), false),
+            Values::Complex(v) => (
+                v.iter()
+                    .map(|(re, im)| 20.0 * re.hypot(*im).log10())
+                    .collect(),
+                true,
+            ),
+        };
+        let transient = self.variables[0].kind == "time";
+        self.steps
+            .iter()
+            .filter(|range| !range.is_empty())
+            .map(|range| {
+                let x = &axis[range.clone()];
+                let y = &y[range.clone()];
+                let min = y.iter().copied().fold(f64::INFINITY, f64::min);
+                let max = y.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+                Stats {
+                    min,
+                    max,
+                    rms: (!complex).then(|| rms(x, y)),
+                    final_value: y[y.len() - 1],
+                    settling_time: if transient && !complex {
+                        settling_time(x, y, min, max)
+                    } else {
+                        None
+                    },
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> RawFile {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/raw")
+            .join(name);
+        load(&path).unwrap()
+    }
+
+    fn real(raw: &RawFile, name: &str) -> Vec<f64> {
+        raw.trace(name).unwrap().magnitude()
+    }
+
+    #[test]
+    fn reads_ltspice_compressed() {
+        // 3 points of 16-byte compressed rows is also 2 points of doubles.
+        let raw = fixture("ltspice_compressed.raw");
+        assert_eq!(raw.points(), 3);
+        assert_eq!(raw.axis(), [0.0, 1e-3, 2e-3]);
+        assert_eq!(real(&raw, "V(out)"), [0.0, 0.25, 0.75]);
+        assert_eq!(real(&raw, "I(R1)"), [0.5, 0.375, 0.125]);
+    }
+
+    #[test]
+    fn reads_ltspice_double() {
+        let raw = fixture("ltspice_double.raw");
+        assert_eq!(raw.points(), 2);
+        assert_eq!(real(&raw, "V(out)"), [0.1, 0.3]);
+        assert_eq!(real(&raw, "I(R1)"), [0.2, 0.4]);
+    }
+
+    #[test]
+    fn reads_ltspice_stepped() {
+        let raw = fixture("ltspice_stepped.raw");
+        assert_eq!(raw.steps, [0..3, 3..6]);
+        assert_eq!(real(&raw, "V(out)"), [0.0, 0.5, 1.0, 0.0, 1.0, 2.0]);
+    }
+
+    #[test]
+    fn reads_ngspice_real() {
+        // 48 bytes divide into both 24-byte double and 16-byte compressed rows.
+        let raw = fixture("ngspice_real.raw");
+        assert_eq!(raw.command, "version 42");
+        assert_eq!(raw.points(), 2);
+        assert_eq!(raw.axis(), [0.0, 1e-3]);
+        assert_eq!(real(&raw, "v(out)"), [0.1, 0.3]);
+        assert_eq!(real(&raw, "i(v1)"), [0.2, 0.4]);
+    }
+
+    #[test]
+    fn reads_ngspice_complex() {
+        let raw = fixture("ngspice_complex.raw");
+        assert!(raw.is_complex());
+        assert_eq!(raw.axis(), [1.0, 10.0, 100.0]);
+        assert_eq!(
+            raw.trace("v(out)"),
+            Some(&Values::Complex(vec![(1.0, 0.0), (0.6, -0.4), (0.0, -0.5)]))
+        );
+    }
+
+    #[test]
+    fn reads_ltspice_ascii_real() {
+        // UTF-16LE throughout, including the Values section.
+        let raw = fixture("ltspice_ascii_real.raw");
+        assert_eq!(raw.points(), 3);
+        assert_eq!(raw.axis(), [0.0, 1e-3, 2e-3]);
+        assert_eq!(real(&raw, "V(out)"), [0.0, 0.25, 0.75]);
+        assert_eq!(real(&raw, "I(R1)"), [0.5, 0.375, 0.125]);
+    }
+
+    #[test]
+    fn reads_ltspice_ascii_complex() {
+        let raw = fixture("ltspice_ascii_complex.raw");
+        assert!(raw.is_complex());
+        assert_eq!(raw.axis(), [1.0, 10.0, 100.0]);
+        assert_eq!(
+            raw.trace("V(out)"),
+            Some(&Values::Complex(vec![(1.0, 0.0), (0.6, -0.4), (0.0, -0.5)]))
+        );
+    }
+
+    #[test]
+    fn reads_ngspice_ascii_real() {
+        let raw = fixture("ngspice_ascii_real.raw");
+        assert_eq!(raw.points(), 2);
+        assert_eq!(raw.axis(), [0.0, 1e-3]);
+        assert_eq!(real(&raw, "v(out)"), [0.1, 0.3]);
+        assert_eq!(real(&raw, "i(v1)"), [0.2, 0.4]);
+    }
+
+    #[test]
+    fn reads_ngspice_ascii_complex() {
+        let raw = fixture("ngspice_ascii_complex.raw");
+        assert!(raw.is_complex());
+        assert_eq!(raw.axis(), [1.0, 10.0, 100.0]);
+        assert_eq!(
+            raw.trace("v(out)"),
+            Some(&Values::Complex(vec![(1.0, 0.0), (0.6, -0.4), (0.0, -0.5)]))
+        );
+    }
+}
@@ -0,0 +1,238 @@
+use crate::llm::client::ClientConfig;
+use crate::llm::ProviderConfig;
+use crate::ltspice::WineConfig;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+
+pub const CURRENT_VERSION: u64 = 2;
+
+const SETTINGS_FILE: &str = "settings.json";
+/// Version 1 of the settings, which only held the provider and client.
+const CONNECTION_FILE: &str = "connection.json";
+
+const MAX_RECENT_DIRECTORIES: usize = 10;
+
+/// What happens to edits the model makes in plain chat.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EditPolicy {
+    /// Written right away, with an entry in the edit history.
+    #[default]
+    Apply,
+    /// Held as proposals until accepted.
+    Propose,
+}
+
+/// Which engine runs simulations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SimulatorChoice {
+    /// LTspice when it can be found, otherwise ngspice.
+    #[default]
+    Auto,
+    Ltspice,
+    Ngspice,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub version: u64,
+    /// Chat backend; its `model` is the default model.
+    pub provider: ProviderConfig,
+    pub client: ClientConfig,
+    pub max_tokens: u32,
+    /// Most recent first; the first is reopened on start.
+    pub recent_directories: Vec<String>,
+    pub symbol_library_path: Option<String>,
+    pub ltspice_path: Option<String>,
+    pub wine: WineConfig,
+    pub simulator: SimulatorChoice,
+    pub edit_policy: EditPolicy,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            version: CURRENT_VERSION,
+            provider: ProviderConfig::default(),
+            client: ClientConfig::default(),
+            max_tokens: 16000,
+            recent_directories: Vec::new(),
+            symbol_library_path: None,
+            ltspice_path: None,
+            wine: WineConfig::default(),
+            simulator: SimulatorChoice::default(),
+            edit_policy: EditPolicy::default(),
+        }
+    }
+}
+
+/// Migrations by the version they upgrade from, starting at 1.
+const MIGRATIONS: &[fn(&mut Value)] = &[from_connection];
+
+/// Version 1 is `connection.json`, whose `provider` and `client` keep their
+/// shape; everything added since takes its default.
+fn from_connection(_settings: &mut Value) {}
+
+/// Brings settings of any earlier version up to the current one.
+pub fn migrate(mut settings: Value) -> Value {
+    let version = settings["version"].as_u64().unwrap_or(1).max(1);
+    for migration in MIGRATIONS.iter().skip(version as usize - 1) {
+        migration(&mut settings);
+    }
+    if version < CURRENT_VERSION {
+        settings["version"] = CURRENT_VERSION.into();
+    }
+    settings
+}
+
+/// Merges `patch` into `target`, recursing into objects so a patch can
+/// change one field of a nested setting.
+pub fn merge(target: &mut Value, patch: Value) {
+    match (target, patch) {
+        (Value::Object(target), Value::Object(patch)) => {
+            for (key, value) in patch {
+                merge(target.entry(key).or_insert(Value::Null), value);
+            }
+        }
+        (target, patch) => *target = patch,
+    }
+}
+
+impl Settings {
+    /// Reads the settings, upgrading older files. Without a stored file the
+    /// defaults apply; a stored file that cannot be read is an error, so
+    /// that it is never replaced by defaults.
+    pub fn load(config_dir: &Path) -> Result<Self, String> {
+        for file in [SETTINGS_FILE, CONNECTION_FILE] {
+            let path = config_dir.join(file);
+            let json = match std::fs::read_to_string(&path) {
+                Ok(json) => json,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+            };
+            return serde_json::from_str::<Value>(&json)
+                .and_then(|settings| serde_json::from_value(migrate(settings)))
+                .map_err(|e| format!("{} is corrupt, fix or remove it: {}", path.display(), e));
+        }
+        Ok(Self::default())
+    }
+
+    pub fn save(&self, config_dir: &Path) -> Result<(), String> {
+        std::fs::create_dir_all(config_dir)
+            .map_err(|e| format!("Failed to create config directory: {}", e))?;
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(config_dir.join(SETTINGS_FILE), json)
+            .map_err(|e| format!("Failed to save settings: {}", e))?;
+        let _ = std::fs::remove_file(config_dir.join(CONNECTION_FILE));
+        Ok(())
+    }
+
+    pub fn add_recent_directory(&mut self, path: &str) {
+        self.recent_directories.retain(|p| p != path);
+        self.recent_directories.insert(0, path.to_string());
+        self.recent_directories.truncate(MAX_RECENT_DIRECTORIES);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::ProviderKind;
+    use serde_json::json;
+
+    fn config_dir(name: &str) -> std::path::PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("spicy-settings-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn migrates_a_connection_file() {
+        let connection = json!({
+            "provider": { "kind": "anthropic", "model": "claude" },
+            "client": { "https_proxy": "http://proxy:3128" },
+        });
+        let migrated = migrate(connection.clone());
+        assert_eq!(migrated["version"], CURRENT_VERSION);
+        assert_eq!(migrated["provider"], connection["provider"]);
+
+        let settings: Settings = serde_json::from_value(migrated).unwrap();
+        assert_eq!(settings.provider.kind, ProviderKind::Anthropic);
+        assert_eq!(settings.provider.model.as_deref(), Some("claude"));
+        assert_eq!(
+            settings.client.https_proxy.as_deref(),
+            Some("http://proxy:3128")
+        );
+        assert_eq!(settings.max_tokens, Settings::default().max_tokens);
+        assert_eq!(settings.edit_policy, EditPolicy::Apply);
+    }
+
+    #[test]
+    fn current_settings_are_left_as_they_are() {
+        let stored = json!({ "version": CURRENT_VERSION, "max_tokens": 4000 });
+        assert_eq!(migrate(stored.clone()), stored);
+    }
+
+    #[test]
+    fn merge_changes_only_the_patched_fields() {
+        let mut settings = serde_json::to_value(Settings::default()).unwrap();
+        settings["client"]["headers"] = json!({ "X-Title": "Spicy" });
+        merge(
+            &mut settings,
+            json!({
+                "client": { "read_timeout_secs": 30, "headers": { "HTTP-Referer": "x" } },
+                "simulator": "ngspice",
+            }),
+        );
+
+        assert_eq!(settings["client"]["read_timeout_secs"], 30);
+        assert_eq!(settings["client"]["connect_timeout_secs"], Value::Null);
+        assert_eq!(
+            settings["client"]["headers"],
+            json!({ "X-Title": "Spicy", "HTTP-Referer": "x" })
+        );
+        assert_eq!(settings["max_tokens"], 16000);
+        let settings: Settings = serde_json::from_value(settings).unwrap();
+        assert_eq!(settings.simulator, SimulatorChoice::Ngspice);
+    }
+
+    #[test]
+    fn loads_and_replaces_a_connection_file() {
+        let dir = config_dir("connection");
+        std::fs::write(
+            dir.join(CONNECTION_FILE),
+            r#"{ "provider": { "kind": "openai", "base_url": "http://localhost:8080" } }"#,
+        )
+        .unwrap();
+
+        let settings = Settings::load(&dir).unwrap();
+        assert_eq!(settings.provider.kind, ProviderKind::OpenAi);
+        settings.save(&dir).unwrap();
+        assert!(!dir.join(CONNECTION_FILE).exists());
+        let saved = Settings::load(&dir).unwrap();
+        assert_eq!(saved.version, CURRENT_VERSION);
+        assert_eq!(
+            saved.provider.base_url.as_deref(),
+            Some("http://localhost:8080")
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_settings_are_the_defaults_and_corrupt_ones_an_error() {
+        let dir = config_dir("corrupt");
+        assert_eq!(Settings::load(&dir).unwrap().max_tokens, 16000);
+
+        std::fs::write(dir.join(SETTINGS_FILE), "{ truncated").unwrap();
+        let err = Settings::load(&dir).unwrap_err();
+        assert!(err.contains("settings.json is corrupt"), "{}", err);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,98 @@
+use super::{SimulationJob, SimulationOutcome, Simulator};
+use crate::ltspice::Integration;
+use std::path::Path;
+use std::time::Instant;
+
+/// LTspice run in batch mode through an [`Integration`], writing an ASCII
+/// .raw file.
+pub struct Ltspice {
+    integration: Box<dyn Integration>,
+}
+
+impl Ltspice {
+    pub fn new(integration: Box<dyn Integration>) -> Self {
+        Self { integration }
+    }
+}
+
+/// Moves a file LTspice wrote beside the netlist to where the job wants it.
+fn move_output(written: &Path, wanted: &Path) -> Result<(), String> {
+    if written == wanted || !written.is_file() {
+        return Ok(());
+    }
+    std::fs::rename(written, wanted)
+        .map_err(|e| format!("Failed to move {}: {}", written.display(), e))
+}
+
+impl Simulator for Ltspice {
+    fn name(&self) -> &str {
+        "ltspice"
+    }
+
+    fn run(
+        &self,
+        job: &SimulationJob,
+        on_output: &mut dyn FnMut(&str),
+    ) -> Result<SimulationOutcome, String> {
+        // LTspice always writes its output beside the netlist.
+        let raw_written = job.netlist_path.with_extension("raw");
+        let log_written = job.netlist_path.with_extension("log");
+        for path in [&raw_written, &log_written, &job.raw_path, &job.log_path] {
+            let _ = std::fs::remove_file(path);
+        }
+
+        let started = Instant::now();
+        let exited_ok = self
+            .integration
+            .batch(&job.netlist_path, job.timeout, on_output)?;
+        let raw_exists = raw_written.is_file();
+        move_output(&raw_written, &job.raw_path)?;
+        move_output(&log_written, &job.log_path)?;
+
+        Ok(SimulationOutcome {
+            simulator: self.name().to_string(),
+            success: exited_ok && raw_exists,
+            raw_path: raw_exists.then(|| job.raw_path.clone()),
+            log_path: job.log_path.is_file().then(|| job.log_path.clone()),
+            elapsed_ms: started.elapsed().as_millis() as u64,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::goals;
+    use crate::ltspice::Stub;
+    use std::time::Duration;
+
+    #[test]
+    fn failed_run_does_not_reuse_an_old_log() {
+        let dir = std::env::temp_dir().join(format!("spicy-stale-log-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let job = SimulationJob {
+            netlist_path: dir.join("rc.cir"),
+            raw_path: dir.join("rc.raw"),
+            log_path: dir.join("rc.log"),
+            timeout: Duration::from_secs(5),
+        };
+        let fixture =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/log/ltspice_tran.log");
+        std::fs::copy(&fixture, &job.log_path).unwrap();
+        std::fs::write(&job.netlist_path, "* rc\n.end\n").unwrap();
+        let goal = vec!["vpk < 1".to_string()];
+        let old = crate::logfile::load(&job.log_path).unwrap();
+        assert_eq!(goals::evaluate(&goal, Some(&old))[0].met, Some(true));
+
+        // The stub writes nothing, so the run fails.
+        let outcome = Ltspice::new(Box::new(Stub::default()))
+            .run(&job, &mut |_| {})
+            .unwrap();
+        assert!(!outcome.success);
+        assert_eq!(outcome.log_path, None);
+        assert!(!job.log_path.exists());
+        let report = outcome.log().filter(|_| outcome.success);
+        assert_eq!(goals::evaluate(&goal, report.as_ref())[0].met, Some(false));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,156 @@
+pub mod ltspice;
+pub mod ngspice;
+
+use crate::logfile::{self, SimulationReport};
+use serde::Serialize;
+use std::io::{BufRead, BufReader, Read};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// Input and output locations for one simulator run.
+pub struct SimulationJob {
+    pub netlist_path: PathBuf,
+    pub raw_path: PathBuf,
+    pub log_path: PathBuf,
+    pub timeout: Duration,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SimulationOutcome {
+    pub simulator: String,
+    pub success: bool,
+    pub raw_path: Option<PathBuf>,
+    pub log_path: Option<PathBuf>,
+    pub elapsed_ms: u64,
+}
+
+impl SimulationOutcome {
+    /// The parsed log this run wrote, if it wrote one.
+    pub fn log(&self) -> Option<SimulationReport> {
+        logfile::load(self.log_path.as_deref()?).ok()
+    }
+}
+
+/// The latest run for a schematic, returned to the UI as a handle for
+/// fetching waveforms and the log.
+#[derive(Debug, Clone, Serialize)]
+pub struct SimulationRun {
+    pub id: String,
+    pub file: String,
+    pub netlist_path: PathBuf,
+    #[serde(flatten)]
+    pub outcome: SimulationOutcome,
+}
+
+/// A SPICE engine that can run a netlist to completion.
+pub trait Simulator: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Adapts an LTspice-flavoured deck to this simulator's dialect.
+    fn prepare_netlist(&self, netlist: &str) -> String {
+        netlist.to_string()
+    }
+
+    /// Runs `job`, passing each line the simulator prints to `on_output`.
+    fn run(
+        &self,
+        job: &SimulationJob,
+        on_output: &mut dyn FnMut(&str),
+    ) -> Result<SimulationOutcome, String>;
+}
+
+const ANALYSES: [&str; 6] = [".tran", ".ac", ".dc", ".op", ".noise", ".tf"];
+
+fn is_analysis(line: &str) -> bool {
+    let word = line
+        .split_whitespace()
+        .next()
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    ANALYSES.contains(&word.as_str())
+}
+
+/// Replaces every analysis directive in `netlist` with `analysis`.
+pub fn apply_analysis(netlist: &str, analysis: &str) -> String {
+    let mut lines: Vec<&str> = netlist.lines().filter(|l| !is_analysis(l)).collect();
+    let end = lines
+        .iter()
+        .position(|l| {
+            let l = l.trim().to_ascii_lowercase();
+            l == ".backanno" || l == ".end"
+        })
+        .unwrap_or(lines.len());
+    lines.insert(end, analysis.trim());
+    let mut out = lines.join("\n");
+    out.push('\n');
+    out
+}
+
+/// Searches `PATH` for an executable, trying `.exe` on Windows.
+pub fn find_on_path(name: &str) -> Option<PathBuf> {
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths).find_map(|dir| {
+        let candidate = dir.join(name);
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+        let exe = candidate.with_extension("exe");
+        (cfg!(windows) && exe.is_file()).then_some(exe)
+    })
+}
+
+fn forward_lines(stream: impl Read + Send + 'static, tx: mpsc::Sender<String>) {
+    std::thread::spawn(move || {
+        for line in BufReader::new(stream).lines().map_while(Result::ok) {
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Spawns `command`, streams its stdout and stderr lines to `on_output`, and
+/// kills it once `timeout` has passed. Returns whether it exited successfully.
+pub fn run_streaming(
+    mut command: Command,
+    timeout: Duration,
+    on_output: &mut dyn FnMut(&str),
+) -> Result<bool, String> {
+    let mut child: Child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to start {:?}: {}", command.get_program(), e))?;
+
+    let (tx, rx) = mpsc::channel();
+    if let Some(stdout) = child.stdout.take() {
+        forward_lines(stdout, tx.clone());
+    }
+    if let Some(stderr) = child.stderr.take() {
+        forward_lines(stderr, tx);
+    }
+
+    let started = Instant::now();
+    loop {
+        match rx.recv_timeout(Duration::from_millis(100)) {
+            Ok(line) => on_output(&line),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            // Both pipes closed: the process is exiting.
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                let status = child.wait().map_err(|e| e.to_string())?;
+                return Ok(status.success());
+            }
+        }
+        if started.elapsed() > timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(format!(
+                "Simulation timed out after {} s",
+                timeout.as_secs()
+            ));
+        }
+    }
+}
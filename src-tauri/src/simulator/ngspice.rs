@@ -0,0 +1,76 @@
+use super::{find_on_path, run_streaming, SimulationJob, SimulationOutcome, Simulator};
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Instant;
+
+/// ngspice run in batch mode (`ngspice -b`), writing a binary .raw file.
+pub struct Ngspice {
+    executable: PathBuf,
+}
+
+impl Ngspice {
+    pub fn new(executable: PathBuf) -> Self {
+        Self { executable }
+    }
+
+    pub fn locate() -> Option<Self> {
+        find_on_path("ngspice").map(Self::new)
+    }
+}
+
+impl Simulator for Ngspice {
+    fn name(&self) -> &str {
+        "ngspice"
+    }
+
+    fn prepare_netlist(&self, netlist: &str) -> String {
+        // `.backanno` is LTspice-only, and ngspice does not accept the micro
+        // sign as a scale suffix.
+        netlist
+            .lines()
+            .filter(|l| !l.trim().eq_ignore_ascii_case(".backanno"))
+            .map(|l| l.replace(['µ', 'μ'], "u"))
+            .collect::<Vec<_>>()
+            .join("\n")
+            + "\n"
+    }
+
+    fn run(
+        &self,
+        job: &SimulationJob,
+        on_output: &mut dyn FnMut(&str),
+    ) -> Result<SimulationOutcome, String> {
+        let _ = std::fs::remove_file(&job.raw_path);
+
+        // `-o` would send everything to the log and leave nothing to stream,
+        // so the log is written here from the streamed lines instead.
+        let mut log = File::create(&job.log_path)
+            .map_err(|e| format!("Failed to create {}: {}", job.log_path.display(), e))?;
+        let mut command = Command::new(&self.executable);
+        command
+            .arg("-b")
+            .arg("-r")
+            .arg(&job.raw_path)
+            .arg(&job.netlist_path);
+        if let Some(dir) = job.netlist_path.parent() {
+            command.current_dir(dir);
+        }
+
+        let started = Instant::now();
+        let exited_ok = run_streaming(command, job.timeout, &mut |line| {
+            let _ = writeln!(log, "{}", line);
+            on_output(line);
+        })?;
+        let raw_exists = job.raw_path.is_file();
+
+        Ok(SimulationOutcome {
+            simulator: self.name().to_string(),
+            success: exited_ok && raw_exists,
+            raw_path: raw_exists.then(|| job.raw_path.clone()),
+            log_path: job.log_path.is_file().then(|| job.log_path.clone()),
+            elapsed_ms: started.elapsed().as_millis() as u64,
+        })
+    }
+}
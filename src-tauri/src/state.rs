@@ -1,8 +1,36 @@
-use std::sync::Mutex;
+use crate::commands::proposals::Proposal;
+use crate::keystore::KeyStore;
+use crate::settings::Settings;
+use crate::simulator::SimulationRun;
+use crate::textfile::FormatCache;
+use crate::watcher::DirectoryWatcher;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
 
 pub struct AppState {
     pub working_directory: Mutex<Option<String>>,
+    /// Key of the current provider: the active one from the key store, or
+    /// the provider's environment variable.
     pub api_key: Mutex<String>,
+    /// Persisted in the app config directory; saved by `save_settings`.
+    pub settings: Mutex<Settings>,
+    /// Why the stored settings could not be loaded. While set, the defaults
+    /// in use are never saved over them.
+    pub settings_error: OnceLock<String>,
+    /// Built from the client settings and shared by every chat request.
+    pub http_client: Mutex<reqwest::Client>,
+    /// App config directory, set during setup.
+    pub config_dir: OnceLock<PathBuf>,
+    pub file_formats: FormatCache,
+    /// `sym/` directory shipped in the app's resources, set during setup.
+    pub bundled_symbols: OnceLock<PathBuf>,
+    /// Most recent simulation per schematic, keyed by relative path.
+    pub simulations: Mutex<HashMap<String, SimulationRun>>,
+    /// Edits waiting for review in propose mode, keyed by proposal id.
+    pub proposals: Mutex<HashMap<String, Proposal>>,
+    /// Reports changes in the working directory while it is set.
+    pub watcher: Mutex<Option<DirectoryWatcher>>,
 }
 
 impl AppState {
@@ -11,6 +39,108 @@ impl AppState {
         Self {
             working_directory: Mutex::new(None),
             api_key: Mutex::new(api_key),
+            settings: Mutex::new(Settings::default()),
+            settings_error: OnceLock::new(),
+            http_client: Mutex::new(reqwest::Client::new()),
+            config_dir: OnceLock::new(),
+            file_formats: FormatCache::default(),
+            bundled_symbols: OnceLock::new(),
+            simulations: Mutex::new(HashMap::new()),
+            proposals: Mutex::new(HashMap::new()),
+            watcher: Mutex::new(None),
         }
     }
+
+    /// Restores the saved settings and reopens the last working directory.
+    /// A client that fails to build is replaced by the default one until the
+    /// settings are fixed; settings that cannot be read are replaced by the
+    /// defaults, which are then never saved.
+    pub fn load_settings(&self, config_dir: PathBuf) {
+        let settings = Settings::load(&config_dir).unwrap_or_else(|e| {
+            eprintln!("Using default settings: {}", e);
+            let _ = self.settings_error.set(e);
+            Settings::default()
+        });
+        if let Ok(client) = settings.client.build() {
+            if let Ok(mut http_client) = self.http_client.lock() {
+                *http_client = client;
+            }
+        }
+        let last_directory = settings
+            .recent_directories
+            .first()
+            .filter(|dir| Path::new(dir).is_dir());
+        if let (Some(dir), Ok(mut working_directory)) =
+            (last_directory, self.working_directory.lock())
+        {
+            *working_directory = Some(dir.clone());
+        }
+        if let Ok(mut current) = self.settings.lock() {
+            *current = settings;
+        }
+        let _ = self.config_dir.set(config_dir);
+    }
+
+    pub fn save_settings(&self) -> Result<(), String> {
+        let Some(config_dir) = self.config_dir.get() else {
+            return Ok(());
+        };
+        if let Some(e) = self.settings_error.get() {
+            return Err(format!("Settings were not saved: {}", e));
+        }
+        let settings = self.settings.lock().map_err(|e| e.to_string())?;
+        settings.save(config_dir)
+    }
+
+    pub fn key_store(&self) -> Result<KeyStore, String> {
+        let config_dir = self
+            .config_dir
+            .get()
+            .ok_or("The config directory is not available")?;
+        Ok(KeyStore::new(config_dir))
+    }
+
+    /// Reloads `api_key` after the provider or its stored keys change.
+    pub fn refresh_api_key(&self) -> Result<(), String> {
+        let provider = self
+            .settings
+            .lock()
+            .map_err(|e| e.to_string())?
+            .provider
+            .clone();
+        let stored = match self.config_dir.get() {
+            Some(config_dir) => KeyStore::new(config_dir).active_key(provider.kind)?,
+            None => None,
+        };
+        let key =
+            stored.unwrap_or_else(|| std::env::var(provider.key_variable()).unwrap_or_default());
+        *self.api_key.lock().map_err(|e| e.to_string())? = key;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unreadable_settings_are_not_saved_over() {
+        let dir = std::env::temp_dir().join(format!("spicy-state-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("settings.json"), "{ \"max_tokens\": \"many\" }").unwrap();
+        std::fs::write(dir.join("connection.json"), "{}").unwrap();
+
+        let state = AppState::new();
+        state.load_settings(dir.clone());
+        assert_eq!(state.settings.lock().unwrap().max_tokens, 16000);
+        let err = state.save_settings().unwrap_err();
+        assert!(err.contains("is corrupt"), "{}", err);
+        assert_eq!(
+            std::fs::read_to_string(dir.join("settings.json")).unwrap(),
+            "{ \"max_tokens\": \"many\" }"
+        );
+        assert!(dir.join("connection.json").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
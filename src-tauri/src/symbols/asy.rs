@@ -0,0 +1,92 @@
+use super::{PinDef, SymbolDef};
+use crate::asc::{self, ParseError, ShapeKind};
+
+fn error(line: usize, message: impl Into<String>) -> ParseError {
+    ParseError {
+        line,
+        message: message.into(),
+    }
+}
+
+/// Parses the text of an LTspice `.asy` symbol file.
+///
+/// `PINATTR` lines apply to the `PIN` above them. Pins without a
+/// `SpiceOrder` keep their order of appearance.
+pub fn parse(name: &str, text: &str) -> Result<SymbolDef, ParseError> {
+    let mut def = SymbolDef {
+        name: name.to_string(),
+        ..SymbolDef::default()
+    };
+
+    for (i, raw) in text.lines().enumerate() {
+        let number = i + 1;
+        let raw = raw.trim_end_matches('\r');
+        let mut fields = raw.split_whitespace();
+        let keyword = fields.next().unwrap_or("");
+
+        match keyword {
+            "SymbolType" => def.symbol_type = fields.next().unwrap_or("").to_string(),
+            "LINE" => def
+                .shapes
+                .push(asc::parse_shape(ShapeKind::Line, raw, number)?),
+            "RECTANGLE" => def
+                .shapes
+                .push(asc::parse_shape(ShapeKind::Rectangle, raw, number)?),
+            "CIRCLE" => def
+                .shapes
+                .push(asc::parse_shape(ShapeKind::Circle, raw, number)?),
+            "ARC" => def
+                .shapes
+                .push(asc::parse_shape(ShapeKind::Arc, raw, number)?),
+            "WINDOW" => def.windows.push(asc::parse_window(raw, number)?),
+            "SYMATTR" => {
+                let attr = asc::parse_symattr(raw, number)?;
+                if attr.key == "Prefix" {
+                    def.prefix = attr.value.clone();
+                }
+                def.attrs.push(attr);
+            }
+            "PIN" => {
+                let coords: Vec<i32> = fields
+                    .by_ref()
+                    .take(2)
+                    .map(|f| {
+                        f.parse()
+                            .map_err(|_| error(number, "invalid PIN coordinate"))
+                    })
+                    .collect::<Result<_, _>>()?;
+                if coords.len() != 2 {
+                    return Err(error(number, "PIN needs x and y"));
+                }
+                let order = def.pins.len() as u32 + 1;
+                def.pins.push(PinDef {
+                    name: format!("{}", order),
+                    offset: asc::Point::new(coords[0], coords[1]),
+                    spice_order: order,
+                });
+            }
+            "PINATTR" => {
+                let pin = def
+                    .pins
+                    .last_mut()
+                    .ok_or_else(|| error(number, "PINATTR before any PIN"))?;
+                let key = fields.next().unwrap_or("");
+                let value = fields.collect::<Vec<_>>().join(" ");
+                match key {
+                    "PinName" => pin.name = value,
+                    "SpiceOrder" => {
+                        pin.spice_order = value
+                            .parse()
+                            .map_err(|_| error(number, "invalid SpiceOrder"))?
+                    }
+                    _ => {}
+                }
+            }
+            // Version, TEXT labels and anything newer are not needed for
+            // connectivity or netlisting.
+            _ => {}
+        }
+    }
+
+    Ok(def)
+}
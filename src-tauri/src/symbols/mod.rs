@@ -0,0 +1,178 @@
+pub mod asy;
+
+use crate::asc::{Attr, Point, Shape, Window};
+use crate::textfile;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PinDef {
+    pub name: String,
+    /// Offset from the symbol origin in the R0 orientation.
+    pub offset: Point,
+    pub spice_order: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+pub struct SymbolDef {
+    pub name: String,
+    pub prefix: String,
+    pub pins: Vec<PinDef>,
+    /// `CELL` or `BLOCK`; empty for built-in definitions.
+    pub symbol_type: String,
+    /// Default attributes such as `Value`, `SpiceModel` and `Description`.
+    pub attrs: Vec<Attr>,
+    pub windows: Vec<Window>,
+    pub shapes: Vec<Shape>,
+    pub path: Option<PathBuf>,
+}
+
+impl SymbolDef {
+    pub fn attr(&self, key: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|a| a.key == key)
+            .map(|a| a.value.as_str())
+    }
+}
+
+fn def(name: &str, prefix: &str, pins: &[(&str, i32, i32)]) -> SymbolDef {
+    SymbolDef {
+        name: name.to_string(),
+        prefix: prefix.to_string(),
+        pins: pins
+            .iter()
+            .enumerate()
+            .map(|(i, &(pin, x, y))| PinDef {
+                name: pin.to_string(),
+                offset: Point::new(x, y),
+                spice_order: i as u32 + 1,
+            })
+            .collect(),
+        ..SymbolDef::default()
+    }
+}
+
+/// Pin tables for the LTspice primitives, listed in SpiceOrder. Used when no
+/// `.asy` file for the symbol is found on the search path.
+pub fn builtin(name: &str) -> Option<SymbolDef> {
+    let lower = name.to_ascii_lowercase();
+    Some(match lower.as_str() {
+        "res" | "res2" => def(name, "R", &[("A", 16, 16), ("B", 16, 96)]),
+        "ind" | "ind2" => def(name, "L", &[("A", 16, 16), ("B", 16, 96)]),
+        "cap" | "polcap" => def(name, "C", &[("A", 16, 0), ("B", 16, 64)]),
+        "voltage" => def(name, "V", &[("+", 0, 16), ("-", 0, 96)]),
+        "bv" => def(name, "B", &[("+", 0, 16), ("-", 0, 96)]),
+        "current" => def(name, "I", &[("+", 0, 0), ("-", 0, 80)]),
+        "bi" | "bi2" => def(name, "B", &[("+", 0, 0), ("-", 0, 80)]),
+        "diode" | "schottky" | "zener" | "led" | "varactor" => {
+            def(name, "D", &[("A", 16, 0), ("K", 16, 64)])
+        }
+        "npn" => def(name, "Q", &[("C", 64, 0), ("B", 0, 48), ("E", 64, 96)]),
+        "pnp" => def(name, "Q", &[("C", 64, 96), ("B", 0, 48), ("E", 64, 0)]),
+        "nmos" => def(name, "M", &[("D", 48, 0), ("G", 0, 80), ("S", 48, 96)]),
+        "njf" => def(name, "J", &[("D", 48, 0), ("G", 0, 80), ("S", 48, 96)]),
+        _ => return None,
+    })
+}
+
+/// Finds `name` (which may contain `\\`-separated subdirectories, as in
+/// `Opamps\\opamp2`) below `dir`, matching path components
+/// case-insensitively the way LTspice does on Windows.
+fn find_asy(dir: &Path, name: &str) -> Option<PathBuf> {
+    let mut components: Vec<&str> = name.split(['\\', '/']).filter(|c| !c.is_empty()).collect();
+    let file = format!("{}.asy", components.pop()?);
+    components.push(&file);
+
+    let exact = components.iter().fold(dir.to_path_buf(), |p, c| p.join(c));
+    if exact.is_file() {
+        return Some(exact);
+    }
+
+    let mut path = dir.to_path_buf();
+    for component in components {
+        let entry = std::fs::read_dir(&path).ok()?.flatten().find(|e| {
+            e.file_name()
+                .to_string_lossy()
+                .eq_ignore_ascii_case(component)
+        })?;
+        path = entry.path();
+    }
+    path.is_file().then_some(path)
+}
+
+pub fn load(name: &str, path: &Path) -> Result<SymbolDef, String> {
+    let bytes =
+        std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let text = textfile::decode(&bytes, None).content;
+    let mut def = asy::parse(name, &text)
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+    def.path = Some(path.to_path_buf());
+    Ok(def)
+}
+
+/// Resolves symbol names to pin definitions by searching `.asy` files in
+/// each directory in order, then falling back to the built-in primitives.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolLibrary {
+    dirs: Vec<PathBuf>,
+}
+
+impl SymbolLibrary {
+    pub fn new(dirs: Vec<PathBuf>) -> Self {
+        Self { dirs }
+    }
+
+    pub fn dirs(&self) -> &[PathBuf] {
+        &self.dirs
+    }
+
+    /// The first `.asy` found for `name` wins; if it cannot be read or
+    /// parsed that is an error rather than a reason to use a later one.
+    pub fn lookup(&self, name: &str) -> Result<Option<SymbolDef>, String> {
+        match self.dirs.iter().find_map(|dir| find_asy(dir, name)) {
+            Some(path) => load(name, &path).map(Some),
+            None => Ok(builtin(name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn library_with(name: &str, asy: &str) -> (SymbolLibrary, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("spicy-symbols-{}", name));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("res.asy"), asy).unwrap();
+        (SymbolLibrary::new(vec![dir.clone()]), dir)
+    }
+
+    #[test]
+    fn loads_pins_from_asy() {
+        let (library, dir) = library_with(
+            "valid",
+            "Version 4\nSymbolType CELL\nPIN 0 0 NONE 0\nPINATTR PinName X\nPIN 0 32 NONE 0\n",
+        );
+        let def = library.lookup("res").unwrap().unwrap();
+        assert_eq!(def.pins[0].name, "X");
+        assert_eq!(def.pins[1].offset, Point::new(0, 32));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn reports_malformed_asy_instead_of_using_builtin() {
+        let (library, dir) = library_with("malformed", "Version 4\nPIN 16 sixteen NONE 0\n");
+        let error = library.lookup("res").unwrap_err();
+        assert!(error.contains("res.asy"), "{}", error);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn falls_back_to_builtin_when_missing() {
+        let library = SymbolLibrary::default();
+        let def = library.lookup("voltage").unwrap().unwrap();
+        assert_eq!(def.prefix, "V");
+        assert!(library.lookup("no_such_symbol").unwrap().is_none());
+    }
+}
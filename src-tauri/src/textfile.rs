@@ -0,0 +1,241 @@
+use crate::asc::LineEnding;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Encoding {
+    Utf8,
+    Utf8Bom,
+    /// UTF-16LE without a byte order mark, as LTspice XVII writes it.
+    Utf16Le,
+    Utf16LeBom,
+    Windows1252,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct FileFormat {
+    pub encoding: Encoding,
+    pub line_ending: LineEnding,
+}
+
+impl Default for FileFormat {
+    fn default() -> Self {
+        Self {
+            encoding: Encoding::Utf8,
+            line_ending: LineEnding::Lf,
+        }
+    }
+}
+
+/// Decoded file content with line endings normalized to `\n`.
+pub struct TextFile {
+    pub content: String,
+    pub format: FileFormat,
+}
+
+fn looks_like_utf16le(bytes: &[u8]) -> bool {
+    // Every LTspice file starts with an ASCII keyword, so the high byte of
+    // the first few UTF-16LE code units is zero.
+    let sample = &bytes[..bytes.len().min(16)];
+    sample.len() >= 4
+        && sample.len().is_multiple_of(2)
+        && sample.chunks(2).all(|c| c[0] != 0 && c[1] == 0)
+}
+
+fn decode_utf16le(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+/// Detects the encoding and line ending of `bytes`. `hint` is the format
+/// last seen for the same file and only breaks ties: a pure-ASCII file is
+/// valid in both UTF-8 and Windows-1252, and a file without newlines has no
+/// line ending of its own.
+pub fn decode(bytes: &[u8], hint: Option<FileFormat>) -> TextFile {
+    let (encoding, content) = if let Some(rest) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        (
+            Encoding::Utf8Bom,
+            String::from_utf8_lossy(rest).into_owned(),
+        )
+    } else if let Some(rest) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+        (Encoding::Utf16LeBom, decode_utf16le(rest))
+    } else if looks_like_utf16le(bytes) {
+        (Encoding::Utf16Le, decode_utf16le(bytes))
+    } else if let Ok(text) = std::str::from_utf8(bytes) {
+        let encoding = match hint {
+            Some(h) if h.encoding == Encoding::Windows1252 && bytes.is_ascii() => {
+                Encoding::Windows1252
+            }
+            _ => Encoding::Utf8,
+        };
+        (encoding, text.to_string())
+    } else {
+        let (text, _, _) = encoding_rs::WINDOWS_1252.decode(bytes);
+        (Encoding::Windows1252, text.into_owned())
+    };
+
+    let line_ending = match content.find('\n') {
+        Some(i) if content[..i].ends_with('\r') => LineEnding::CrLf,
+        Some(_) => LineEnding::Lf,
+        None => hint.map(|h| h.line_ending).unwrap_or_default(),
+    };
+
+    TextFile {
+        content: content.replace("\r\n", "\n"),
+        format: FileFormat {
+            encoding,
+            line_ending,
+        },
+    }
+}
+
+/// Encodes `content` (with `\n` line endings) back into `format`.
+pub fn encode(content: &str, format: FileFormat) -> Vec<u8> {
+    let normalized = content.replace("\r\n", "\n");
+    let text = match format.line_ending {
+        LineEnding::Lf => normalized,
+        LineEnding::CrLf => normalized.replace('\n', "\r\n"),
+    };
+
+    match format.encoding {
+        Encoding::Utf8 => text.into_bytes(),
+        Encoding::Utf8Bom => [&[0xEF, 0xBB, 0xBF][..], text.as_bytes()].concat(),
+        Encoding::Utf16Le | Encoding::Utf16LeBom => {
+            let mut bytes = Vec::with_capacity(text.len() * 2 + 2);
+            if format.encoding == Encoding::Utf16LeBom {
+                bytes.extend_from_slice(&[0xFF, 0xFE]);
+            }
+            for unit in text.encode_utf16() {
+                bytes.extend_from_slice(&unit.to_le_bytes());
+            }
+            bytes
+        }
+        Encoding::Windows1252 => {
+            let (bytes, _, _) = encoding_rs::WINDOWS_1252.encode(&text);
+            bytes.into_owned()
+        }
+    }
+}
+
+/// Remembers the on-disk format of every file read through it so that
+/// writes go back out in the same encoding and line ending.
+#[derive(Default)]
+pub struct FormatCache {
+    formats: Mutex<HashMap<PathBuf, FileFormat>>,
+}
+
+impl FormatCache {
+    pub fn read(&self, path: &Path) -> Result<String, String> {
+        let bytes =
+            std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let mut formats = self.formats.lock().map_err(|e| e.to_string())?;
+        let file = decode(&bytes, formats.get(path).copied());
+        formats.insert(path.to_path_buf(), file.format);
+        Ok(file.content)
+    }
+
+    pub fn format_of(&self, path: &Path) -> Result<Option<FileFormat>, String> {
+        let formats = self.formats.lock().map_err(|e| e.to_string())?;
+        Ok(formats.get(path).copied())
+    }
+
+    /// Writes `content` in the format last read from `path`, detecting it
+    /// from the current file if it has not been read yet.
+    pub fn write(&self, path: &Path, content: &str) -> Result<(), String> {
+        let format = match self.format_of(path)? {
+            Some(format) => format,
+            None => match std::fs::read(path) {
+                Ok(bytes) => decode(&bytes, None).format,
+                Err(_) => FileFormat::default(),
+            },
+        };
+        std::fs::write(path, encode(content, format))
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        let mut formats = self.formats.lock().map_err(|e| e.to_string())?;
+        formats.insert(path.to_path_buf(), format);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "Version 4\nSHEET 1 880 680\nSYMATTR Value 10µ\n";
+
+    fn format(encoding: Encoding, line_ending: LineEnding) -> FileFormat {
+        FileFormat {
+            encoding,
+            line_ending,
+        }
+    }
+
+    fn assert_round_trip(bytes: &[u8], expected: FileFormat) {
+        let file = decode(bytes, None);
+        assert_eq!(file.format, expected);
+        assert_eq!(file.content, TEXT);
+        assert_eq!(encode(&file.content, file.format), bytes);
+    }
+
+    fn utf16le(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    #[test]
+    fn round_trips_utf8() {
+        assert_round_trip(TEXT.as_bytes(), format(Encoding::Utf8, LineEnding::Lf));
+    }
+
+    #[test]
+    fn round_trips_utf8_with_bom() {
+        let bytes = [&[0xEF, 0xBB, 0xBF][..], TEXT.as_bytes()].concat();
+        assert_round_trip(&bytes, format(Encoding::Utf8Bom, LineEnding::Lf));
+    }
+
+    #[test]
+    fn round_trips_utf16le_without_bom() {
+        let bytes = utf16le(&TEXT.replace('\n', "\r\n"));
+        assert_round_trip(&bytes, format(Encoding::Utf16Le, LineEnding::CrLf));
+    }
+
+    #[test]
+    fn round_trips_utf16le_with_bom() {
+        let bytes = [&[0xFF, 0xFE][..], &utf16le(TEXT)].concat();
+        assert_round_trip(&bytes, format(Encoding::Utf16LeBom, LineEnding::Lf));
+    }
+
+    #[test]
+    fn falls_back_to_windows1252() {
+        let text = TEXT.replace('\n', "\r\n");
+        let (bytes, _, _) = encoding_rs::WINDOWS_1252.encode(&text);
+        assert!(std::str::from_utf8(&bytes).is_err());
+        assert_round_trip(&bytes, format(Encoding::Windows1252, LineEnding::CrLf));
+    }
+
+    #[test]
+    fn preserves_crlf() {
+        let bytes = TEXT.replace('\n', "\r\n");
+        let file = decode(bytes.as_bytes(), None);
+        assert_eq!(file.format.line_ending, LineEnding::CrLf);
+        assert!(!file.content.contains('\r'));
+        let edited = file.content.replace("10µ", "22µ");
+        assert_eq!(
+            encode(&edited, file.format),
+            bytes.replace("10µ", "22µ").into_bytes()
+        );
+    }
+
+    #[test]
+    fn hint_breaks_ties() {
+        let hint = format(Encoding::Windows1252, LineEnding::CrLf);
+        let file = decode(b"SHEET 1 880 680", Some(hint));
+        assert_eq!(file.format, hint);
+        let file = decode(b"SHEET 1 880 680", None);
+        assert_eq!(file.format, FileFormat::default());
+    }
+}
@@ -0,0 +1,352 @@
+use crate::asc::{Point, Schematic};
+use crate::connectivity;
+use crate::symbols::SymbolLibrary;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Every schematic coordinate the editor produces lies on this grid.
+pub const GRID: i32 = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rule {
+    MalformedEdit,
+    InvalidOperation,
+    InvalidRange,
+    OverlappingEdits,
+    ParseError,
+    OffGrid,
+    DiagonalWire,
+    DuplicateInstName,
+    FloatingPin,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Violation {
+    pub rule: Rule,
+    pub message: String,
+    /// Line number when known: in the original file for range problems, in
+    /// the edited file for parse errors.
+    pub line: Option<usize>,
+}
+
+impl Violation {
+    pub fn new(rule: Rule, message: impl Into<String>) -> Self {
+        Self {
+            rule,
+            message: message.into(),
+            line: None,
+        }
+    }
+}
+
+/// Replaces lines `start..=end` (1-based) with `replacement`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineEdit {
+    pub start: usize,
+    pub end: usize,
+    pub replacement: String,
+}
+
+/// Reads the `edits` array of a model response.
+pub fn parse_edits(edits: &[serde_json::Value]) -> Result<Vec<LineEdit>, Vec<Violation>> {
+    let mut parsed = Vec::new();
+    let mut violations = Vec::new();
+    for (i, e) in edits.iter().enumerate() {
+        match (
+            e["start"].as_u64(),
+            e["end"].as_u64(),
+            e["replacement"].as_str(),
+        ) {
+            (Some(start), Some(end), Some(replacement)) => parsed.push(LineEdit {
+                start: start as usize,
+                end: end as usize,
+                replacement: replacement.to_string(),
+            }),
+            _ => violations.push(Violation::new(
+                Rule::MalformedEdit,
+                format!(
+                    "Edit {} needs numeric start/end and a replacement string",
+                    i + 1
+                ),
+            )),
+        }
+    }
+    if violations.is_empty() {
+        Ok(parsed)
+    } else {
+        Err(violations)
+    }
+}
+
+/// Rejects ranges outside the file, inverted ranges and ranges that share a
+/// line, since the result of those depends on application order.
+pub fn check_ranges(edits: &[LineEdit], line_count: usize) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    for e in edits {
+        if e.start == 0 || e.start > e.end || e.end > line_count {
+            violations.push(Violation {
+                line: Some(e.start),
+                ..Violation::new(
+                    Rule::InvalidRange,
+                    format!(
+                        "Lines {}-{} are not a valid range in a {}-line file",
+                        e.start, e.end, line_count
+                    ),
+                )
+            });
+        }
+    }
+
+    let mut sorted: Vec<&LineEdit> = edits.iter().collect();
+    sorted.sort_by_key(|e| (e.start, e.end));
+    for pair in sorted.windows(2) {
+        if pair[1].start <= pair[0].end {
+            violations.push(Violation {
+                line: Some(pair[1].start),
+                ..Violation::new(
+                    Rule::OverlappingEdits,
+                    format!(
+                        "Lines {}-{} overlap lines {}-{}",
+                        pair[1].start, pair[1].end, pair[0].start, pair[0].end
+                    ),
+                )
+            });
+        }
+    }
+    violations
+}
+
+fn off_grid(p: Point) -> bool {
+    p.x % GRID != 0 || p.y % GRID != 0
+}
+
+/// Checks the drawing rules the prompt gives the model: grid-aligned
+/// coordinates, axis-aligned wires, unique InstNames and no floating pins.
+pub fn check_schematic(schematic: &Schematic, library: &SymbolLibrary) -> Vec<Violation> {
+    let mut violations = Vec::new();
+
+    for wire in schematic.wires() {
+        let line = format!(
+            "WIRE {} {} {} {}",
+            wire.start.x, wire.start.y, wire.end.x, wire.end.y
+        );
+        if off_grid(wire.start) || off_grid(wire.end) {
+            violations.push(Violation::new(
+                Rule::OffGrid,
+                format!("{} is off the {}-unit grid", line, GRID),
+            ));
+        }
+        if !wire.is_axis_aligned() {
+            violations.push(Violation::new(
+                Rule::DiagonalWire,
+                format!("{} is neither horizontal nor vertical", line),
+            ));
+        }
+    }
+    for flag in schematic.flags().filter(|f| off_grid(f.at)) {
+        violations.push(Violation::new(
+            Rule::OffGrid,
+            format!(
+                "FLAG {} {} {} is off the {}-unit grid",
+                flag.at.x, flag.at.y, flag.label, GRID
+            ),
+        ));
+    }
+
+    let mut names: HashMap<&str, usize> = HashMap::new();
+    for symbol in schematic.symbols() {
+        let inst_name = symbol.inst_name().unwrap_or(&symbol.name);
+        if off_grid(symbol.at) {
+            violations.push(Violation::new(
+                Rule::OffGrid,
+                format!(
+                    "{} (SYMBOL {} {} {}) is off the {}-unit grid",
+                    inst_name, symbol.name, symbol.at.x, symbol.at.y, GRID
+                ),
+            ));
+        }
+        if let Some(name) = symbol.inst_name() {
+            *names.entry(name).or_default() += 1;
+        }
+    }
+    let mut duplicates: Vec<_> = names.into_iter().filter(|(_, n)| *n > 1).collect();
+    duplicates.sort();
+    for (name, count) in duplicates {
+        violations.push(Violation::new(
+            Rule::DuplicateInstName,
+            format!("InstName {} is used by {} symbols", name, count),
+        ));
+    }
+
+    let graph = connectivity::extract(schematic, library);
+    for component in &graph.components {
+        for pin in component.pins.iter().filter(|p| !p.connected) {
+            violations.push(Violation::new(
+                Rule::FloatingPin,
+                format!(
+                    "Pin {} of {} at ({}, {}) is not connected to anything",
+                    pin.name, component.inst_name, pin.at.x, pin.at.y
+                ),
+            ));
+        }
+    }
+
+    violations
+}
+
+/// Violations in `after` that `before` did not already have, so that an
+/// edit is only refused for problems it introduces.
+pub fn check_result(before: &str, after: &str, library: &SymbolLibrary) -> Vec<Violation> {
+    let after = match Schematic::parse(after) {
+        Ok(s) => s,
+        Err(e) => {
+            return vec![Violation {
+                line: Some(e.line),
+                ..Violation::new(
+                    Rule::ParseError,
+                    format!("The edited file does not parse: {}", e),
+                )
+            }]
+        }
+    };
+    let existing = Schematic::parse(before)
+        .map(|s| check_schematic(&s, library))
+        .unwrap_or_default();
+    check_schematic(&after, library)
+        .into_iter()
+        .filter(|v| !existing.contains(v))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    const RC_FILTER: &str = include_str!("../tests/fixtures/asc/rc_filter.asc");
+
+    fn library() -> SymbolLibrary {
+        SymbolLibrary::new(vec![
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("resources/sym")
+        ])
+    }
+
+    fn edit(start: usize, end: usize) -> LineEdit {
+        LineEdit {
+            start,
+            end,
+            replacement: String::new(),
+        }
+    }
+
+    fn check(body: &str) -> Vec<Violation> {
+        let text = format!("Version 4\nSHEET 1 880 680\n{}", body);
+        check_schematic(&Schematic::parse(&text).unwrap(), &library())
+    }
+
+    fn rules(violations: &[Violation]) -> Vec<Rule> {
+        violations.iter().map(|v| v.rule).collect()
+    }
+
+    #[test]
+    fn accepts_disjoint_ranges_in_any_order() {
+        assert_eq!(check_ranges(&[edit(4, 5), edit(1, 2), edit(3, 3)], 5), []);
+    }
+
+    #[test]
+    fn rejects_ranges_outside_the_file_or_inverted() {
+        let violations = check_ranges(&[edit(0, 1), edit(3, 2), edit(4, 6)], 5);
+        assert_eq!(rules(&violations), [Rule::InvalidRange; 3]);
+        let lines: Vec<_> = violations.iter().map(|v| v.line).collect();
+        assert_eq!(lines, [Some(0), Some(3), Some(4)]);
+        assert_eq!(
+            violations[2].message,
+            "Lines 4-6 are not a valid range in a 5-line file"
+        );
+    }
+
+    #[test]
+    fn rejects_ranges_sharing_a_line() {
+        let violations = check_ranges(&[edit(4, 5), edit(2, 3), edit(1, 2)], 5);
+        assert_eq!(rules(&violations), [Rule::OverlappingEdits]);
+        assert_eq!(violations[0].message, "Lines 2-3 overlap lines 1-2");
+        assert_eq!(violations[0].line, Some(2));
+    }
+
+    #[test]
+    fn fixture_is_clean() {
+        let schematic = Schematic::parse(RC_FILTER).unwrap();
+        assert_eq!(check_schematic(&schematic, &library()), []);
+    }
+
+    #[test]
+    fn reports_off_grid_points() {
+        let violations = check(
+            "WIRE 0 0 40 0\nFLAG 0 0 a\nFLAG 40 0 b\n\
+             SYMBOL res -8 -16 R0\nSYMATTR InstName R1\n",
+        );
+        let messages: Vec<_> = violations
+            .iter()
+            .filter(|v| v.rule == Rule::OffGrid)
+            .map(|v| v.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "WIRE 0 0 40 0 is off the 16-unit grid",
+                "FLAG 40 0 b is off the 16-unit grid",
+                "R1 (SYMBOL res -8 -16) is off the 16-unit grid",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_diagonal_wires() {
+        let violations = check("WIRE 0 0 32 32\n");
+        assert_eq!(rules(&violations), [Rule::DiagonalWire]);
+        assert_eq!(
+            violations[0].message,
+            "WIRE 0 0 32 32 is neither horizontal nor vertical"
+        );
+    }
+
+    #[test]
+    fn reports_duplicate_inst_names_once() {
+        let violations = check(
+            "WIRE 16 16 96 16\nWIRE 16 96 96 96\n\
+             SYMBOL res 0 0 R0\nSYMATTR InstName R1\n\
+             SYMBOL res 80 0 R0\nSYMATTR InstName R1\n",
+        );
+        assert_eq!(rules(&violations), [Rule::DuplicateInstName]);
+        assert_eq!(violations[0].message, "InstName R1 is used by 2 symbols");
+    }
+
+    #[test]
+    fn reports_floating_pins() {
+        let violations = check("FLAG 16 16 a\nSYMBOL res 0 0 R0\nSYMATTR InstName R1\n");
+        assert_eq!(rules(&violations), [Rule::FloatingPin]);
+        assert_eq!(
+            violations[0].message,
+            "Pin B of R1 at (16, 96) is not connected to anything"
+        );
+    }
+
+    #[test]
+    fn check_result_reports_only_what_the_edit_adds() {
+        let before = format!("{}WIRE 0 0 32 32\n", RC_FILTER);
+        let after = format!("{}SYMBOL res 1600 1600 R0\nSYMATTR InstName R9\n", before);
+        assert_eq!(check_result(&before, &before, &library()), []);
+
+        let violations = check_result(&before, &after, &library());
+        assert_eq!(rules(&violations), [Rule::FloatingPin; 2]);
+        assert!(violations.iter().all(|v| v.message.contains("of R9")));
+    }
+
+    #[test]
+    fn check_result_reports_unparsable_output() {
+        let violations = check_result(RC_FILTER, "Version 4\nWIRE 0 zero 16 0\n", &library());
+        assert_eq!(rules(&violations), [Rule::ParseError]);
+        assert_eq!(violations[0].line, Some(2));
+    }
+}
@@ -0,0 +1,269 @@
+use notify_debouncer_full::notify::event::{ModifyKind, RenameMode};
+use notify_debouncer_full::notify::{EventKind, RecommendedWatcher, RecursiveMode};
+use notify_debouncer_full::{new_debouncer, DebounceEventResult, Debouncer, RecommendedCache};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Files the UI and chat context depend on.
+const WATCHED_EXTENSIONS: [&str; 4] = ["asc", "asy", "raw", "log"];
+
+/// Events arriving within this long of each other are reported together.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// A change to a watched file, with paths relative to the watched directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FileEvent {
+    Created { path: String },
+    Modified { path: String },
+    Deleted { path: String },
+    Renamed { from: String, to: String },
+}
+
+/// `path` relative to `root`, if it is a watched file outside any hidden
+/// directory such as `.spicy`.
+fn relative(root: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+    let hidden = relative
+        .components()
+        .any(|c| c.as_os_str().to_string_lossy().starts_with('.'));
+    let watched = relative
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| WATCHED_EXTENSIONS.iter().any(|w| e.eq_ignore_ascii_case(w)));
+    (watched && !hidden).then(|| relative.to_string_lossy().replace('\\', "/"))
+}
+
+/// Turns one file system event into the file events it means for `root`.
+fn translate(root: &Path, kind: &EventKind, paths: &[PathBuf]) -> Vec<FileEvent> {
+    let each = |make: fn(String) -> FileEvent| -> Vec<FileEvent> {
+        paths
+            .iter()
+            .filter_map(|p| relative(root, p))
+            .map(make)
+            .collect()
+    };
+    let created = |path| FileEvent::Created { path };
+    let deleted = |path| FileEvent::Deleted { path };
+    match kind {
+        EventKind::Create(_) => each(created),
+        EventKind::Remove(_) => each(deleted),
+        EventKind::Modify(ModifyKind::Name(mode)) => match (mode, paths) {
+            (RenameMode::Both, [from, to]) => {
+                match (relative(root, from), relative(root, to)) {
+                    (Some(from), Some(to)) => vec![FileEvent::Renamed { from, to }],
+                    // Renamed to or from a name that is not watched.
+                    (Some(path), None) => vec![FileEvent::Deleted { path }],
+                    (None, Some(path)) => vec![FileEvent::Created { path }],
+                    (None, None) => vec![],
+                }
+            }
+            (RenameMode::From, _) => each(deleted),
+            (RenameMode::To, _) => each(created),
+            _ => paths
+                .iter()
+                .filter_map(|p| {
+                    let path = relative(root, p)?;
+                    Some(if p.exists() {
+                        FileEvent::Created { path }
+                    } else {
+                        FileEvent::Deleted { path }
+                    })
+                })
+                .collect(),
+        },
+        EventKind::Modify(_) => each(|path| FileEvent::Modified { path }),
+        _ => vec![],
+    }
+}
+
+/// Watches a directory tree and reports batches of changes to watched files.
+/// Watching stops when this is dropped.
+pub struct DirectoryWatcher {
+    _debouncer: Debouncer<RecommendedWatcher, RecommendedCache>,
+}
+
+impl DirectoryWatcher {
+    pub fn start(
+        root: PathBuf,
+        on_events: impl Fn(Vec<FileEvent>) + Send + 'static,
+    ) -> Result<Self, String> {
+        // Events carry resolved paths, such as /private/var for /var on
+        // macOS, so the root has to be resolved too for them to match.
+        let root = root
+            .canonicalize()
+            .map_err(|e| format!("Failed to watch {}: {}", root.display(), e))?;
+        let watched_root = root.clone();
+        let mut debouncer = new_debouncer(DEBOUNCE, None, move |result: DebounceEventResult| {
+            let Ok(events) = result else {
+                return;
+            };
+            let mut changes: Vec<FileEvent> = Vec::new();
+            for event in &events {
+                for change in translate(&watched_root, &event.kind, &event.paths) {
+                    if !changes.contains(&change) {
+                        changes.push(change);
+                    }
+                }
+            }
+            if !changes.is_empty() {
+                on_events(changes);
+            }
+        })
+        .map_err(|e| format!("Failed to start file watcher: {}", e))?;
+        debouncer
+            .watch(&root, RecursiveMode::Recursive)
+            .map_err(|e| format!("Failed to watch {}: {}", root.display(), e))?;
+        Ok(Self {
+            _debouncer: debouncer,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify_debouncer_full::notify::event::{CreateKind, DataChange, RemoveKind};
+
+    fn paths(paths: &[&str]) -> Vec<PathBuf> {
+        paths.iter().map(|p| Path::new("/w").join(p)).collect()
+    }
+
+    fn rename(mode: RenameMode, names: &[&str]) -> Vec<FileEvent> {
+        let kind = EventKind::Modify(ModifyKind::Name(mode));
+        translate(Path::new("/w"), &kind, &paths(names))
+    }
+
+    #[test]
+    fn relative_keeps_watched_files_outside_hidden_directories() {
+        let root = Path::new("/w");
+        let relative = |p: &str| relative(root, Path::new(p));
+        assert_eq!(relative("/w/rc.asc").as_deref(), Some("rc.asc"));
+        assert_eq!(relative("/w/sub/RC.ASC").as_deref(), Some("sub/RC.ASC"));
+        assert_eq!(relative("/w/sub/rc.raw").as_deref(), Some("sub/rc.raw"));
+        assert_eq!(relative("/w/.spicy/chats/rc.asc"), None);
+        assert_eq!(relative("/w/sub/.rc.asc"), None);
+        assert_eq!(relative("/w/rc.asc.bak"), None);
+        assert_eq!(relative("/w/rc.net"), None);
+        assert_eq!(relative("/elsewhere/rc.asc"), None);
+    }
+
+    #[test]
+    fn creations_removals_and_changes() {
+        let root = Path::new("/w");
+        let modified = EventKind::Modify(ModifyKind::Data(DataChange::Content));
+        assert_eq!(
+            translate(
+                root,
+                &modified,
+                &paths(&["rc.asc", ".spicy/edits/history.json"])
+            ),
+            [FileEvent::Modified {
+                path: "rc.asc".to_string()
+            }]
+        );
+        assert_eq!(
+            translate(
+                root,
+                &EventKind::Create(CreateKind::File),
+                &paths(&["rc.log"])
+            ),
+            [FileEvent::Created {
+                path: "rc.log".to_string()
+            }]
+        );
+        assert_eq!(
+            translate(
+                root,
+                &EventKind::Remove(RemoveKind::File),
+                &paths(&["rc.raw"])
+            ),
+            [FileEvent::Deleted {
+                path: "rc.raw".to_string()
+            }]
+        );
+        assert_eq!(
+            translate(
+                root,
+                &EventKind::Create(CreateKind::File),
+                &paths(&[".spicy/chats/rc.asc"])
+            ),
+            []
+        );
+    }
+
+    #[test]
+    fn renames_across_the_watched_extensions() {
+        let path = |p: &str| p.to_string();
+        assert_eq!(
+            rename(RenameMode::Both, &["a.asc", "b.asc"]),
+            [FileEvent::Renamed {
+                from: path("a.asc"),
+                to: path("b.asc")
+            }]
+        );
+        // Editors save by writing a temporary file and renaming it over.
+        assert_eq!(
+            rename(RenameMode::Both, &["a.asc.tmp", "a.asc"]),
+            [FileEvent::Created {
+                path: path("a.asc")
+            }]
+        );
+        assert_eq!(
+            rename(RenameMode::Both, &["a.asc", "a.asc.bak"]),
+            [FileEvent::Deleted {
+                path: path("a.asc")
+            }]
+        );
+        assert_eq!(
+            rename(RenameMode::Both, &["a.asc", ".spicy/a.asc"]),
+            [FileEvent::Deleted {
+                path: path("a.asc")
+            }]
+        );
+        assert_eq!(rename(RenameMode::Both, &["a.tmp", "b.tmp"]), []);
+        assert_eq!(
+            rename(RenameMode::From, &["a.asc"]),
+            [FileEvent::Deleted {
+                path: path("a.asc")
+            }]
+        );
+        assert_eq!(
+            rename(RenameMode::To, &["b.asc"]),
+            [FileEvent::Created {
+                path: path("b.asc")
+            }]
+        );
+    }
+
+    #[test]
+    fn unpaired_renames_check_whether_the_file_exists() {
+        let dir = std::env::temp_dir().join(format!("spicy-watch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("here.asc"), "Version 4\n").unwrap();
+
+        let kind = EventKind::Modify(ModifyKind::Name(RenameMode::Any));
+        let events = translate(&dir, &kind, &[dir.join("here.asc"), dir.join("gone.asc")]);
+        assert_eq!(
+            events,
+            [
+                FileEvent::Created {
+                    path: "here.asc".to_string()
+                },
+                FileEvent::Deleted {
+                    path: "gone.asc".to_string()
+                },
+            ]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn start_refuses_a_missing_directory() {
+        let missing = std::env::temp_dir().join(format!("spicy-missing-{}", std::process::id()));
+        let err = DirectoryWatcher::start(missing, |_| {}).err().unwrap();
+        assert!(err.starts_with("Failed to watch"), "{}", err);
+    }
+}
@@ -0,0 +1,289 @@
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+/// How symbolic links under the working directory are treated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Symlinks {
+    /// Followed, as long as they end inside the working directory.
+    Contained,
+    /// Refused for the file itself, so a write cannot land on a link's
+    /// target; directories are followed as with `Contained`.
+    Refused,
+}
+
+/// What a command may reach through a user-supplied path.
+#[derive(Debug, Clone, Copy)]
+pub struct Access {
+    /// Allowed extensions, compared without case.
+    pub extensions: &'static [&'static str],
+    pub symlinks: Symlinks,
+}
+
+/// Schematics the user opens, edits and chats about.
+pub const SCHEMATIC: Access = Access {
+    extensions: &["asc"],
+    symlinks: Symlinks::Contained,
+};
+
+/// Waveforms the user plots.
+pub const RAW: Access = Access {
+    extensions: &["raw"],
+    symlinks: Symlinks::Contained,
+};
+
+/// Simulation logs.
+pub const LOG: Access = Access {
+    extensions: &["log"],
+    symlinks: Symlinks::Contained,
+};
+
+/// Netlists written beside a schematic.
+pub const NETLIST: Access = Access {
+    extensions: &["net", "cir"],
+    symlinks: Symlinks::Refused,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathError {
+    /// Absolute, or climbs out of the working directory with `..` or a link.
+    Outside(String),
+    Symlink(String),
+    Extension {
+        path: String,
+        allowed: &'static [&'static str],
+    },
+    /// Not a single file or directory name.
+    InvalidName(String),
+    Io {
+        path: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathError::Outside(path) => {
+                write!(f, "{} is outside the working directory", path)
+            }
+            PathError::Symlink(path) => write!(f, "{} goes through a symbolic link", path),
+            PathError::Extension { path, allowed } => write!(
+                f,
+                "{} is not an allowed file type (expected .{})",
+                path,
+                allowed.join(", .")
+            ),
+            PathError::InvalidName(name) => write!(f, "Invalid name: {}", name),
+            PathError::Io { path, message } => write!(f, "Failed to resolve {}: {}", path, message),
+        }
+    }
+}
+
+impl From<PathError> for String {
+    fn from(error: PathError) -> Self {
+        error.to_string()
+    }
+}
+
+/// Resolves `path`, relative to the working directory `root`, to an
+/// absolute path that stays inside it. Links are resolved for the part of
+/// the path that exists, so a file about to be created can be resolved too.
+pub fn resolve(root: &str, path: &str, access: &Access) -> Result<PathBuf, PathError> {
+    let relative = Path::new(path);
+    let io_error = |e: std::io::Error| PathError::Io {
+        path: path.to_string(),
+        message: e.to_string(),
+    };
+
+    let mut parts = Vec::new();
+    for component in relative.components() {
+        match component {
+            Component::Normal(part) => parts.push(part),
+            Component::CurDir => {}
+            _ => return Err(PathError::Outside(path.to_string())),
+        }
+    }
+    if parts.is_empty() {
+        return Err(PathError::InvalidName(path.to_string()));
+    }
+
+    let extension = relative.extension().and_then(|e| e.to_str());
+    let allowed = access
+        .extensions
+        .iter()
+        .any(|a| extension.is_some_and(|e| e.eq_ignore_ascii_case(a)));
+    if !allowed {
+        return Err(PathError::Extension {
+            path: path.to_string(),
+            allowed: access.extensions,
+        });
+    }
+
+    let root = Path::new(root).canonicalize().map_err(io_error)?;
+    let mut resolved = root.clone();
+    let mut missing = Vec::new();
+    let last = parts.len() - 1;
+    for (i, part) in parts.into_iter().enumerate() {
+        if !missing.is_empty() {
+            missing.push(part);
+            continue;
+        }
+        let next = resolved.join(part);
+        match next.symlink_metadata() {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                if i == last && access.symlinks == Symlinks::Refused {
+                    return Err(PathError::Symlink(path.to_string()));
+                }
+                resolved = next.canonicalize().map_err(io_error)?;
+                if !resolved.starts_with(&root) {
+                    return Err(PathError::Outside(path.to_string()));
+                }
+            }
+            Ok(_) => resolved = next,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => missing.push(part),
+            Err(e) => return Err(io_error(e)),
+        }
+    }
+    Ok(missing
+        .into_iter()
+        .fold(resolved, |path, part| path.join(part)))
+}
+
+/// `file` with its extension replaced, still relative to the working
+/// directory.
+pub fn with_extension(file: &str, extension: &str) -> String {
+    Path::new(file)
+        .with_extension(extension)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Checks that `name` is a single plain file or directory name, such as an
+/// identifier that becomes part of a path.
+pub fn file_name(name: &str) -> Result<&str, PathError> {
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Ok(name),
+        _ => Err(PathError::InvalidName(name.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A working directory holding `sub/rc.asc`, beside a file outside it.
+    fn workspace(name: &str) -> (PathBuf, String) {
+        let base = std::env::temp_dir().join(format!("spicy-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        let root = base.join("root");
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        std::fs::write(root.join("sub/rc.asc"), "Version 4\n").unwrap();
+        std::fs::write(base.join("outside.asc"), "Version 4\n").unwrap();
+        let root = root.canonicalize().unwrap();
+        let dir = root.to_string_lossy().into_owned();
+        (root, dir)
+    }
+
+    #[test]
+    fn resolves_existing_and_missing_files() {
+        let (root, dir) = workspace("resolve");
+        assert_eq!(
+            resolve(&dir, "sub/rc.asc", &SCHEMATIC).unwrap(),
+            root.join("sub/rc.asc")
+        );
+        assert_eq!(
+            resolve(&dir, "./sub/rc.asc", &SCHEMATIC).unwrap(),
+            root.join("sub/rc.asc")
+        );
+        assert_eq!(
+            resolve(&dir, "new/deeper/filter.asc", &SCHEMATIC).unwrap(),
+            root.join("new/deeper/filter.asc")
+        );
+    }
+
+    #[test]
+    fn refuses_paths_leaving_the_root() {
+        let (_, dir) = workspace("outside");
+        for path in ["../outside.asc", "sub/../../outside.asc", "sub/../rc.asc"] {
+            assert_eq!(
+                resolve(&dir, path, &SCHEMATIC),
+                Err(PathError::Outside(path.to_string()))
+            );
+        }
+        let absolute = format!("{}/sub/rc.asc", dir);
+        assert_eq!(
+            resolve(&dir, &absolute, &SCHEMATIC),
+            Err(PathError::Outside(absolute.clone()))
+        );
+        assert!(matches!(
+            resolve(&dir, "", &SCHEMATIC),
+            Err(PathError::InvalidName(_))
+        ));
+    }
+
+    #[test]
+    fn checks_the_extension() {
+        let (root, dir) = workspace("extension");
+        assert!(matches!(
+            resolve(&dir, "sub/rc.txt", &SCHEMATIC),
+            Err(PathError::Extension { .. })
+        ));
+        assert!(matches!(
+            resolve(&dir, "sub/rc", &SCHEMATIC),
+            Err(PathError::Extension { .. })
+        ));
+        assert_eq!(
+            resolve(&dir, "sub/RC.ASC", &SCHEMATIC).unwrap(),
+            root.join("sub/RC.ASC")
+        );
+        assert_eq!(
+            resolve(&dir, "sub/rc.cir", &NETLIST).unwrap(),
+            root.join("sub/rc.cir")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn follows_links_only_inside_the_root() {
+        use std::os::unix::fs::symlink;
+        let (root, dir) = workspace("links");
+        symlink(root.join("sub"), root.join("linked")).unwrap();
+        symlink(root.join("sub/rc.asc"), root.join("rc.asc")).unwrap();
+        symlink(root.parent().unwrap(), root.join("escape")).unwrap();
+
+        assert_eq!(
+            resolve(&dir, "linked/rc.asc", &SCHEMATIC).unwrap(),
+            root.join("sub/rc.asc")
+        );
+        assert_eq!(
+            resolve(&dir, "escape/outside.asc", &SCHEMATIC),
+            Err(PathError::Outside("escape/outside.asc".to_string()))
+        );
+
+        // Netlists are written, so only their directories may be links.
+        std::fs::write(root.join("sub/rc.net"), "* rc\n").unwrap();
+        symlink(root.join("sub/rc.net"), root.join("rc.net")).unwrap();
+        assert_eq!(
+            resolve(&dir, "linked/rc.net", &NETLIST).unwrap(),
+            root.join("sub/rc.net")
+        );
+        assert_eq!(
+            resolve(&dir, "rc.net", &NETLIST),
+            Err(PathError::Symlink("rc.net".to_string()))
+        );
+    }
+
+    #[test]
+    fn file_names_are_single_components() {
+        assert_eq!(file_name("session-1"), Ok("session-1"));
+        for name in ["", "..", "a/b", "/etc"] {
+            assert!(file_name(name).is_err());
+        }
+    }
+
+    #[test]
+    fn replaces_the_extension() {
+        assert_eq!(with_extension("sub/rc.asc", "raw"), "sub/rc.raw");
+    }
+}